@@ -0,0 +1,559 @@
+use crate::image::{ColorType, ImageData};
+use crate::png::{Chunk, ParseOptions, Png};
+use crate::Result;
+use std::fmt::Display;
+
+/// Ancillary chunks whose payload is deflated, so high entropy is expected.
+const COMPRESSED_TYPES: [&str; 4] = ["zTXt", "iTXt", "iCCP", "fdAT"];
+
+/// One observation, scored 0 (informational) to 100 (near-certain hidden data).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Finding {
+    score: u8,
+    message: String,
+}
+impl Finding {
+    fn new(score: u8, message: String) -> Finding {
+        Finding { score, message }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Report {
+    findings: Vec<Finding>,
+}
+impl Report {
+    /// Findings combined as independent evidence: 1 - Π(1 - score/100), scaled to 0..=100.
+    pub fn score(&self) -> u8 {
+        let clean: f64 = self
+            .findings
+            .iter()
+            .map(|finding| 1.0 - finding.score as f64 / 100.0)
+            .product();
+        ((1.0 - clean) * 100.0).round() as u8
+    }
+    pub fn verdict(&self) -> &'static str {
+        match self.score() {
+            0..=19 => "clean",
+            20..=59 => "suspicious",
+            _ => "likely contains hidden data",
+        }
+    }
+    fn add(&mut self, score: u8, message: String) {
+        self.findings.push(Finding::new(score, message));
+    }
+}
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "[{:>3}] {}", finding.score, finding.message)?;
+        }
+        writeln!(f, "score: {}/100 ({})", self.score(), self.verdict())
+    }
+}
+#[cfg(feature = "serde")]
+impl serde::Serialize for Report {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Report", 3)?;
+        state.serialize_field("score", &self.score())?;
+        state.serialize_field("verdict", self.verdict())?;
+        state.serialize_field("findings", &self.findings)?;
+        state.end()
+    }
+}
+
+/// Triages a PNG file for signs of embedded data.
+pub fn analyze(bytes: &[u8]) -> Result<Report> {
+    let mut report = Report::default();
+    let options = ParseOptions {
+        allow_unknown_critical: true,
+        allow_trailing: true,
+        ..ParseOptions::default()
+    };
+    let (png, diagnostics) = Png::parse_with(bytes, options)?;
+    for diagnostic in diagnostics {
+        report.add(40, diagnostic.to_string());
+    }
+
+    check_chunk_types(png.chunks(), &mut report);
+    check_trailing_data(png.trailing_data(), &mut report);
+    check_idat_layout(png.chunks(), &mut report);
+    check_entropy(png.chunks(), &mut report);
+    check_lsb_planes(&png, &mut report);
+    Ok(report)
+}
+
+fn check_chunk_types(chunks: &[Chunk], report: &mut Report) {
+    for (index, chunk) in chunks.iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        if !chunk_type.is_public() {
+            report.add(
+                40,
+                format!("private chunk type {} at index {}", chunk_type, index),
+            );
+        } else if !chunk_type.is_standard() {
+            report.add(
+                30,
+                format!("non-standard chunk type {} at index {}", chunk_type, index),
+            );
+        }
+    }
+}
+
+fn check_trailing_data(trailing: &[u8], report: &mut Report) {
+    if trailing.is_empty() {
+        return;
+    }
+    let kind = if trailing.starts_with(&Png::STANDARD_HEADER) {
+        ", looks like another PNG"
+    } else if trailing.starts_with(b"PK\x03\x04") {
+        ", looks like a ZIP archive"
+    } else {
+        ""
+    };
+    report.add(
+        70,
+        format!(
+            "{} bytes after IEND (entropy {:.2} bits/byte{})",
+            trailing.len(),
+            entropy(trailing),
+            kind
+        ),
+    );
+}
+
+fn check_idat_layout(chunks: &[Chunk], report: &mut Report) {
+    let positions: Vec<usize> = chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| &chunk.chunk_type().bytes() == b"IDAT")
+        .map(|(index, _)| index)
+        .collect();
+    if positions.windows(2).any(|w| w[1] != w[0] + 1) {
+        report.add(
+            50,
+            "IDAT chunks are interleaved with other chunks".to_string(),
+        );
+    }
+    let lengths: Vec<u32> = positions.iter().map(|&i| chunks[i].length()).collect();
+    if let Some((last, rest)) = lengths.split_last() {
+        let irregular = rest.windows(2).any(|w| w[0] != w[1])
+            || rest.first().is_some_and(|first| last > first)
+            || lengths.contains(&0);
+        if irregular {
+            report.add(
+                20,
+                format!("IDAT is split irregularly: {:?} bytes", lengths),
+            );
+        }
+    }
+}
+
+fn check_entropy(chunks: &[Chunk], report: &mut Report) {
+    for (index, chunk) in chunks.iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        let compressed = COMPRESSED_TYPES
+            .iter()
+            .any(|t| t.as_bytes() == chunk_type.bytes());
+        if chunk_type.is_critical() || compressed || chunk.data().len() < 16 {
+            continue;
+        }
+        // Short payloads cannot reach 8 bits/byte, so compare against what is attainable.
+        let bits = entropy(chunk.data());
+        let attainable = (chunk.data().len().min(256) as f64).log2();
+        if bits / attainable > 0.9 {
+            report.add(
+                50,
+                format!(
+                    "{} at index {} has a high-entropy payload ({:.2} bits/byte over {} bytes)",
+                    chunk_type,
+                    index,
+                    bits,
+                    chunk.data().len()
+                ),
+            );
+        }
+    }
+}
+
+fn check_lsb_planes(png: &Png, report: &mut Report) {
+    let image = match ImageData::decode(png) {
+        Ok(image) => image,
+        Err(e) => {
+            report.add(30, format!("image data could not be decoded: {}", e));
+            return;
+        }
+    };
+    let header = image.header();
+    let names: &[&str] = match header.color_type() {
+        ColorType::Indexed => {
+            report.add(0, "LSB tests skipped for indexed-color image".to_string());
+            return;
+        }
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => &["gray"],
+        ColorType::Rgb | ColorType::Rgba => &["red", "green", "blue"],
+    };
+    if header.bit_depth() < 8 {
+        report.add(0, "LSB tests skipped for sub-byte samples".to_string());
+        return;
+    }
+
+    let channels = header.color_type().channels() as usize;
+    let sample_bytes = header.bit_depth() as usize / 8;
+    for (channel, name) in names.iter().enumerate() {
+        // The least significant byte of each sample carries its LSB plane.
+        let samples: Vec<u8> = image
+            .passes()
+            .iter()
+            .flat_map(|pass| pass.rows())
+            .flat_map(|row| {
+                row.chunks(sample_bytes)
+                    .skip(channel)
+                    .step_by(channels)
+                    .map(|sample| sample[sample_bytes - 1])
+            })
+            .collect();
+
+        if let Some(p) = chi_square(&samples) {
+            let score = if p > 0.95 { 50 } else { 0 };
+            report.add(score, format!("{} chi-square p-value: {:.3}", name, p));
+        }
+        if let Some(rate) = rs_estimate(&samples) {
+            let score = if rate > 0.1 {
+                (rate * 100.0).min(80.0) as u8
+            } else {
+                0
+            };
+            report.add(
+                score,
+                format!("{} RS embedding rate estimate: {:.3}", name, rate),
+            );
+        }
+    }
+}
+
+/// Shannon entropy in bits per byte.
+pub fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / data.len() as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Westfeld–Pfitzmann pairs-of-values test. Values near 1 mean the LSBs look randomized.
+fn chi_square(samples: &[u8]) -> Option<f64> {
+    let mut histogram = [0u64; 256];
+    for &sample in samples {
+        histogram[sample as usize] += 1;
+    }
+    let mut statistic = 0.0;
+    let mut categories = 0;
+    for pair in histogram.chunks(2) {
+        let expected = (pair[0] + pair[1]) as f64 / 2.0;
+        if expected < 5.0 {
+            continue;
+        }
+        statistic += (pair[0] as f64 - expected).powi(2) / expected;
+        categories += 1;
+    }
+    if categories < 2 {
+        return None;
+    }
+    let df = (categories - 1) as f64;
+    Some(1.0 - regularized_gamma(df / 2.0, statistic / 2.0))
+}
+
+/// Regularized lower incomplete gamma function P(a, x).
+fn regularized_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..500 {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term < sum * 1e-12 {
+                break;
+            }
+        }
+        prefix * sum
+    } else {
+        // Lentz's continued fraction for the upper function Q(a, x)
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for n in 1..500 {
+            let an = -(n as f64) * (n as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-12 {
+                break;
+            }
+        }
+        1.0 - prefix * h
+    }
+}
+
+/// Lanczos approximation of ln Γ(x).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let series: f64 = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c / (x + 1.0 + i as f64))
+        .sum();
+    -tmp + (2.5066282746310005 * (1.000000000190015 + series) / x).ln()
+}
+
+/// Applies F1 (flip the LSB) for `1`, F-1 (shifted flip) for `-1` and nothing for `0`.
+fn flip(sample: i16, direction: i8) -> i16 {
+    match direction {
+        1 => sample ^ 1,
+        -1 => ((sample + 1) ^ 1) - 1,
+        _ => sample,
+    }
+}
+
+fn smoothness(group: &[i16]) -> i32 {
+    group.windows(2).map(|w| (w[1] - w[0]).abs() as i32).sum()
+}
+
+/// Fractions of regular and singular groups of four under `mask`.
+fn regular_singular(samples: &[u8], mask: [i8; 4]) -> (f64, f64) {
+    let (mut regular, mut singular, mut total) = (0, 0, 0);
+    for group in samples.chunks_exact(4) {
+        let group: Vec<i16> = group.iter().map(|&s| s as i16).collect();
+        let flipped: Vec<i16> = group
+            .iter()
+            .zip(mask)
+            .map(|(&s, m)| flip(s, m).clamp(0, 255))
+            .collect();
+        let (before, after) = (smoothness(&group), smoothness(&flipped));
+        if after > before {
+            regular += 1;
+        } else if after < before {
+            singular += 1;
+        }
+        total += 1;
+    }
+    (
+        regular as f64 / total as f64,
+        singular as f64 / total as f64,
+    )
+}
+
+/// Fridrich's RS steganalysis: estimated fraction of samples carrying embedded LSBs.
+fn rs_estimate(samples: &[u8]) -> Option<f64> {
+    if samples.len() < 4 * 64 {
+        return None;
+    }
+    let mask = [0, 1, 1, 0];
+    let negative = [0, -1, -1, 0];
+    let inverted: Vec<u8> = samples.iter().map(|s| s ^ 1).collect();
+
+    let (r_m, s_m) = regular_singular(samples, mask);
+    let (r_n, s_n) = regular_singular(samples, negative);
+    let (r_m1, s_m1) = regular_singular(&inverted, mask);
+    let (r_n1, s_n1) = regular_singular(&inverted, negative);
+    let (d0, d1) = (r_m - s_m, r_m1 - s_m1);
+    let (n0, n1) = (r_n - s_n, r_n1 - s_n1);
+
+    let a = 2.0 * (d1 + d0);
+    let b = n0 - n1 - d1 - 3.0 * d0;
+    let c = d0 - n0;
+    let x = if a.abs() < 1e-9 {
+        if b.abs() < 1e-9 {
+            return Some(0.0);
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return Some(0.0);
+        }
+        let roots = [
+            (-b + discriminant.sqrt()) / (2.0 * a),
+            (-b - discriminant.sqrt()) / (2.0 * a),
+        ];
+        if roots[0].abs() < roots[1].abs() {
+            roots[0]
+        } else {
+            roots[1]
+        }
+    };
+    Some((x / (x - 0.5)).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+    use crate::png::ChunkType;
+    use std::str::FromStr;
+
+    fn lcg(state: &mut u32) -> u32 {
+        *state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        (*state >> 16) & 0x7fff
+    }
+
+    /// Smooth waves plus a little noise, the kind of cover LSB tests are calibrated for.
+    fn cover() -> Vec<u8> {
+        let mut state = 7;
+        (0..8192)
+            .map(|i| {
+                let x = i as f64;
+                let wave = 100.0 + 50.0 * (x / 40.0).sin() + 20.0 * (x / 9.0).cos();
+                (wave as u32 + lcg(&mut state) % 3) as u8
+            })
+            .collect()
+    }
+
+    /// Overwrites the LSB of roughly `rate` of the samples with pseudo-random bits.
+    fn embed_lsbs(samples: &[u8], rate: f64) -> Vec<u8> {
+        let mut state = 12345;
+        samples
+            .iter()
+            .map(|&s| {
+                if (lcg(&mut state) as f64 / 32768.0) < rate {
+                    (s & !1) | (lcg(&mut state) & 1) as u8
+                } else {
+                    s
+                }
+            })
+            .collect()
+    }
+
+    fn png_with(extra: Vec<Chunk>, trailing: &[u8]) -> Vec<u8> {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let (end, rest) = png.chunks().split_last().unwrap();
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        for chunk in rest.iter().chain(&extra).chain([end]) {
+            bytes.extend(chunk.as_bytes());
+        }
+        bytes.extend(trailing);
+        bytes
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[7; 100]), 0.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert!((entropy(&all) - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_regularized_gamma() {
+        // P(1, x) = 1 - e^-x
+        assert!((regularized_gamma(1.0, 2.0) - (1.0 - (-2.0f64).exp())).abs() < 1e-9);
+        assert!((regularized_gamma(3.0, 0.5) - 0.0143876).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_chi_square_detects_randomized_lsbs() {
+        assert!(chi_square(&cover()).unwrap() < 0.9);
+        assert!(chi_square(&embed_lsbs(&cover(), 1.0)).unwrap() > 0.95);
+    }
+
+    #[test]
+    fn test_rs_estimate() {
+        assert!(rs_estimate(&cover()).unwrap() < 0.1);
+        let half = rs_estimate(&embed_lsbs(&cover(), 0.5)).unwrap();
+        assert!((0.35..0.65).contains(&half), "{}", half);
+    }
+
+    #[test]
+    fn test_lsb_tests_quiet_on_cover_image() {
+        let report = analyze(&PNG_FILE).unwrap();
+        let lsb_scores: Vec<u8> = report
+            .findings
+            .iter()
+            .filter(|f| f.message.contains("chi-square") || f.message.contains("RS"))
+            .map(|f| f.score)
+            .collect();
+        assert_eq!(lsb_scores, vec![0; 6]);
+    }
+
+    #[test]
+    fn test_flags_private_chunk_and_trailer() {
+        let secret = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hello".to_vec());
+        let bytes = png_with(vec![secret], b"appended data");
+        let report = analyze(&bytes).unwrap();
+        let messages: Vec<&str> = report.findings.iter().map(|f| &f.message[..]).collect();
+        assert!(messages.contains(&"private chunk type ruSt at index 6"));
+        assert!(messages
+            .iter()
+            .any(|m| m.starts_with("13 bytes after IEND")));
+        assert!(report.score() >= 60);
+    }
+
+    #[test]
+    fn test_flags_high_entropy_payload() {
+        let noise: Vec<u8> = (0..=255u8).map(|i| i.wrapping_mul(167)).collect();
+        let chunk = Chunk::new(ChunkType::from_str("tEXt").unwrap(), noise);
+        let report = analyze(&png_with(vec![chunk], &[])).unwrap();
+        assert!(report
+            .findings
+            .iter()
+            .any(|f| f.message.contains("high-entropy")));
+    }
+
+    #[test]
+    fn test_reports_crc_mismatch_and_keeps_going() {
+        let chunk = Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"a\0b".to_vec());
+        let mut bytes = png_with(vec![chunk], b"appended data");
+        let at = bytes.windows(4).position(|w| w == b"tEXt").unwrap() - 4;
+        bytes[at + 11] ^= 0xff;
+        let report = analyze(&bytes).unwrap();
+        let messages: Vec<&str> = report.findings.iter().map(|f| &f.message[..]).collect();
+        assert!(messages
+            .iter()
+            .any(|m| m.starts_with(&format!("offset {} chunk 6 (tEXt): CRC mismatch", at))));
+        assert!(messages
+            .iter()
+            .any(|m| m.starts_with("13 bytes after IEND")));
+    }
+
+    #[test]
+    fn test_combined_score() {
+        let mut report = Report::default();
+        report.add(50, "a".to_string());
+        report.add(50, "b".to_string());
+        assert_eq!(report.score(), 75);
+    }
+}
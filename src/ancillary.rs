@@ -0,0 +1,341 @@
+use crate::chunk::{check_chunk, invalid, u16_at};
+use crate::image::{ColorType, ImageHeader};
+use crate::png::{Chunk, ChunkType};
+use crate::text::{keyword_bytes, latin1_string, split_null};
+use crate::{Error, Result};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// bKGD: the color to show the image against, in the image's own sample format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Background {
+    Gray(u16),
+    Rgb([u16; 3]),
+    Indexed(u8),
+}
+impl Background {
+    /// Decodes bKGD, checking it against the color type, bit depth and palette size.
+    pub fn parse(
+        chunk: &Chunk,
+        header: &ImageHeader,
+        palette_len: Option<usize>,
+    ) -> Result<Background> {
+        check_chunk(chunk, "bKGD", None)?;
+        let data = chunk.data();
+        let background = match (header.color_type(), data.len()) {
+            (ColorType::Grayscale | ColorType::GrayscaleAlpha, 2) => {
+                Background::Gray(u16_at(data, 0))
+            }
+            (ColorType::Rgb | ColorType::Rgba, 6) => {
+                Background::Rgb([u16_at(data, 0), u16_at(data, 2), u16_at(data, 4)])
+            }
+            (ColorType::Indexed, 1) => Background::Indexed(data[0]),
+            (color_type, _) => {
+                return Err(invalid(format!(
+                    "invalid bKGD chunk for {} image",
+                    color_type
+                )))
+            }
+        };
+        match &background {
+            Background::Indexed(index) => {
+                let entries =
+                    palette_len.ok_or_else(|| invalid("bKGD needs a PLTE".to_string()))?;
+                if *index as usize >= entries {
+                    return Err(invalid(format!(
+                        "bKGD index {} is past the {}-entry palette",
+                        index, entries
+                    )));
+                }
+            }
+            Background::Gray(gray) if *gray > header.max_sample() => {
+                return Err(invalid(format!(
+                    "bKGD sample exceeds {}-bit depth",
+                    header.bit_depth()
+                )))
+            }
+            Background::Rgb(rgb) if rgb.iter().any(|&s| s > header.max_sample()) => {
+                return Err(invalid(format!(
+                    "bKGD sample exceeds {}-bit depth",
+                    header.bit_depth()
+                )))
+            }
+            _ => {}
+        }
+        Ok(background)
+    }
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let data = match self {
+            Background::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Background::Rgb(rgb) => rgb.iter().flat_map(|s| s.to_be_bytes()).collect(),
+            Background::Indexed(index) => vec![*index],
+        };
+        Ok(Chunk::new(ChunkType::from_str("bKGD")?, data))
+    }
+}
+impl Display for Background {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Background::Gray(gray) => write!(f, "background gray {}", gray),
+            Background::Rgb([r, g, b]) => write!(f, "background color ({}, {}, {})", r, g, b),
+            Background::Indexed(index) => write!(f, "background palette index {}", index),
+        }
+    }
+}
+
+/// sBIT: how many bits of each channel were significant in the original data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignificantBits {
+    bits: Vec<u8>,
+}
+impl SignificantBits {
+    /// One count per channel; indexed images give red, green and blue of the palette.
+    pub fn new(bits: Vec<u8>, header: &ImageHeader) -> Result<SignificantBits> {
+        let (channels, depth) = match header.color_type() {
+            ColorType::Indexed => (3, 8),
+            color_type => (color_type.channels() as usize, header.bit_depth()),
+        };
+        if bits.len() != channels {
+            return Err(invalid(format!(
+                "sBIT for a {} image needs {} values, found {}",
+                header.color_type(),
+                channels,
+                bits.len()
+            )));
+        }
+        if bits.iter().any(|&b| b == 0 || b > depth) {
+            return Err(invalid(format!(
+                "sBIT values must be between 1 and {}",
+                depth
+            )));
+        }
+        Ok(SignificantBits { bits })
+    }
+    pub fn parse(chunk: &Chunk, header: &ImageHeader) -> Result<SignificantBits> {
+        check_chunk(chunk, "sBIT", None)?;
+        SignificantBits::new(chunk.data().to_vec(), header)
+    }
+}
+impl Display for SignificantBits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bits: Vec<String> = self.bits.iter().map(|b| b.to_string()).collect();
+        write!(f, "significant bits {}", bits.join("/"))
+    }
+}
+
+/// hIST: approximate usage frequency of each palette entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    frequencies: Vec<u16>,
+}
+impl Histogram {
+    pub fn new(frequencies: Vec<u16>, palette_len: usize) -> Result<Histogram> {
+        if frequencies.len() != palette_len {
+            return Err(invalid(format!(
+                "hIST has {} entries but PLTE has {}",
+                frequencies.len(),
+                palette_len
+            )));
+        }
+        Ok(Histogram { frequencies })
+    }
+    pub fn parse(chunk: &Chunk, palette_len: Option<usize>) -> Result<Histogram> {
+        check_chunk(chunk, "hIST", None)?;
+        let palette_len = palette_len.ok_or_else(|| invalid("hIST needs a PLTE".to_string()))?;
+        if !chunk.data().len().is_multiple_of(2) {
+            return Err(invalid("hIST length is odd".to_string()));
+        }
+        let frequencies = chunk.data().chunks(2).map(|f| u16_at(f, 0)).collect();
+        Histogram::new(frequencies, palette_len)
+    }
+    pub fn frequencies(&self) -> &[u16] {
+        &self.frequencies
+    }
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let data = self
+            .frequencies
+            .iter()
+            .flat_map(|f| f.to_be_bytes())
+            .collect();
+        Ok(Chunk::new(ChunkType::from_str("hIST")?, data))
+    }
+}
+impl Display for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unused = self.frequencies.iter().filter(|&&f| f == 0).count();
+        write!(
+            f,
+            "histogram of {} palette entries, {} unused",
+            self.frequencies.len(),
+            unused
+        )
+    }
+}
+
+/// One sPLT entry: an RGBA color at the palette's sample depth and its relative frequency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuggestedColor {
+    pub rgba: [u16; 4],
+    pub frequency: u16,
+}
+
+/// sPLT: a named palette suggested for displays that cannot show the full image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuggestedPalette {
+    name: String,
+    depth: u8,
+    colors: Vec<SuggestedColor>,
+}
+impl SuggestedPalette {
+    pub fn new(name: &str, depth: u8, colors: Vec<SuggestedColor>) -> Result<SuggestedPalette> {
+        keyword_bytes(name)?;
+        if depth != 8 && depth != 16 {
+            return Err(invalid(format!(
+                "sPLT depth must be 8 or 16, found {}",
+                depth
+            )));
+        }
+        if depth == 8 && colors.iter().any(|c| c.rgba.iter().any(|&s| s > 255)) {
+            return Err(invalid("sPLT sample exceeds 8-bit depth".to_string()));
+        }
+        Ok(SuggestedPalette {
+            name: name.to_string(),
+            depth,
+            colors,
+        })
+    }
+}
+impl TryFrom<&Chunk> for SuggestedPalette {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "sPLT", None)?;
+        let (name, rest) = split_null(chunk.data())?;
+        let (&depth, entries) = rest
+            .split_first()
+            .ok_or_else(|| invalid("truncated sPLT chunk".to_string()))?;
+        let sample_size = match depth {
+            8 => 1,
+            16 => 2,
+            _ => {
+                return Err(invalid(format!(
+                    "sPLT depth must be 8 or 16, found {}",
+                    depth
+                )))
+            }
+        };
+        let entry_size = 4 * sample_size + 2;
+        if entries.len() % entry_size != 0 {
+            return Err(invalid("sPLT entries are truncated".to_string()));
+        }
+        let colors = entries
+            .chunks(entry_size)
+            .map(|entry| {
+                let sample = |i: usize| match sample_size {
+                    1 => entry[i] as u16,
+                    _ => u16_at(entry, 2 * i),
+                };
+                SuggestedColor {
+                    rgba: [sample(0), sample(1), sample(2), sample(3)],
+                    frequency: u16_at(entry, 4 * sample_size),
+                }
+            })
+            .collect();
+        SuggestedPalette::new(&latin1_string(name), depth, colors)
+    }
+}
+impl Display for SuggestedPalette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "suggested palette {:?}: {} colors at {}-bit depth",
+            self.name,
+            self.colors.len(),
+            self.depth
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{chunk, image_header};
+
+    #[test]
+    fn test_background_per_color_type() {
+        let rgb = chunk("bKGD", &[0, 255, 0, 128, 0, 0]);
+        assert_eq!(
+            Background::parse(&rgb, &image_header(8, 6), None).unwrap(),
+            Background::Rgb([255, 128, 0])
+        );
+        assert!(Background::parse(&rgb, &image_header(8, 0), None).is_err());
+
+        let gray = Background::Gray(300).to_chunk().unwrap();
+        assert!(Background::parse(&gray, &image_header(16, 4), None).is_ok());
+        assert!(Background::parse(&gray, &image_header(8, 0), None).is_err());
+
+        let index = Background::Indexed(4).to_chunk().unwrap();
+        assert!(Background::parse(&index, &image_header(8, 3), Some(5)).is_ok());
+        assert!(Background::parse(&index, &image_header(8, 3), Some(4)).is_err());
+        assert!(Background::parse(&index, &image_header(8, 3), None).is_err());
+    }
+
+    #[test]
+    fn test_significant_bits() {
+        let bits = SignificantBits::new(vec![5, 6, 5], &image_header(8, 2)).unwrap();
+        let chunk = chunk("sBIT", &[5, 6, 5]);
+        assert_eq!(
+            SignificantBits::parse(&chunk, &image_header(8, 2)).unwrap(),
+            bits
+        );
+        assert_eq!(bits.to_string(), "significant bits 5/6/5");
+        assert!(SignificantBits::parse(&chunk, &image_header(8, 3)).is_ok());
+        assert!(SignificantBits::parse(&chunk, &image_header(8, 6)).is_err());
+        assert!(SignificantBits::new(vec![3], &image_header(2, 0)).is_err());
+    }
+
+    #[test]
+    fn test_histogram_matches_palette() {
+        let histogram = Histogram::new(vec![10, 0, 3], 3).unwrap();
+        let chunk = histogram.to_chunk().unwrap();
+        assert_eq!(Histogram::parse(&chunk, Some(3)).unwrap(), histogram);
+        assert_eq!(
+            histogram.to_string(),
+            "histogram of 3 palette entries, 1 unused"
+        );
+        assert!(Histogram::parse(&chunk, Some(4)).is_err());
+        assert!(Histogram::parse(&chunk, None).is_err());
+    }
+
+    #[test]
+    fn test_suggested_palette_per_depth() {
+        let colors = vec![
+            SuggestedColor {
+                rgba: [255, 0, 0, 255],
+                frequency: 100,
+            },
+            SuggestedColor {
+                rgba: [0, 0, 0, 0],
+                frequency: 1,
+            },
+        ];
+        let narrow = chunk("sPLT", b"web safe\0\x08\xff\0\0\xff\0\x64\0\0\0\0\0\x01");
+        let palette = SuggestedPalette::new("web safe", 8, colors.clone()).unwrap();
+        assert_eq!(SuggestedPalette::try_from(&narrow).unwrap(), palette);
+        let mut wide_data = b"web safe\0\x10".to_vec();
+        for color in &colors {
+            wide_data.extend(color.rgba.iter().flat_map(|s| s.to_be_bytes()));
+            wide_data.extend(color.frequency.to_be_bytes());
+        }
+        let palette = SuggestedPalette::new("web safe", 16, colors).unwrap();
+        assert_eq!(
+            SuggestedPalette::try_from(&chunk("sPLT", &wide_data)).unwrap(),
+            palette
+        );
+        let wide = SuggestedColor {
+            rgba: [256, 0, 0, 0],
+            frequency: 0,
+        };
+        assert!(SuggestedPalette::new("x", 8, vec![wide]).is_err());
+        assert!(SuggestedPalette::try_from(&chunk("sPLT", b"x\0\x08\x01")).is_err());
+    }
+}
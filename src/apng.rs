@@ -0,0 +1,513 @@
+use crate::chunk::{check_chunk, invalid, u16_at, u32_at};
+use crate::png::{Chunk, ChunkType, Png};
+use crate::{Error, Result};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// acTL: how many frames the animation has and how many times to play it (0 = forever).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AnimationControl {
+    num_frames: u32,
+    num_plays: u32,
+}
+impl AnimationControl {
+    pub fn new(num_frames: u32, num_plays: u32) -> Result<AnimationControl> {
+        if num_frames == 0 {
+            return Err(invalid("an animation needs at least one frame".to_string()));
+        }
+        Ok(AnimationControl {
+            num_frames,
+            num_plays,
+        })
+    }
+    pub fn to_chunk(self) -> Result<Chunk> {
+        let mut data = self.num_frames.to_be_bytes().to_vec();
+        data.extend(self.num_plays.to_be_bytes());
+        Ok(Chunk::new(ChunkType::from_str("acTL")?, data))
+    }
+}
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "acTL", Some(8))?;
+        AnimationControl::new(u32_at(chunk.data(), 0), u32_at(chunk.data(), 4))
+    }
+}
+impl Display for AnimationControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} frames, ", self.num_frames)?;
+        match self.num_plays {
+            0 => write!(f, "loops forever"),
+            plays => write!(f, "plays {} times", plays),
+        }
+    }
+}
+
+/// How the frame's region is treated before the next frame is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum DisposeOp {
+    None,
+    Background,
+    Previous,
+}
+
+/// Whether the frame replaces its region or is alpha-composited over it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum BlendOp {
+    Source,
+    Over,
+}
+
+/// fcTL: the region, timing and compositing of one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FrameControl {
+    sequence: u32,
+    width: u32,
+    height: u32,
+    x_offset: u32,
+    y_offset: u32,
+    delay_num: u16,
+    delay_den: u16,
+    dispose_op: DisposeOp,
+    blend_op: BlendOp,
+}
+impl FrameControl {
+    /// A full-canvas frame shown for `delay_ms` milliseconds.
+    pub fn full_frame(sequence: u32, width: u32, height: u32, delay_ms: u16) -> FrameControl {
+        FrameControl {
+            sequence,
+            width,
+            height,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: delay_ms,
+            delay_den: 1000,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        }
+    }
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+    /// Seconds to show the frame; a zero denominator means hundredths.
+    pub fn delay(&self) -> f64 {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+        self.delay_num as f64 / den as f64
+    }
+    pub fn to_chunk(self) -> Result<Chunk> {
+        let mut data = vec![];
+        for value in [
+            self.sequence,
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+        ] {
+            data.extend(value.to_be_bytes());
+        }
+        data.extend(self.delay_num.to_be_bytes());
+        data.extend(self.delay_den.to_be_bytes());
+        data.push(self.dispose_op as u8);
+        data.push(self.blend_op as u8);
+        Ok(Chunk::new(ChunkType::from_str("fcTL")?, data))
+    }
+}
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        check_chunk(chunk, "fcTL", Some(26))?;
+        let dispose_op = match data[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            op => return Err(invalid(format!("invalid fcTL dispose op {}", op))),
+        };
+        let blend_op = match data[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            op => return Err(invalid(format!("invalid fcTL blend op {}", op))),
+        };
+        let control = FrameControl {
+            sequence: u32_at(data, 0),
+            width: u32_at(data, 4),
+            height: u32_at(data, 8),
+            x_offset: u32_at(data, 12),
+            y_offset: u32_at(data, 16),
+            delay_num: u16_at(data, 20),
+            delay_den: u16_at(data, 22),
+            dispose_op,
+            blend_op,
+        };
+        if control.width == 0 || control.height == 0 {
+            return Err(invalid("fcTL frame has zero size".to_string()));
+        }
+        Ok(control)
+    }
+}
+impl Display for FrameControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} at ({}, {}), {:.0} ms, dispose {:?}, blend {:?}",
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+            self.delay() * 1000.0,
+            self.dispose_op,
+            self.blend_op
+        )
+    }
+}
+
+/// One frame: its control chunk and its compressed image data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    control: FrameControl,
+    data: Vec<u8>,
+    /// Whether the frame's data is the IDAT image every decoder shows.
+    is_default_image: bool,
+}
+impl Frame {
+    pub fn control(&self) -> &FrameControl {
+        &self.control
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    pub fn is_default_image(&self) -> bool {
+        self.is_default_image
+    }
+}
+/// The frame control, the length of the compressed data and whether it is the default image.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Frame {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Frame", 3)?;
+        state.serialize_field("control", &self.control)?;
+        state.serialize_field("length", &self.data.len())?;
+        state.serialize_field("default_image", &self.is_default_image)?;
+        state.end()
+    }
+}
+
+/// The frames of an APNG, checked for sequence numbers, counts and bounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Animation {
+    control: AnimationControl,
+    frames: Vec<Frame>,
+}
+impl Animation {
+    /// Returns `None` for a still PNG without acTL.
+    pub fn from_png(png: &Png) -> Result<Option<Animation>> {
+        let header = png.image_header()?;
+        let mut control = None;
+        let mut frames: Vec<Frame> = vec![];
+        let mut next_sequence = 0;
+        let mut seen_idat = false;
+        let mut check_sequence = |sequence: u32, name: &str| {
+            if sequence != next_sequence {
+                return Err(invalid(format!(
+                    "{} has sequence number {}, expected {}",
+                    name, sequence, next_sequence
+                )));
+            }
+            next_sequence += 1;
+            Ok(())
+        };
+
+        for chunk in png.chunks() {
+            match &chunk.chunk_type().bytes() {
+                b"acTL" if seen_idat => return Err(invalid("acTL after IDAT".to_string())),
+                b"acTL" => control = Some(AnimationControl::try_from(chunk)?),
+                b"fcTL" => {
+                    let frame = FrameControl::try_from(chunk)?;
+                    check_sequence(frame.sequence, "fcTL")?;
+                    if frame.x_offset as u64 + frame.width as u64 > header.width() as u64
+                        || frame.y_offset as u64 + frame.height as u64 > header.height() as u64
+                    {
+                        return Err(invalid(format!(
+                            "frame {} extends past the {}x{} canvas",
+                            frames.len(),
+                            header.width(),
+                            header.height()
+                        )));
+                    }
+                    frames.push(Frame {
+                        control: frame,
+                        data: vec![],
+                        is_default_image: !seen_idat,
+                    });
+                }
+                b"IDAT" => {
+                    seen_idat = true;
+                    if let Some(frame) = frames.last_mut().filter(|f| f.is_default_image) {
+                        frame.data.extend(chunk.data());
+                    }
+                }
+                b"fdAT" => {
+                    if chunk.data().len() < 4 {
+                        return Err(invalid("fdAT chunk is truncated".to_string()));
+                    }
+                    check_sequence(u32_at(chunk.data(), 0), "fdAT")?;
+                    match frames.last_mut() {
+                        Some(frame) if !frame.is_default_image => {
+                            frame.data.extend(&chunk.data()[4..])
+                        }
+                        _ => return Err(invalid("fdAT without a preceding fcTL".to_string())),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let Some(control) = control else {
+            return Ok(None);
+        };
+        if frames.len() != control.num_frames as usize {
+            return Err(invalid(format!(
+                "acTL declares {} frames but {} were found",
+                control.num_frames,
+                frames.len()
+            )));
+        }
+        if let Some(first) = frames.first().filter(|f| f.is_default_image) {
+            let c = &first.control;
+            if c.x_offset != 0
+                || c.y_offset != 0
+                || c.width != header.width()
+                || c.height != header.height()
+            {
+                return Err(invalid(
+                    "the default image's frame must cover the whole canvas".to_string(),
+                ));
+            }
+        }
+        if let Some(index) = frames.iter().position(|f| f.data.is_empty()) {
+            return Err(invalid(format!("frame {} has no image data", index)));
+        }
+        Ok(Some(Animation { control, frames }))
+    }
+
+    pub fn control(&self) -> &AnimationControl {
+        &self.control
+    }
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Builds a standalone PNG for one frame: the source's IHDR resized to the frame, the
+    /// chunks it has before IDAT (palette, transparency, color), and the frame's data.
+    pub fn frame_png(&self, source: &Png, index: usize) -> Result<Png> {
+        let frame = self
+            .frames
+            .get(index)
+            .ok_or_else(|| invalid(format!("no frame {}", index)))?;
+        source.image_header()?;
+        let mut chunks = vec![];
+        for chunk in source.chunks() {
+            match &chunk.chunk_type().bytes() {
+                b"IHDR" => {
+                    let mut data = chunk.data().to_vec();
+                    data[0..4].copy_from_slice(&frame.control.width.to_be_bytes());
+                    data[4..8].copy_from_slice(&frame.control.height.to_be_bytes());
+                    chunks.push(Chunk::new(chunk.chunk_type().clone(), data));
+                }
+                b"IDAT" | b"fdAT" | b"IEND" => break,
+                b"acTL" | b"fcTL" => {}
+                _ => chunks.push(chunk.clone()),
+            }
+        }
+        chunks.push(Chunk::new(ChunkType::from_str("IDAT")?, frame.data.clone()));
+        chunks.push(Chunk::new(ChunkType::from_str("IEND")?, vec![]));
+        Ok(Png::from_chunks(chunks))
+    }
+
+    /// Builds an APNG from still frames of identical format, each shown for its delay in
+    /// milliseconds. The first frame is also the default image.
+    pub fn assemble(frames: &[Png], delays_ms: &[u16], num_plays: u32) -> Result<Png> {
+        let first = frames
+            .first()
+            .ok_or_else(|| invalid("no frames to assemble".to_string()))?;
+        for (index, frame) in frames.iter().enumerate() {
+            frame
+                .image_header()
+                .map_err(|e| invalid(format!("frame {}: {}", index, e)))?;
+        }
+        let header_chunk = &first.chunks()[0];
+        let header = first.image_header()?;
+        let palette = first.palette()?;
+        for (index, frame) in frames.iter().enumerate().skip(1) {
+            if frame.chunks()[0].as_bytes() != header_chunk.as_bytes() {
+                return Err(invalid(format!(
+                    "frame {} has a different IHDR from frame 0",
+                    index
+                )));
+            }
+            if frame.palette()? != palette {
+                return Err(invalid(format!(
+                    "frame {} has a different PLTE from frame 0",
+                    index
+                )));
+            }
+        }
+        let delay = |index: usize| match delays_ms {
+            [single] => *single,
+            delays => delays[index],
+        };
+        if delays_ms.len() != 1 && delays_ms.len() != frames.len() {
+            return Err(invalid(format!(
+                "give one delay or one per frame ({}), not {}",
+                frames.len(),
+                delays_ms.len()
+            )));
+        }
+
+        let mut chunks = vec![header_chunk.clone()];
+        chunks.push(AnimationControl::new(frames.len() as u32, num_plays)?.to_chunk()?);
+        for chunk in &first.chunks()[1..] {
+            match &chunk.chunk_type().bytes() {
+                b"IDAT" | b"IEND" => break,
+                b"acTL" | b"fcTL" | b"fdAT" => {}
+                _ => chunks.push(chunk.clone()),
+            }
+        }
+        let mut sequence = 0;
+        for (index, frame) in frames.iter().enumerate() {
+            let control =
+                FrameControl::full_frame(sequence, header.width(), header.height(), delay(index));
+            chunks.push(control.to_chunk()?);
+            sequence += 1;
+            if index == 0 {
+                chunks.push(Chunk::new(ChunkType::from_str("IDAT")?, frame.image_data()));
+            } else {
+                let mut data = sequence.to_be_bytes().to_vec();
+                data.extend(frame.image_data());
+                chunks.push(Chunk::new(ChunkType::from_str("fdAT")?, data));
+                sequence += 1;
+            }
+        }
+        chunks.push(Chunk::new(ChunkType::from_str("IEND")?, vec![]));
+        Ok(Png::from_chunks(chunks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+
+    fn two_frames() -> Png {
+        let frame = Png::try_from(&PNG_FILE[..]).unwrap();
+        let again = Png::try_from(&PNG_FILE[..]).unwrap();
+        Animation::assemble(&[frame, again], &[100, 250], 0).unwrap()
+    }
+
+    fn renumber_fdat(png: &Png, sequence: u32) -> Png {
+        let chunks = png
+            .chunks()
+            .iter()
+            .map(|chunk| match &chunk.chunk_type().bytes() {
+                b"fdAT" => {
+                    let mut data = chunk.data().to_vec();
+                    data[..4].copy_from_slice(&sequence.to_be_bytes());
+                    Chunk::new(chunk.chunk_type().clone(), data)
+                }
+                _ => chunk.clone(),
+            })
+            .collect();
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_assemble_and_parse() {
+        let apng = two_frames();
+        let animation = Animation::from_png(&apng).unwrap().unwrap();
+        assert_eq!(animation.control().to_string(), "2 frames, loops forever");
+        let frames = animation.frames();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].is_default_image());
+        assert!(!frames[1].is_default_image());
+        assert_eq!(frames[1].control().delay(), 0.25);
+        let still = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(frames[1].data(), still.image_data());
+    }
+
+    #[test]
+    fn test_still_png_is_not_animated() {
+        let still = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(Animation::from_png(&still).unwrap(), None);
+    }
+
+    #[test]
+    fn test_sequence_numbers_are_validated() {
+        let apng = two_frames();
+        let error = Animation::from_png(&renumber_fdat(&apng, 5)).unwrap_err();
+        assert_eq!(error.to_string(), "fdAT has sequence number 5, expected 2");
+        assert!(Animation::from_png(&renumber_fdat(&apng, 2)).is_ok());
+    }
+
+    #[test]
+    fn test_frame_count_is_validated() {
+        let apng = two_frames();
+        let mut chunks = apng.chunks().to_vec();
+        chunks[1] = AnimationControl::new(3, 0).unwrap().to_chunk().unwrap();
+        let error = Animation::from_png(&Png::from_chunks(chunks)).unwrap_err();
+        assert_eq!(error.to_string(), "acTL declares 3 frames but 2 were found");
+    }
+
+    #[test]
+    fn test_frame_png_is_standalone() {
+        let apng = two_frames();
+        let animation = Animation::from_png(&apng).unwrap().unwrap();
+        let frame = animation.frame_png(&apng, 1).unwrap();
+        let bytes = frame.as_bytes();
+        let reparsed = Png::try_from(&bytes[..]).unwrap();
+        assert_eq!(Animation::from_png(&reparsed).unwrap(), None);
+        assert_eq!(reparsed.image_header().unwrap().width(), 50);
+        assert_eq!(reparsed.image_data(), animation.frames()[1].data());
+    }
+
+    #[test]
+    fn test_assemble_rejects_mismatched_delays() {
+        let frame = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(Animation::assemble(&[frame], &[1, 2], 0).is_err());
+        assert!(Animation::assemble(&[], &[1], 0).is_err());
+    }
+
+    #[test]
+    fn test_assemble_rejects_invalid_frames() {
+        let frame = Png::try_from(&PNG_FILE[..]).unwrap();
+        let empty = Png::from_chunks(vec![]);
+        let error = Animation::assemble(&[frame, empty], &[1], 0).unwrap_err();
+        assert_eq!(error.to_string(), "frame 1: missing IHDR chunk");
+
+        let mut duplicated = Png::try_from(&PNG_FILE[..]).unwrap();
+        let plte = Chunk::new(ChunkType::from_str("PLTE").unwrap(), vec![0; 3]);
+        duplicated.insert_chunk(1, plte.clone());
+        duplicated.insert_chunk(1, plte);
+        assert!(Animation::assemble(&[duplicated], &[1], 0).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -6,7 +6,68 @@ pub enum Args {
     Encode(EncodeArgs),
     Decode(DecodeArgs),
     Remove(RemoveArgs),
+    /// Change the data of an existing chunk, keeping its position
+    Update(UpdateArgs),
     Print(PrintArgs),
+    /// Score a file for signs of hidden data
+    Analyze(AnalyzeArgs),
+    /// Remove any data after the IEND chunk
+    StripTrailer(StripTrailerArgs),
+    /// Report how many message bytes each method can hide
+    Capacity(CapacityArgs),
+    /// Read and edit tEXt, zTXt and iTXt metadata
+    Text(TextArgs),
+    /// Read and edit the XMP packet
+    Xmp(XmpArgs),
+    /// Read the eXIf chunk and remove sensitive tags from it
+    Exif(ExifArgs),
+    /// Remove identifying metadata, keeping only what is needed to render
+    Scrub(ScrubArgs),
+    /// Inspect and edit the gAMA, cHRM, sRGB, iCCP and cICP chunks
+    Color(ColorArgs),
+    /// Read or set the physical resolution in the pHYs chunk
+    Dpi(DpiArgs),
+    /// Set the last-modification time in the tIME chunk
+    Touch(TouchArgs),
+    /// Inspect, split and build animated PNGs
+    Apng(ApngArgs),
+    /// Write every chunk except IDAT to a JSON manifest
+    ExportMeta(ExportMetaArgs),
+    /// Rebuild the ancillary chunks from a manifest written by export-meta
+    ImportMeta(ImportMetaArgs),
+    /// Copy ancillary chunks from one PNG into another
+    CopyChunks(CopyChunksArgs),
+    /// Compare the chunks and pixels of two PNGs
+    Diff(DiffArgs),
+    /// Apply a patch written by `diff --patch`
+    Apply(ApplyArgs),
+    /// Recover the readable chunks of a damaged PNG
+    Repair(RepairArgs),
+    /// Show the bytes of one chunk as a hex dump, or write them to a file
+    Dump(DumpArgs),
+    /// Add the contents of a file as a new chunk
+    Inject(InjectArgs),
+}
+
+/// Where in the file a message is hidden. Every method tags the message with `chunk_type`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// A standalone chunk of the given type
+    Chunk,
+    /// The order of the PLTE entries of an indexed-color image
+    Palette,
+    /// The filter type of each scanline
+    Filter,
+    /// A chunk-framed record appended after IEND
+    Trailer,
+}
+
+/// How the inspection commands write their output.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Yaml,
 }
 
 #[derive(Parser, Debug)]
@@ -15,21 +76,504 @@ pub struct EncodeArgs {
     pub chunk_type: String,
     pub message: String,
     pub output_file: Option<String>,
+    #[arg(long, value_enum, default_value_t = Method::Chunk)]
+    pub method: Method,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
 }
 
 #[derive(Parser, Debug)]
 pub struct DecodeArgs {
     pub path: String,
     pub chunk_type: String,
+    #[arg(long, value_enum, default_value_t = Method::Chunk)]
+    pub method: Method,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
 }
 
 #[derive(Parser, Debug)]
 pub struct RemoveArgs {
     pub path: String,
     pub chunk_type: String,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct UpdateArgs {
+    pub path: String,
+    pub chunk_type: String,
+    pub message: String,
+    pub output_file: Option<String>,
+    /// Add the message to the end of the existing data instead of replacing it
+    #[arg(long)]
+    pub append: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
 }
 
 #[derive(Parser, Debug)]
 pub struct PrintArgs {
     pub path: String,
+    /// Decode a chunk type with a generic codec, e.g. `ruSt=text`; codecs are text and hex
+    #[arg(long = "decode-as", value_name = "TYPE=CODEC")]
+    pub decode_as: Vec<String>,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+    /// Show damaged files as far as they can be read and list every problem found
+    #[arg(long)]
+    pub lenient: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct AnalyzeArgs {
+    pub path: String,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(Parser, Debug)]
+pub struct StripTrailerArgs {
+    pub path: String,
+    pub output_file: Option<String>,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct CapacityArgs {
+    pub path: String,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(Parser, Debug)]
+pub struct TextArgs {
+    #[command(subcommand)]
+    pub command: TextCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TextCommand {
+    /// Print every text chunk
+    List(TextListArgs),
+    /// Print the text stored under a keyword
+    Get(TextGetArgs),
+    /// Store text under a keyword, replacing any existing entry in place
+    Set(TextSetArgs),
+    /// Remove every text chunk with a keyword
+    Delete(TextDeleteArgs),
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextKind {
+    /// Uncompressed Latin-1 (tEXt)
+    Text,
+    /// Compressed Latin-1 (zTXt)
+    Ztxt,
+    /// UTF-8 with language tag (iTXt)
+    Itxt,
+}
+
+#[derive(Parser, Debug)]
+pub struct TextListArgs {
+    pub path: String,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(Parser, Debug)]
+pub struct TextGetArgs {
+    pub path: String,
+    pub keyword: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct TextSetArgs {
+    pub path: String,
+    pub keyword: String,
+    pub text: String,
+    /// Chunk kind; defaults to tEXt, or iTXt when the text is not Latin-1
+    #[arg(long, value_enum)]
+    pub kind: Option<TextKind>,
+    /// Compress iTXt text
+    #[arg(long)]
+    pub compress: bool,
+    /// iTXt language tag, e.g. en-GB
+    #[arg(long, default_value = "")]
+    pub language: String,
+    /// iTXt keyword translated into the language
+    #[arg(long, default_value = "")]
+    pub translated_keyword: String,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct TextDeleteArgs {
+    pub path: String,
+    pub keyword: String,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct XmpArgs {
+    #[command(subcommand)]
+    pub command: XmpCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum XmpCommand {
+    /// Print the property tree, or one property
+    Show(XmpShowArgs),
+    /// Set a property, e.g. `dc:title`, `dc:creator[2]` or `xmpRights:UsageTerms[en]`
+    Set(XmpSetArgs),
+    /// Remove a property, or the whole packet when none is given
+    Delete(XmpDeleteArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct XmpShowArgs {
+    pub path: String,
+    pub property: Option<String>,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(Parser, Debug)]
+pub struct XmpSetArgs {
+    pub path: String,
+    pub property: String,
+    pub value: String,
+    /// Namespace URI for a prefix the packet does not declare yet
+    #[arg(long)]
+    pub namespace: Option<String>,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct XmpDeleteArgs {
+    pub path: String,
+    pub property: Option<String>,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExifArgs {
+    #[command(subcommand)]
+    pub command: ExifCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExifCommand {
+    /// Print every tag, with orientation and GPS position decoded
+    Show(ExifShowArgs),
+    /// Remove sensitive tags, keeping everything else byte-for-byte
+    Scrub(ExifScrubArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct ExifShowArgs {
+    pub path: String,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExifScrubArgs {
+    pub path: String,
+    /// Remove the GPS location block
+    #[arg(long)]
+    pub gps: bool,
+    /// Remove the camera owner name and body and lens serial numbers
+    #[arg(long)]
+    pub serials: bool,
+    pub output_file: Option<String>,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ScrubArgs {
+    pub path: String,
+    /// Chunk types to keep even if they would be removed, e.g. `--keep tEXt,iCCP`
+    #[arg(long, value_delimiter = ',')]
+    pub keep: Vec<String>,
+    /// Extra ancillary chunk types to remove
+    #[arg(long, value_delimiter = ',')]
+    pub drop: Vec<String>,
+    pub output_file: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ColorArgs {
+    #[command(subcommand)]
+    pub command: ColorCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ColorCommand {
+    /// Print the decoded color chunks and any rule violations
+    Show(ColorShowArgs),
+    /// Check ordering, exclusion and HDR metadata rules, failing on any problem
+    Validate(ColorShowArgs),
+    /// Write one or more color chunks
+    Set(ColorSetArgs),
+    /// Save the embedded ICC profile, uncompressed
+    ExtractIcc(ExtractIccArgs),
+    /// Embed an ICC profile, replacing any sRGB chunk
+    EmbedIcc(EmbedIccArgs),
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Intent {
+    Perceptual,
+    Relative,
+    Saturation,
+    Absolute,
+}
+
+#[derive(Parser, Debug)]
+pub struct ColorShowArgs {
+    pub path: String,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(Parser, Debug)]
+pub struct ColorSetArgs {
+    pub path: String,
+    /// File gamma, e.g. 0.45455 for a 2.2 display exponent
+    #[arg(long)]
+    pub gamma: Option<f64>,
+    /// White, red, green and blue x,y pairs, e.g. `0.3127,0.329,0.64,0.33,0.3,0.6,0.15,0.06`
+    #[arg(long, value_delimiter = ',')]
+    pub chromaticities: Vec<f64>,
+    /// Mark the image as sRGB, removing any ICC profile
+    #[arg(long, value_enum)]
+    pub srgb: Option<Intent>,
+    /// H.273 primaries, transfer, matrix and full-range flag, e.g. `9,16,0,1`
+    #[arg(long, value_delimiter = ',')]
+    pub cicp: Vec<u8>,
+    /// Mastering display red, green, blue and white x,y, then max and min cd/m²
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    pub mastering: Vec<f64>,
+    /// MaxCLL and MaxFALL in cd/m²
+    #[arg(long, value_delimiter = ',')]
+    pub light_level: Vec<f64>,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExtractIccArgs {
+    pub path: String,
+    pub output_file: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct EmbedIccArgs {
+    pub path: String,
+    pub profile: String,
+    #[arg(long, default_value = "ICC profile")]
+    pub name: String,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct DpiArgs {
+    #[command(subcommand)]
+    pub command: DpiCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DpiCommand {
+    /// Print the resolution, or the pixel aspect ratio if no unit is given
+    Get(DpiGetArgs),
+    /// Set square pixels at the given dots per inch
+    Set(DpiSetArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct DpiGetArgs {
+    pub path: String,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(Parser, Debug)]
+pub struct DpiSetArgs {
+    pub path: String,
+    pub value: f64,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct TouchArgs {
+    pub path: String,
+    /// UTC time as `YYYY-MM-DDTHH:MM:SS` instead of now
+    #[arg(long)]
+    pub time: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ApngArgs {
+    #[command(subcommand)]
+    pub command: ApngCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ApngCommand {
+    /// Print the animation and frame controls
+    Info(ApngInfoArgs),
+    /// Write each frame's region, as stored, to a standalone PNG
+    Extract(ApngExtractArgs),
+    /// Build an APNG from frame PNGs that share one IHDR
+    Assemble(ApngAssembleArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct ApngInfoArgs {
+    pub path: String,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(Parser, Debug)]
+pub struct ApngExtractArgs {
+    pub path: String,
+    pub output_dir: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct ApngAssembleArgs {
+    pub output_file: String,
+    #[arg(required = true)]
+    pub frames: Vec<String>,
+    /// Milliseconds per frame: one value for all frames, or one per frame
+    #[arg(long, value_delimiter = ',', default_value = "100")]
+    pub delay: Vec<u16>,
+    /// Times to play the animation; 0 loops forever
+    #[arg(long, default_value_t = 0)]
+    pub plays: u32,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExportMetaArgs {
+    pub path: String,
+    /// Write the manifest here instead of to standard output
+    pub output_file: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ImportMetaArgs {
+    pub path: String,
+    pub manifest: String,
+    pub output_file: Option<String>,
+    /// Keep the file's own ancillary chunks, replacing only those the manifest also has
+    #[arg(long)]
+    pub merge: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct CopyChunksArgs {
+    pub source: String,
+    pub target: String,
+    pub output_file: Option<String>,
+    /// Only copy these chunk types, e.g. `--types tEXt,ruSt`
+    #[arg(long, value_delimiter = ',')]
+    pub types: Vec<String>,
+    /// Copy unsafe-to-copy chunks even when the critical chunks differ
+    #[arg(long)]
+    pub force: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct DiffArgs {
+    pub old: String,
+    pub new: String,
+    /// Write a binary patch that turns the old file into the new one to standard output
+    #[arg(long)]
+    pub patch: bool,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(Parser, Debug)]
+pub struct ApplyArgs {
+    pub path: String,
+    pub patch: String,
+    pub output_file: Option<String>,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct RepairArgs {
+    pub input_file: String,
+    pub output_file: String,
+    /// Keep chunks with a bad CRC, recomputing it, instead of dropping them
+    #[arg(long)]
+    pub fix_crc: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct DumpArgs {
+    pub path: String,
+    pub chunk_type: String,
+    /// Which chunk of that type to dump, counting from 0
+    #[arg(long, default_value_t = 0)]
+    pub index: usize,
+    /// Print a hex and ASCII dump; this is the default
+    #[arg(long, conflicts_with = "raw")]
+    pub hex: bool,
+    /// Write the raw bytes to FILE instead
+    #[arg(long, value_name = "FILE")]
+    pub raw: Option<String>,
+    /// Include the length, type and CRC around the data
+    #[arg(long)]
+    pub framed: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct InjectArgs {
+    pub path: String,
+    pub chunk_type: String,
+    /// File whose bytes become the chunk data
+    pub file: String,
+    pub output_file: Option<String>,
+    /// Allow a critical chunk type, which is still placed just before IEND
+    #[arg(long)]
+    pub force: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
 }
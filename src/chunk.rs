@@ -9,8 +9,12 @@ pub struct Chunk {
     chunk_type: ChunkType,
     chunk_data: Vec<u8>,
     crc: u32,
+    /// Byte offset of the length field in the file the chunk was read from.
+    offset: Option<usize>,
 }
 impl Chunk {
+    /// Largest data length the specification allows, 2^31 - 1 bytes.
+    pub const MAX_LENGTH: u32 = (1 << 31) - 1;
     pub fn new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Chunk {
         let length = chunk_data.len() as u32;
         let crc = crc_checksum(&chunk_type, &chunk_data);
@@ -19,8 +23,35 @@ impl Chunk {
             chunk_type,
             chunk_data,
             crc,
+            offset: None,
         }
     }
+    /// Keeps `crc` as read from a file even if it does not match, so lenient parsing can show
+    /// a damaged chunk as it is.
+    pub(crate) fn with_crc(chunk_type: ChunkType, chunk_data: Vec<u8>, crc: u32) -> Chunk {
+        Chunk {
+            length: chunk_data.len() as u32,
+            chunk_type,
+            chunk_data,
+            crc,
+            offset: None,
+        }
+    }
+    /// Records where in its file the chunk was read from.
+    pub(crate) fn at_offset(self, offset: usize) -> Chunk {
+        Chunk {
+            offset: Some(offset),
+            ..self
+        }
+    }
+    /// Byte offset of the chunk's length field in the file it was parsed from, if it was.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+    /// Whether the stored CRC matches the type and data.
+    pub fn crc_is_valid(&self) -> bool {
+        self.crc == crc_checksum(&self.chunk_type, &self.chunk_data)
+    }
     pub fn length(&self) -> u32 {
         self.length
     }
@@ -33,6 +64,7 @@ impl Chunk {
     pub fn crc(&self) -> u32 {
         self.crc
     }
+    #[allow(dead_code)]
     pub fn data_as_string(&self) -> Result<String> {
         Ok(String::from_utf8(self.chunk_data.clone())?)
     }
@@ -50,29 +82,35 @@ impl Chunk {
 impl TryFrom<&[u8]> for Chunk {
     type Error = Error;
     fn try_from(value: &[u8]) -> Result<Self> {
-        let mut index = 0;
-        let length: u32 = to_u32((&value[..4]).try_into()?);
-        index += 4;
-
-        let chunk_type_bytes: [u8; 4] = (&value[index..index + 4]).try_into()?;
+        let Some(header) = value.get(..8) else {
+            return Err(Box::new(std::io::Error::other(format!(
+                "truncated chunk header, {} bytes left",
+                value.len()
+            ))));
+        };
+        let length: u32 = to_u32(header[..4].try_into()?);
+        let chunk_type_bytes: [u8; 4] = header[4..].try_into()?;
         let chunk_type: ChunkType = ChunkType::try_from(chunk_type_bytes)?;
-        index += 4;
-
-        let chunk_data: Vec<u8> = (&value[index..index + (length as usize)]).into();
-        index += length as usize;
 
-        let crc: u32 = to_u32((&value[index..index + 4]).try_into()?);
+        let end = 8 + length as usize;
+        let (Some(chunk_data), Some(crc)) = (value.get(8..end), value.get(end..end + 4)) else {
+            return Err(Box::new(std::io::Error::other(format!(
+                "truncated chunk, {} data bytes declared but only {} left",
+                length,
+                value.len() - 8
+            ))));
+        };
+        let chunk_data = chunk_data.to_vec();
+        let crc: u32 = to_u32(crc.try_into()?);
         if crc != crc_checksum(&chunk_type, &chunk_data) {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "invalid checksum",
-            )));
+            return Err(Box::new(std::io::Error::other("invalid checksum")));
         }
         Ok(Chunk {
             length,
             chunk_type,
             chunk_data,
             crc,
+            offset: None,
         })
     }
 }
@@ -88,6 +126,43 @@ impl Display for Chunk {
     }
 }
 
+/// The chunk type, its property bits, length, CRC and base64 data.
+#[cfg(feature = "serde")]
+impl Chunk {
+    /// Number of fields written by `serialize_fields`.
+    pub(crate) const SERIALIZED_FIELDS: usize = 8;
+    pub(crate) fn serialize_fields<S: serde::ser::SerializeStruct>(
+        &self,
+        state: &mut S,
+    ) -> std::result::Result<(), S::Error> {
+        use base64::Engine;
+        state.serialize_field("type", &self.chunk_type)?;
+        state.serialize_field("critical", &self.chunk_type.is_critical())?;
+        state.serialize_field("public", &self.chunk_type.is_public())?;
+        state.serialize_field(
+            "reserved_bit_valid",
+            &self.chunk_type.is_reserved_bit_valid(),
+        )?;
+        state.serialize_field("safe_to_copy", &self.chunk_type.is_safe_to_copy())?;
+        state.serialize_field("length", &self.length)?;
+        state.serialize_field("crc", &format!("{:08x}", self.crc))?;
+        let data = base64::engine::general_purpose::STANDARD.encode(&self.chunk_data);
+        state.serialize_field("data", &data)
+    }
+}
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chunk {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Chunk", Chunk::SERIALIZED_FIELDS)?;
+        self.serialize_fields(&mut state)?;
+        state.end()
+    }
+}
+
 pub fn to_u32(bytes: [u8; 4]) -> u32 {
     ((bytes[0] as u32) << 24)
         | ((bytes[1] as u32) << 16)
@@ -95,7 +170,42 @@ pub fn to_u32(bytes: [u8; 4]) -> u32 {
         | (bytes[3] as u32)
 }
 
-fn crc_checksum(chunk_type: &ChunkType, data: &Vec<u8>) -> u32 {
+/// A malformed-contents error for the typed chunk parsers.
+pub(crate) fn invalid(message: String) -> Error {
+    Box::new(std::io::Error::other(message))
+}
+
+/// Checks that `chunk` is of type `expected` and, when given, has exactly `length` data bytes.
+pub(crate) fn check_chunk(chunk: &Chunk, expected: &str, length: Option<usize>) -> Result<()> {
+    if chunk.chunk_type().bytes() != expected.as_bytes() {
+        return Err(invalid(format!(
+            "expected {} chunk, found {}",
+            expected,
+            chunk.chunk_type()
+        )));
+    }
+    if let Some(length) = length.filter(|&l| l != chunk.data().len()) {
+        return Err(invalid(format!(
+            "{} chunk must be {} bytes, found {}",
+            expected,
+            length,
+            chunk.data().len()
+        )));
+    }
+    Ok(())
+}
+
+/// Big-endian u16 at byte `offset`.
+pub(crate) fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+/// Big-endian u32 at byte `offset`.
+pub(crate) fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn crc_checksum(chunk_type: &ChunkType, data: &[u8]) -> u32 {
     let algo = &Algorithm {
         init: 0xffffffff,
         refin: true,
@@ -106,7 +216,7 @@ fn crc_checksum(chunk_type: &ChunkType, data: &Vec<u8>) -> u32 {
     let crc = Crc::<u32>::new(algo);
     let mut digest = crc.digest();
     digest.update(&chunk_type.bytes());
-    digest.update(&data);
+    digest.update(data);
     digest.finalize()
 }
 
@@ -144,6 +254,14 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_truncated_chunk() {
+        let bytes = testing_chunk().as_bytes();
+        for len in [0, 7, 8, 40, bytes.len() - 1] {
+            assert!(Chunk::try_from(&bytes[..len]).is_err());
+        }
+    }
+
     #[test]
     fn test_chunk_length() {
         let chunk = testing_chunk();
@@ -238,4 +356,15 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_check_chunk_type_and_length() {
+        let chunk = testing_chunk();
+        assert!(check_chunk(&chunk, "RuSt", None).is_ok());
+        assert!(check_chunk(&chunk, "RuSt", Some(42)).is_ok());
+        assert!(check_chunk(&chunk, "RuSt", Some(41)).is_err());
+        assert!(check_chunk(&chunk, "ruSt", None).is_err());
+        assert_eq!(u16_at(chunk.data(), 0), u16::from_be_bytes(*b"Th"));
+        assert_eq!(u32_at(chunk.data(), 1), u32::from_be_bytes(*b"his "));
+    }
 }
@@ -3,7 +3,14 @@ use std::convert::{TryFrom, TryInto};
 use std::fmt::Display;
 use std::str::FromStr;
 
-#[derive(PartialEq, Debug, Eq, Clone)]
+/// Chunk types defined by the PNG specification and its registered extensions.
+pub const STANDARD_TYPES: [&str; 33] = [
+    "IHDR", "PLTE", "IDAT", "IEND", "tRNS", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCv",
+    "cLLi", "tEXt", "zTXt", "iTXt", "bKGD", "hIST", "pHYs", "sPLT", "eXIf", "tIME", "acTL", "fcTL",
+    "fdAT", "oFFs", "pCAL", "sCAL", "gIFg", "gIFx", "gIFt", "sTER", "dSIG",
+];
+
+#[derive(PartialEq, Debug, Eq, Clone, Hash)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
@@ -12,13 +19,13 @@ impl ChunkType {
         let valid_ascii = bytes
             .iter()
             .map(|&x| x as char)
-            .all(|x| (x >= 'a' && x <= 'z') || (x >= 'A' && x <= 'Z'));
+            .all(|x| x.is_ascii_alphabetic());
         match valid_ascii {
             true => Ok(ChunkType { bytes }),
-            false => Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("invalid ascii: {}", String::from_utf8_lossy(&bytes)),
-            ))),
+            false => Err(Box::new(std::io::Error::other(format!(
+                "invalid ascii: {}",
+                String::from_utf8_lossy(&bytes)
+            )))),
         }
     }
     pub fn bytes(&self) -> [u8; 4] {
@@ -39,6 +46,9 @@ impl ChunkType {
     pub fn is_valid(&self) -> bool {
         self.is_reserved_bit_valid()
     }
+    pub fn is_standard(&self) -> bool {
+        STANDARD_TYPES.iter().any(|t| t.as_bytes() == self.bytes)
+    }
 }
 impl Display for ChunkType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -46,6 +56,15 @@ impl Display for ChunkType {
         write!(f, "{}", string_rep)
     }
 }
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
 impl TryFrom<[u8; 4]> for ChunkType {
     type Error = Error;
     fn try_from(value: [u8; 4]) -> Result<Self> {
@@ -142,6 +161,14 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_is_standard() {
+        assert!(ChunkType::from_str("IDAT").unwrap().is_standard());
+        assert!(ChunkType::from_str("tEXt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("RuSt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("Idat").unwrap().is_standard());
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -0,0 +1,406 @@
+use crate::ancillary::{Background, Histogram, SignificantBits, SuggestedPalette};
+use crate::apng::{AnimationControl, FrameControl};
+use crate::color::{
+    Chromaticities, CodingIndependentCodePoints, ContentLightLevel, Gamma, IccProfile,
+    MasteringDisplay, RenderingIntent,
+};
+use crate::exif::Exif;
+use crate::phys::PhysicalDimensions;
+use crate::png::{Chunk, ChunkType, Palette, Png, Transparency};
+use crate::text::TextualChunk;
+use crate::timestamp::LastModified;
+use crate::Result;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Decoded chunk contents as named fields, in display order.
+pub type Fields = Vec<(String, String)>;
+
+/// Signature of the built-in decoders.
+type DecodeFn = fn(&Chunk, &Png) -> Result<Fields>;
+
+fn field(name: &str, value: impl Display) -> (String, String) {
+    (name.to_string(), value.to_string())
+}
+
+/// Turns a chunk's bytes into structured fields. `png` is the file the chunk belongs to, for
+/// chunks whose layout depends on IHDR or PLTE.
+///
+/// Any `Fn(&Chunk, &Png) -> Result<Fields>` is a codec, so simple decoders can be closures.
+pub trait ChunkCodec {
+    fn decode(&self, chunk: &Chunk, png: &Png) -> Result<Fields>;
+}
+impl<F: Fn(&Chunk, &Png) -> Result<Fields>> ChunkCodec for F {
+    fn decode(&self, chunk: &Chunk, png: &Png) -> Result<Fields> {
+        self(chunk, png)
+    }
+}
+
+/// Shows the chunk data as text, replacing invalid UTF-8.
+pub struct TextCodec;
+impl ChunkCodec for TextCodec {
+    fn decode(&self, chunk: &Chunk, _png: &Png) -> Result<Fields> {
+        Ok(vec![field("text", String::from_utf8_lossy(chunk.data()))])
+    }
+}
+
+/// Shows up to the first 64 bytes of chunk data in hex.
+pub struct HexCodec;
+impl ChunkCodec for HexCodec {
+    fn decode(&self, chunk: &Chunk, _png: &Png) -> Result<Fields> {
+        let data = chunk.data();
+        let hex: Vec<String> = data.iter().take(64).map(|b| format!("{:02x}", b)).collect();
+        let ellipsis = if data.len() > 64 { " …" } else { "" };
+        Ok(vec![field(
+            "data",
+            format!("{}{}", hex.join(" "), ellipsis),
+        )])
+    }
+}
+
+/// Codecs keyed by chunk type. Registering a type again replaces its codec.
+pub struct CodecRegistry {
+    codecs: HashMap<ChunkType, Box<dyn ChunkCodec>>,
+}
+impl CodecRegistry {
+    pub fn new() -> CodecRegistry {
+        CodecRegistry {
+            codecs: HashMap::new(),
+        }
+    }
+    /// A registry with codecs for every standard chunk type this crate understands.
+    pub fn with_builtins() -> CodecRegistry {
+        let mut registry = CodecRegistry::new();
+        let builtins: [(&str, DecodeFn); 26] = [
+            ("IHDR", decode_header),
+            ("PLTE", decode_palette),
+            ("IDAT", decode_image_data),
+            ("IEND", |_, _| Ok(vec![])),
+            ("tRNS", decode_transparency),
+            ("bKGD", decode_background),
+            ("sBIT", |chunk, png| {
+                let bits = SignificantBits::parse(chunk, &png.image_header()?)?;
+                Ok(vec![field("bits", bits)])
+            }),
+            ("hIST", |chunk, png| {
+                let histogram = Histogram::parse(chunk, palette_len(png)?)?;
+                Ok(vec![field("histogram", histogram)])
+            }),
+            ("sPLT", |chunk, _| {
+                Ok(vec![field("palette", SuggestedPalette::try_from(chunk)?)])
+            }),
+            ("tEXt", decode_text),
+            ("zTXt", decode_text),
+            ("iTXt", decode_text),
+            ("gAMA", |chunk, _| {
+                let gamma = Gamma::try_from(chunk)?;
+                Ok(vec![
+                    field("gamma", format!("{:.5}", gamma.value())),
+                    field("display exponent", format!("{:.2}", 1.0 / gamma.value())),
+                ])
+            }),
+            ("cHRM", decode_chromaticities),
+            ("sRGB", |chunk, _| {
+                Ok(vec![field("intent", RenderingIntent::try_from(chunk)?)])
+            }),
+            ("iCCP", |chunk, _| {
+                let icc = IccProfile::try_from(chunk)?;
+                Ok(vec![
+                    field("name", icc.name()),
+                    field("profile", format!("{} bytes", icc.profile().len())),
+                    field("color space", String::from_utf8_lossy(icc.color_space())),
+                ])
+            }),
+            ("cICP", |chunk, _| {
+                Ok(vec![field(
+                    "code points",
+                    CodingIndependentCodePoints::try_from(chunk)?,
+                )])
+            }),
+            ("mDCv", |chunk, _| {
+                let display = MasteringDisplay::try_from(chunk)?;
+                Ok(vec![
+                    field("display", display),
+                    field(
+                        "max luminance",
+                        format!("{} cd/m²", display.max_luminance()),
+                    ),
+                    field(
+                        "min luminance",
+                        format!("{} cd/m²", display.min_luminance()),
+                    ),
+                ])
+            }),
+            ("cLLi", |chunk, _| {
+                let level = ContentLightLevel::try_from(chunk)?;
+                Ok(vec![
+                    field("MaxCLL", format!("{} cd/m²", level.max_cll())),
+                    field("MaxFALL", format!("{} cd/m²", level.max_fall())),
+                ])
+            }),
+            ("pHYs", |chunk, _| {
+                let dimensions = PhysicalDimensions::try_from(chunk)?;
+                let mut fields = vec![field("resolution", dimensions)];
+                if let Some((x, y)) = dimensions.dpi() {
+                    fields.push(field("dpi", format!("{:.2} x {:.2}", x, y)));
+                }
+                Ok(fields)
+            }),
+            ("tIME", |chunk, _| {
+                Ok(vec![field("modified", LastModified::try_from(chunk)?)])
+            }),
+            ("eXIf", decode_exif),
+            ("acTL", |chunk, _| {
+                Ok(vec![field("animation", AnimationControl::try_from(chunk)?)])
+            }),
+            ("fcTL", |chunk, _| {
+                let control = FrameControl::try_from(chunk)?;
+                Ok(vec![
+                    field("sequence", control.sequence()),
+                    field("frame", control),
+                ])
+            }),
+            ("fdAT", |chunk, _| {
+                let data = chunk.data();
+                if data.len() < 4 {
+                    return Err("fdAT chunk is truncated".into());
+                }
+                Ok(vec![
+                    field("sequence", u32::from_be_bytes(data[..4].try_into()?)),
+                    field("compressed", format!("{} bytes", data.len() - 4)),
+                ])
+            }),
+            ("dSIG", |chunk, _| {
+                Ok(vec![field(
+                    "signature",
+                    format!("{} bytes", chunk.data().len()),
+                )])
+            }),
+        ];
+        for (chunk_type, codec) in builtins {
+            registry.register(ChunkType::from_str(chunk_type).unwrap(), Box::new(codec));
+        }
+        registry
+    }
+    /// Adds or replaces the codec for `chunk_type`, returning the one it replaced.
+    pub fn register(
+        &mut self,
+        chunk_type: ChunkType,
+        codec: Box<dyn ChunkCodec>,
+    ) -> Option<Box<dyn ChunkCodec>> {
+        self.codecs.insert(chunk_type, codec)
+    }
+    /// Decodes `chunk`, or returns `None` if no codec handles its type.
+    pub fn decode(&self, chunk: &Chunk, png: &Png) -> Option<Result<Fields>> {
+        self.codecs
+            .get(chunk.chunk_type())
+            .map(|codec| codec.decode(chunk, png))
+    }
+}
+
+fn palette_len(png: &Png) -> Result<Option<usize>> {
+    Ok(png.palette()?.map(|palette| palette.entries().len()))
+}
+
+fn decode_header(chunk: &Chunk, _png: &Png) -> Result<Fields> {
+    let header = crate::image::ImageHeader::try_from(chunk)?;
+    Ok(vec![
+        field("width", header.width()),
+        field("height", header.height()),
+        field("bit depth", header.bit_depth()),
+        field("color type", header.color_type()),
+        field(
+            "interlace",
+            if header.interlaced() { "Adam7" } else { "none" },
+        ),
+    ])
+}
+
+fn decode_palette(chunk: &Chunk, _png: &Png) -> Result<Fields> {
+    let palette = Palette::try_from(chunk)?;
+    let shown: Vec<String> = palette
+        .entries()
+        .iter()
+        .take(8)
+        .map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b))
+        .collect();
+    let more = palette.entries().len().saturating_sub(shown.len());
+    let mut colors = shown.join(" ");
+    if more > 0 {
+        colors.push_str(&format!(" … {} more", more));
+    }
+    Ok(vec![
+        field("entries", palette.entries().len()),
+        field("colors", colors),
+    ])
+}
+
+fn decode_image_data(chunk: &Chunk, _png: &Png) -> Result<Fields> {
+    Ok(vec![field(
+        "compressed",
+        format!("{} bytes", chunk.data().len()),
+    )])
+}
+
+fn decode_transparency(chunk: &Chunk, png: &Png) -> Result<Fields> {
+    let transparency = Transparency::parse(chunk, &png.image_header()?)?;
+    transparency.check_palette(palette_len(png)?)?;
+    Ok(match transparency {
+        Transparency::Gray(gray) => vec![field("transparent gray", gray)],
+        Transparency::Rgb([r, g, b]) => {
+            vec![field("transparent color", format!("({}, {}, {})", r, g, b))]
+        }
+        Transparency::Indexed(alphas) => vec![
+            field("alpha entries", alphas.len()),
+            field(
+                "fully transparent",
+                alphas.iter().filter(|&&a| a == 0).count(),
+            ),
+        ],
+    })
+}
+
+fn decode_background(chunk: &Chunk, png: &Png) -> Result<Fields> {
+    let background = Background::parse(chunk, &png.image_header()?, palette_len(png)?)?;
+    Ok(match background {
+        Background::Gray(gray) => vec![field("gray", gray)],
+        Background::Rgb([r, g, b]) => vec![field("color", format!("({}, {}, {})", r, g, b))],
+        Background::Indexed(index) => vec![field("palette index", index)],
+    })
+}
+
+fn decode_text(chunk: &Chunk, _png: &Png) -> Result<Fields> {
+    let text = TextualChunk::try_from(chunk)?;
+    let mut fields = vec![field("keyword", text.keyword())];
+    if let TextualChunk::International(international) = &text {
+        if !international.language_tag().is_empty() {
+            fields.push(field("language", international.language_tag()));
+        }
+        if !international.translated_keyword().is_empty() {
+            fields.push(field(
+                "translated keyword",
+                international.translated_keyword(),
+            ));
+        }
+    }
+    fields.push(field("text", text.text()));
+    Ok(fields)
+}
+
+fn decode_chromaticities(chunk: &Chunk, _png: &Png) -> Result<Fields> {
+    let chromaticities = Chromaticities::try_from(chunk)?;
+    let p = chromaticities.points();
+    let point = |i: usize| format!("({:.4}, {:.4})", p[i], p[i + 1]);
+    let mut fields = vec![
+        field("white", point(0)),
+        field("red", point(2)),
+        field("green", point(4)),
+        field("blue", point(6)),
+    ];
+    if chromaticities == Chromaticities::SRGB {
+        fields.push(field("matches", "sRGB"));
+    }
+    Ok(fields)
+}
+
+fn decode_exif(chunk: &Chunk, _png: &Png) -> Result<Fields> {
+    let exif = Exif::try_from(chunk)?;
+    let mut fields = vec![];
+    if let Some(camera) = exif.camera() {
+        fields.push(field("camera", camera));
+    }
+    if let Some(timestamp) = exif.timestamp() {
+        fields.push(field("taken", timestamp));
+    }
+    if let Some(orientation) = exif.orientation() {
+        fields.push(field("orientation", orientation));
+    }
+    if let Some((latitude, longitude)) = exif.gps_position() {
+        fields.push(field("GPS", format!("{:.6}, {:.6}", latitude, longitude)));
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{chunk, PNG_FILE};
+
+    fn decode(registry: &CodecRegistry, png: &Png, chunk_type: &str) -> Option<Result<Fields>> {
+        let chunk = png
+            .chunks()
+            .iter()
+            .find(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
+            .unwrap();
+        registry.decode(chunk, png)
+    }
+
+    #[test]
+    fn test_builtin_header_fields() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let registry = CodecRegistry::with_builtins();
+        let fields = decode(&registry, &png, "IHDR").unwrap().unwrap();
+        assert_eq!(fields[0], field("width", 50));
+        assert_eq!(fields[3], field("color type", "rgba"));
+        assert_eq!(fields[4], field("interlace", "none"));
+    }
+
+    #[test]
+    fn test_every_standard_chunk_in_file_decodes() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let registry = CodecRegistry::with_builtins();
+        for chunk in png.chunks() {
+            if chunk.chunk_type().is_standard() {
+                let decoded = registry.decode(chunk, &png);
+                assert!(matches!(decoded, Some(Ok(_))), "{}", chunk.chunk_type());
+            }
+        }
+        assert!(decode(&registry, &png, "RuSt").is_none());
+    }
+
+    #[test]
+    fn test_duplicate_palette_is_an_error() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 2, 0, 0, 0, 2, 8, 3, 0, 0, 0]),
+            chunk("PLTE", &[0, 0, 0, 255, 255, 255]),
+            chunk("PLTE", &[0, 0, 0]),
+            chunk("tRNS", &[0]),
+            chunk("hIST", &[0, 1, 0, 1]),
+            chunk("IEND", &[]),
+        ]);
+        let registry = CodecRegistry::with_builtins();
+        for chunk_type in ["tRNS", "hIST"] {
+            let error = decode(&registry, &png, chunk_type).unwrap().unwrap_err();
+            assert_eq!(error.to_string(), "duplicate PLTE chunks");
+        }
+    }
+
+    #[test]
+    fn test_register_private_codec() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut registry = CodecRegistry::with_builtins();
+        let rust = ChunkType::from_str("RuSt").unwrap();
+        assert!(registry
+            .register(rust.clone(), Box::new(TextCodec))
+            .is_none());
+        let fields = decode(&registry, &png, "RuSt").unwrap().unwrap();
+        assert_eq!(fields[0].0, "text");
+
+        let length = |chunk: &Chunk, _: &Png| -> Result<Fields> {
+            Ok(vec![field("length", chunk.length())])
+        };
+        assert!(registry.register(rust, Box::new(length)).is_some());
+        let fields = decode(&registry, &png, "RuSt").unwrap().unwrap();
+        assert_eq!(fields[0].0, "length");
+    }
+
+    #[test]
+    fn test_hex_codec_truncates() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0xab; 70]);
+        let fields = HexCodec.decode(&chunk, &png).unwrap();
+        assert!(fields[0].1.starts_with("ab ab"));
+        assert!(fields[0].1.ends_with(" …"));
+    }
+}
@@ -0,0 +1,910 @@
+use crate::chunk::{check_chunk, invalid, u16_at, u32_at};
+use crate::image::ColorType;
+use crate::png::{Chunk, ChunkType, Png};
+use crate::text::{keyword_bytes, latin1_string, split_null};
+use crate::{zlib, Error, Result};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Chunks describing the color space, all of which must precede PLTE and IDAT.
+pub const COLOR_CHUNK_TYPES: [&str; 7] = ["gAMA", "cHRM", "sRGB", "iCCP", "cICP", "mDCv", "cLLi"];
+
+/// cICP transfer functions for HDR content: PQ and HLG.
+const HDR_TRANSFERS: [u8; 2] = [16, 18];
+
+/// ICC tags whose text can name a person, organization or device.
+const ICC_TEXT_TAGS: [&[u8; 4]; 3] = [b"desc", b"cprt", b"dmdd"];
+
+/// gAMA, cHRM and the other fixed-point chunks store values times 100000.
+const SCALE: f64 = 100000.0;
+
+fn fixed_point(value: f64) -> Result<u32> {
+    let scaled = (value * SCALE).round();
+    if !(0.0..=u32::MAX as f64).contains(&scaled) {
+        return Err(invalid(format!("{} is out of range", value)));
+    }
+    Ok(scaled as u32)
+}
+
+/// gAMA: the file gamma, i.e. the exponent that encoded linear light into sample values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gamma(u32);
+impl Gamma {
+    pub fn new(gamma: f64) -> Result<Gamma> {
+        match fixed_point(gamma)? {
+            0 => Err(invalid("gamma must be positive".to_string())),
+            value => Ok(Gamma(value)),
+        }
+    }
+    pub fn value(&self) -> f64 {
+        self.0 as f64 / SCALE
+    }
+    pub fn to_chunk(self) -> Result<Chunk> {
+        Ok(Chunk::new(
+            ChunkType::from_str("gAMA")?,
+            self.0.to_be_bytes().to_vec(),
+        ))
+    }
+}
+impl TryFrom<&Chunk> for Gamma {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "gAMA", Some(4))?;
+        match u32_at(chunk.data(), 0) {
+            0 => Err(invalid("gAMA value of zero".to_string())),
+            value => Ok(Gamma(value)),
+        }
+    }
+}
+impl Display for Gamma {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "gamma {:.5} (display exponent {:.2})",
+            self.value(),
+            1.0 / self.value()
+        )
+    }
+}
+
+/// cHRM: CIE 1931 x,y of the white point and the red, green and blue primaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chromaticities {
+    points: [u32; 8],
+}
+impl Chromaticities {
+    /// The values sRGB (and BT.709) define, which encoders should write alongside sRGB.
+    pub const SRGB: Chromaticities = Chromaticities {
+        points: [31270, 32900, 64000, 33000, 30000, 60000, 15000, 6000],
+    };
+    /// `points` are white x, white y, red x, red y, green x, green y, blue x, blue y.
+    pub fn new(points: [f64; 8]) -> Result<Chromaticities> {
+        let mut scaled = [0; 8];
+        for (scaled, value) in scaled.iter_mut().zip(points) {
+            *scaled = fixed_point(value)?;
+        }
+        Ok(Chromaticities { points: scaled })
+    }
+    pub fn points(&self) -> [f64; 8] {
+        self.points.map(|p| p as f64 / SCALE)
+    }
+    pub fn to_chunk(self) -> Result<Chunk> {
+        let data = self.points.iter().flat_map(|p| p.to_be_bytes()).collect();
+        Ok(Chunk::new(ChunkType::from_str("cHRM")?, data))
+    }
+}
+impl TryFrom<&Chunk> for Chromaticities {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "cHRM", Some(32))?;
+        let mut points = [0; 8];
+        for (index, point) in points.iter_mut().enumerate() {
+            *point = u32_at(chunk.data(), 4 * index);
+        }
+        Ok(Chromaticities { points })
+    }
+}
+impl Display for Chromaticities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let p = self.points();
+        write!(
+            f,
+            "chromaticities white ({:.4}, {:.4}) red ({:.4}, {:.4}) green ({:.4}, {:.4}) blue ({:.4}, {:.4})",
+            p[0], p[1], p[2], p[3], p[4], p[5], p[6], p[7]
+        )?;
+        if *self == Chromaticities::SRGB {
+            write!(f, " [sRGB]")?;
+        }
+        Ok(())
+    }
+}
+
+/// sRGB: the image is in the sRGB color space, rendered with this intent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+impl RenderingIntent {
+    pub fn to_chunk(self) -> Result<Chunk> {
+        Ok(Chunk::new(ChunkType::from_str("sRGB")?, vec![self as u8]))
+    }
+}
+impl TryFrom<&Chunk> for RenderingIntent {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "sRGB", Some(1))?;
+        match chunk.data()[0] {
+            0 => Ok(RenderingIntent::Perceptual),
+            1 => Ok(RenderingIntent::RelativeColorimetric),
+            2 => Ok(RenderingIntent::Saturation),
+            3 => Ok(RenderingIntent::AbsoluteColorimetric),
+            intent => Err(invalid(format!("invalid rendering intent {}", intent))),
+        }
+    }
+}
+impl Display for RenderingIntent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RenderingIntent::Perceptual => "perceptual",
+            RenderingIntent::RelativeColorimetric => "relative colorimetric",
+            RenderingIntent::Saturation => "saturation",
+            RenderingIntent::AbsoluteColorimetric => "absolute colorimetric",
+        };
+        write!(f, "sRGB, {} intent", name)
+    }
+}
+
+/// iCCP: a named ICC profile, stored zlib-compressed and held here inflated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfile {
+    name: String,
+    profile: Vec<u8>,
+}
+impl IccProfile {
+    pub fn new(name: &str, profile: Vec<u8>) -> Result<IccProfile> {
+        keyword_bytes(name)?;
+        if profile.len() < 128 || &profile[36..40] != b"acsp" {
+            return Err(invalid("not an ICC profile".to_string()));
+        }
+        Ok(IccProfile {
+            name: name.to_string(),
+            profile,
+        })
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn profile(&self) -> &[u8] {
+        &self.profile
+    }
+    /// Overwrites the text of the description, copyright and device model tags in place, so no
+    /// offset in the profile moves, and clears the profile ID that covered them. The
+    /// description becomes `description`, the others become empty. Returns the tags changed.
+    pub fn blank_text_tags(&mut self, description: &str) -> Vec<String> {
+        let profile = &mut self.profile;
+        let be32 = |data: &[u8], at: usize| {
+            data.get(at..at + 4)
+                .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+        };
+        // Copies as much of `text` as fits and zeroes the rest.
+        let fill = |field: &mut [u8], text: &[u8]| {
+            let len = text.len().min(field.len().saturating_sub(1));
+            field[..len].copy_from_slice(&text[..len]);
+            field[len..].fill(0);
+        };
+        let mut changed = vec![];
+        let count = be32(profile, 128).unwrap_or(0);
+        for entry in (0..count).map(|i| 132 + 12 * i) {
+            let (Some(signature), Some(start), Some(size)) = (
+                profile.get(entry..entry + 4).map(<[u8]>::to_vec),
+                be32(profile, entry + 4),
+                be32(profile, entry + 8),
+            ) else {
+                break;
+            };
+            if !ICC_TEXT_TAGS.iter().any(|tag| tag[..] == signature[..]) {
+                continue;
+            }
+            let Some(tag) = start
+                .checked_add(size)
+                .and_then(|end| profile.get_mut(start..end))
+            else {
+                continue;
+            };
+            let before = tag.to_vec();
+            let text = if &signature == b"desc" {
+                description.as_bytes()
+            } else {
+                b""
+            };
+            match tag.get(..4) {
+                // textType: ASCII after the type signature and reserved bytes.
+                Some(b"text") if tag.len() > 8 => fill(&mut tag[8..], text),
+                // textDescriptionType: ASCII, then Unicode and ScriptCode variants.
+                Some(b"desc") => {
+                    let ascii = be32(tag, 8).unwrap_or(0);
+                    let unicode_at = 12 + ascii;
+                    if let Some(field) = tag.get_mut(12..unicode_at) {
+                        fill(field, text);
+                    }
+                    if let Some(chars) = be32(tag, unicode_at + 4) {
+                        let start = unicode_at + 8;
+                        let end = (start + 2 * chars).min(tag.len());
+                        if let Some(field) = tag.get_mut(start..end) {
+                            field.fill(0);
+                        }
+                        if let Some(field) = tag.get_mut(end + 2..(end + 70).min(tag.len())) {
+                            field.fill(0);
+                        }
+                    }
+                }
+                // multiLocalizedUnicodeType: records of UTF-16BE strings at tag offsets.
+                Some(b"mluc") => {
+                    let utf16: Vec<u8> = String::from_utf8_lossy(text)
+                        .encode_utf16()
+                        .flat_map(u16::to_be_bytes)
+                        .chain([0, 0])
+                        .collect();
+                    let records = be32(tag, 8).unwrap_or(0);
+                    for record in (0..records).map(|i| 16 + 12 * i) {
+                        let (Some(len), Some(at)) = (be32(tag, record + 4), be32(tag, record + 8))
+                        else {
+                            break;
+                        };
+                        if let Some(field) =
+                            at.checked_add(len).and_then(|end| tag.get_mut(at..end))
+                        {
+                            fill(field, &utf16);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            if tag[..] != before[..] {
+                changed.push(String::from_utf8_lossy(&signature).into_owned());
+            }
+        }
+        if !changed.is_empty() {
+            profile[84..100].fill(0);
+        }
+        changed
+    }
+    /// The profile's data color space signature, e.g. `RGB ` or `GRAY`.
+    pub fn color_space(&self) -> &[u8] {
+        &self.profile[16..20]
+    }
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = keyword_bytes(&self.name)?;
+        data.extend([0, 0]);
+        data.extend(zlib::compress(&self.profile)?);
+        Ok(Chunk::new(ChunkType::from_str("iCCP")?, data))
+    }
+}
+impl TryFrom<&Chunk> for IccProfile {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "iCCP", None)?;
+        let (name, rest) = split_null(chunk.data())?;
+        match rest.split_first() {
+            Some((0, compressed)) => {
+                IccProfile::new(&latin1_string(name), zlib::decompress(compressed)?)
+            }
+            Some((method, _)) => Err(invalid(format!("unknown compression method {}", method))),
+            None => Err(invalid("truncated iCCP chunk".to_string())),
+        }
+    }
+}
+impl Display for IccProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ICC profile {:?} ({} bytes, {})",
+            self.name,
+            self.profile.len(),
+            String::from_utf8_lossy(self.color_space()).trim_end()
+        )
+    }
+}
+
+/// cICP: ITU-T H.273 coding-independent code points, as used for HDR and wide gamut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodingIndependentCodePoints {
+    primaries: u8,
+    transfer: u8,
+    full_range: bool,
+}
+impl CodingIndependentCodePoints {
+    /// PNG images are RGB, so the matrix coefficients are always 0 (identity).
+    pub fn new(primaries: u8, transfer: u8, full_range: bool) -> CodingIndependentCodePoints {
+        CodingIndependentCodePoints {
+            primaries,
+            transfer,
+            full_range,
+        }
+    }
+    pub fn to_chunk(self) -> Result<Chunk> {
+        let data = vec![self.primaries, self.transfer, 0, self.full_range as u8];
+        Ok(Chunk::new(ChunkType::from_str("cICP")?, data))
+    }
+}
+impl TryFrom<&Chunk> for CodingIndependentCodePoints {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "cICP", Some(4))?;
+        let data = chunk.data();
+        if data[2] != 0 {
+            return Err(invalid(format!(
+                "cICP matrix coefficients must be 0 for RGB, found {}",
+                data[2]
+            )));
+        }
+        if data[3] > 1 {
+            return Err(invalid(format!("invalid cICP range flag {}", data[3])));
+        }
+        Ok(CodingIndependentCodePoints::new(
+            data[0],
+            data[1],
+            data[3] == 1,
+        ))
+    }
+}
+impl Display for CodingIndependentCodePoints {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let primaries = match self.primaries {
+            1 => "BT.709",
+            9 => "BT.2020",
+            11 => "DCI-P3",
+            12 => "Display P3",
+            _ => "other",
+        };
+        let transfer = match self.transfer {
+            1 | 6 | 14 | 15 => "BT.709",
+            8 => "linear",
+            13 => "sRGB",
+            16 => "PQ",
+            18 => "HLG",
+            _ => "other",
+        };
+        write!(
+            f,
+            "cICP primaries {} ({}), transfer {} ({}), {} range",
+            self.primaries,
+            primaries,
+            self.transfer,
+            transfer,
+            if self.full_range { "full" } else { "narrow" }
+        )
+    }
+}
+
+/// mDCv: the color volume of the display the content was mastered on (SMPTE ST 2086).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasteringDisplay {
+    /// Red, green and blue x,y, then white point x,y, in units of 0.00002.
+    chromaticities: [u16; 8],
+    /// Maximum and minimum luminance in units of 0.0001 cd/m².
+    max_luminance: u32,
+    min_luminance: u32,
+}
+impl MasteringDisplay {
+    const CHROMATICITY_SCALE: f64 = 50000.0;
+    const LUMINANCE_SCALE: f64 = 10000.0;
+
+    /// `chromaticities` are red, green and blue x,y followed by the white point x,y.
+    pub fn new(
+        chromaticities: [f64; 8],
+        max_luminance: f64,
+        min_luminance: f64,
+    ) -> Result<MasteringDisplay> {
+        let mut scaled = [0; 8];
+        for (scaled, value) in scaled.iter_mut().zip(chromaticities) {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(invalid(format!("chromaticity {} is outside 0..1", value)));
+            }
+            *scaled = (value * Self::CHROMATICITY_SCALE).round() as u16;
+        }
+        let luminance = |value: f64| {
+            let scaled = (value * Self::LUMINANCE_SCALE).round();
+            match (0.0..=u32::MAX as f64).contains(&scaled) {
+                true => Ok(scaled as u32),
+                false => Err(invalid(format!("luminance {} is out of range", value))),
+            }
+        };
+        Ok(MasteringDisplay {
+            chromaticities: scaled,
+            max_luminance: luminance(max_luminance)?,
+            min_luminance: luminance(min_luminance)?,
+        })
+    }
+    pub fn max_luminance(&self) -> f64 {
+        self.max_luminance as f64 / Self::LUMINANCE_SCALE
+    }
+    pub fn min_luminance(&self) -> f64 {
+        self.min_luminance as f64 / Self::LUMINANCE_SCALE
+    }
+    pub fn to_chunk(self) -> Result<Chunk> {
+        let mut data: Vec<u8> = self
+            .chromaticities
+            .iter()
+            .flat_map(|c| c.to_be_bytes())
+            .collect();
+        data.extend(self.max_luminance.to_be_bytes());
+        data.extend(self.min_luminance.to_be_bytes());
+        Ok(Chunk::new(ChunkType::from_str("mDCv")?, data))
+    }
+}
+impl TryFrom<&Chunk> for MasteringDisplay {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "mDCv", Some(24))?;
+        let data = chunk.data();
+        let mut chromaticities = [0; 8];
+        for (index, value) in chromaticities.iter_mut().enumerate() {
+            *value = u16_at(data, 2 * index);
+        }
+        Ok(MasteringDisplay {
+            chromaticities,
+            max_luminance: u32_at(data, 16),
+            min_luminance: u32_at(data, 20),
+        })
+    }
+}
+impl Display for MasteringDisplay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let c = self
+            .chromaticities
+            .map(|c| c as f64 / Self::CHROMATICITY_SCALE);
+        write!(
+            f,
+            "mastering display red ({:.4}, {:.4}) green ({:.4}, {:.4}) blue ({:.4}, {:.4}) white ({:.4}, {:.4}), luminance {}–{} cd/m²",
+            c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7],
+            self.min_luminance(),
+            self.max_luminance()
+        )
+    }
+}
+
+/// cLLi: the brightest pixel (MaxCLL) and brightest frame average (MaxFALL) in the content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLightLevel {
+    /// Both in units of 0.0001 cd/m².
+    max_cll: u32,
+    max_fall: u32,
+}
+impl ContentLightLevel {
+    const SCALE: f64 = 10000.0;
+
+    pub fn new(max_cll: f64, max_fall: f64) -> Result<ContentLightLevel> {
+        let scale = |value: f64| {
+            let scaled = (value * Self::SCALE).round();
+            match (0.0..=u32::MAX as f64).contains(&scaled) {
+                true => Ok(scaled as u32),
+                false => Err(invalid(format!("light level {} is out of range", value))),
+            }
+        };
+        Ok(ContentLightLevel {
+            max_cll: scale(max_cll)?,
+            max_fall: scale(max_fall)?,
+        })
+    }
+    pub fn max_cll(&self) -> f64 {
+        self.max_cll as f64 / Self::SCALE
+    }
+    pub fn max_fall(&self) -> f64 {
+        self.max_fall as f64 / Self::SCALE
+    }
+    pub fn to_chunk(self) -> Result<Chunk> {
+        let mut data = self.max_cll.to_be_bytes().to_vec();
+        data.extend(self.max_fall.to_be_bytes());
+        Ok(Chunk::new(ChunkType::from_str("cLLi")?, data))
+    }
+}
+impl TryFrom<&Chunk> for ContentLightLevel {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "cLLi", Some(8))?;
+        Ok(ContentLightLevel {
+            max_cll: u32_at(chunk.data(), 0),
+            max_fall: u32_at(chunk.data(), 4),
+        })
+    }
+}
+impl Display for ContentLightLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "content light level MaxCLL {} cd/m², MaxFALL {} cd/m²",
+            self.max_cll(),
+            self.max_fall()
+        )
+    }
+}
+
+/// The decoded color chunks of a file.
+#[derive(Debug, Default)]
+pub struct ColorChunks {
+    gamma: Option<Gamma>,
+    chromaticities: Option<Chromaticities>,
+    intent: Option<RenderingIntent>,
+    icc: Option<IccProfile>,
+    cicp: Option<CodingIndependentCodePoints>,
+    mastering: Option<MasteringDisplay>,
+    light_level: Option<ContentLightLevel>,
+}
+impl ColorChunks {
+    pub fn from_png(png: &Png) -> Result<ColorChunks> {
+        let mut colors = ColorChunks::default();
+        for chunk in png.chunks() {
+            match &chunk.chunk_type().bytes() {
+                b"gAMA" => colors.gamma = Some(Gamma::try_from(chunk)?),
+                b"cHRM" => colors.chromaticities = Some(Chromaticities::try_from(chunk)?),
+                b"sRGB" => colors.intent = Some(RenderingIntent::try_from(chunk)?),
+                b"iCCP" => colors.icc = Some(IccProfile::try_from(chunk)?),
+                b"cICP" => colors.cicp = Some(CodingIndependentCodePoints::try_from(chunk)?),
+                b"mDCv" => colors.mastering = Some(MasteringDisplay::try_from(chunk)?),
+                b"cLLi" => colors.light_level = Some(ContentLightLevel::try_from(chunk)?),
+                _ => {}
+            }
+        }
+        Ok(colors)
+    }
+    pub fn icc(&self) -> Option<&IccProfile> {
+        self.icc.as_ref()
+    }
+}
+impl Display for ColorChunks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Listed in the order a decoder gives them precedence.
+        if let Some(cicp) = &self.cicp {
+            writeln!(f, "{}", cicp)?;
+        }
+        if let Some(icc) = &self.icc {
+            writeln!(f, "{}", icc)?;
+        }
+        if let Some(intent) = &self.intent {
+            writeln!(f, "{}", intent)?;
+        }
+        if let Some(gamma) = &self.gamma {
+            writeln!(f, "{}", gamma)?;
+        }
+        if let Some(chromaticities) = &self.chromaticities {
+            writeln!(f, "{}", chromaticities)?;
+        }
+        if let Some(mastering) = &self.mastering {
+            writeln!(f, "{}", mastering)?;
+        }
+        if let Some(light_level) = &self.light_level {
+            writeln!(f, "{}", light_level)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a color chunk before PLTE and IDAT, replacing any chunk of the same type and
+/// removing the one it excludes (sRGB and iCCP may not both be present). Returns the
+/// removed chunks.
+pub fn set_color_chunk(png: &mut Png, chunk: Chunk) -> Vec<Chunk> {
+    let excluded = match &chunk.chunk_type().bytes() {
+        b"sRGB" => Some("iCCP"),
+        b"iCCP" => Some("sRGB"),
+        _ => None,
+    };
+    let mut removed = vec![];
+    if let Some(excluded) = excluded {
+        while let Ok(chunk) = png.remove_chunk(excluded) {
+            removed.push(chunk);
+        }
+    }
+    png.set_chunk_before(&["PLTE", "IDAT"], chunk);
+    removed
+}
+
+/// Lists violations of the spec's ordering, multiplicity and exclusion rules.
+pub fn check_color_chunks(png: &Png) -> Vec<String> {
+    let mut problems = vec![];
+    let types: Vec<[u8; 4]> = png
+        .chunks()
+        .iter()
+        .map(|c| c.chunk_type().bytes())
+        .collect();
+    let first = |t: &[u8; 4]| types.iter().position(|existing| existing == t);
+    let image_start = [first(b"PLTE"), first(b"IDAT")].into_iter().flatten().min();
+    for name in COLOR_CHUNK_TYPES {
+        let bytes: [u8; 4] = name.as_bytes().try_into().unwrap();
+        let positions: Vec<usize> = (0..types.len()).filter(|&i| types[i] == bytes).collect();
+        if positions.len() > 1 {
+            problems.push(format!("{} appears {} times", name, positions.len()));
+        }
+        if let (Some(&last), Some(start)) = (positions.last(), image_start) {
+            if last > start {
+                problems.push(format!("{} must come before PLTE and IDAT", name));
+            }
+        }
+    }
+    if first(b"sRGB").is_some() && first(b"iCCP").is_some() {
+        problems.push("sRGB and iCCP must not both be present".to_string());
+    }
+    let colors = match ColorChunks::from_png(png) {
+        Ok(colors) => colors,
+        Err(e) => {
+            problems.push(e.to_string());
+            return problems;
+        }
+    };
+    problems.extend(check_hdr(&colors));
+    if let Ok(header) = png.image_header() {
+        if let Some(icc) = colors.icc() {
+            let expected: &[u8] = match header.color_type() {
+                ColorType::Grayscale | ColorType::GrayscaleAlpha => b"GRAY",
+                _ => b"RGB ",
+            };
+            if icc.color_space() != expected {
+                problems.push(format!(
+                    "ICC profile is for {} data but the image is {}",
+                    String::from_utf8_lossy(icc.color_space()).trim_end(),
+                    header.color_type()
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// HDR metadata only means something alongside a cICP chunk with a PQ or HLG transfer.
+fn check_hdr(colors: &ColorChunks) -> Vec<String> {
+    let mut problems = vec![];
+    let present: Vec<&str> = [
+        colors.mastering.map(|_| "mDCv"),
+        colors.light_level.map(|_| "cLLi"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    for name in &present {
+        match colors.cicp {
+            None => problems.push(format!("{} has no effect without a cICP chunk", name)),
+            Some(cicp) if !HDR_TRANSFERS.contains(&cicp.transfer) => problems.push(format!(
+                "{} is HDR metadata but cICP transfer {} is not PQ or HLG",
+                name, cicp.transfer
+            )),
+            Some(_) => {}
+        }
+    }
+    if let Some(mastering) = colors.mastering {
+        if mastering.min_luminance >= mastering.max_luminance {
+            problems.push("mDCv minimum luminance is not below the maximum".to_string());
+        }
+    }
+    if let Some(light_level) = colors.light_level {
+        if light_level.max_fall > light_level.max_cll {
+            problems.push("cLLi MaxFALL exceeds MaxCLL".to_string());
+        }
+        if let Some(mastering) = colors.mastering {
+            if light_level.max_cll() > mastering.max_luminance() {
+                problems.push(format!(
+                    "cLLi MaxCLL {} cd/m² exceeds the mastering display's {} cd/m²",
+                    light_level.max_cll(),
+                    mastering.max_luminance()
+                ));
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::png::tests::{types, PNG_FILE};
+
+    fn icc_profile(color_space: &[u8; 4]) -> Vec<u8> {
+        let mut profile = vec![0; 132];
+        profile[..4].copy_from_slice(&132u32.to_be_bytes());
+        profile[16..20].copy_from_slice(color_space);
+        profile[36..40].copy_from_slice(b"acsp");
+        profile
+    }
+
+    /// A profile naming "Bob" in a v2 description, a copyright and a v4 device model tag.
+    pub(crate) fn profile_with_text() -> Vec<u8> {
+        let utf16 =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_be_bytes).collect() };
+        let mut desc = b"desc\0\0\0\0".to_vec();
+        desc.extend(13u32.to_be_bytes());
+        desc.extend(b"Bob's screen\0");
+        desc.extend(0u32.to_be_bytes());
+        desc.extend(12u32.to_be_bytes());
+        desc.extend(utf16("Bob's screen"));
+        desc.extend([0, 0, 12]);
+        desc.extend(b"Bob's screen");
+        desc.resize(desc.len() + 55, 0);
+        let cprt = b"text\0\0\0\0Copyright Bob\0".to_vec();
+        let mut dmdd = b"mluc\0\0\0\0".to_vec();
+        dmdd.extend(1u32.to_be_bytes());
+        dmdd.extend(12u32.to_be_bytes());
+        dmdd.extend(b"enUS");
+        dmdd.extend(22u32.to_be_bytes());
+        dmdd.extend(28u32.to_be_bytes());
+        dmdd.extend(utf16("Bob's Panel"));
+
+        let mut profile = icc_profile(b"RGB ");
+        profile[84..100].fill(0xaa);
+        profile[128..132].copy_from_slice(&3u32.to_be_bytes());
+        let mut at = 132 + 12 * 3;
+        let mut data = vec![];
+        for (signature, tag) in [(b"desc", desc), (b"cprt", cprt), (b"dmdd", dmdd)] {
+            profile.extend(signature);
+            profile.extend((at as u32).to_be_bytes());
+            profile.extend((tag.len() as u32).to_be_bytes());
+            at += tag.len();
+            data.extend(tag);
+        }
+        profile.extend(data);
+        let size = (profile.len() as u32).to_be_bytes();
+        profile[..4].copy_from_slice(&size);
+        profile
+    }
+
+    #[test]
+    fn test_blank_text_tags() {
+        let original = profile_with_text();
+        let mut icc = IccProfile::new("Display", original.clone()).unwrap();
+        assert_eq!(icc.blank_text_tags("ICC profile"), ["desc", "cprt", "dmdd"]);
+        let profile = icc.profile();
+        assert_eq!(profile.len(), original.len());
+        assert_eq!(profile[..84], original[..84]);
+        assert_eq!(profile[84..100], [0; 16]);
+        assert!(!profile.windows(3).any(|w| w == b"Bob"));
+        assert!(!profile.windows(6).any(|w| w == [0, b'B', 0, b'o', 0, b'b']));
+        assert!(profile.windows(12).any(|w| w == b"ICC profile\0"));
+        assert!(icc.blank_text_tags("ICC profile").is_empty());
+    }
+
+    #[test]
+    fn test_gamma_round_trip() {
+        let gamma = Gamma::new(1.0 / 2.2).unwrap();
+        let chunk = gamma.to_chunk().unwrap();
+        assert_eq!(chunk.data(), &45455u32.to_be_bytes());
+        assert_eq!(Gamma::try_from(&chunk).unwrap(), gamma);
+        assert_eq!(gamma.to_string(), "gamma 0.45455 (display exponent 2.20)");
+        assert!(Gamma::new(0.0).is_err());
+    }
+
+    #[test]
+    fn test_chromaticities_round_trip() {
+        let srgb = Chromaticities::new([0.3127, 0.329, 0.64, 0.33, 0.3, 0.6, 0.15, 0.06]).unwrap();
+        assert_eq!(srgb, Chromaticities::SRGB);
+        let chunk = srgb.to_chunk().unwrap();
+        assert_eq!(chunk.length(), 32);
+        assert_eq!(Chromaticities::try_from(&chunk).unwrap(), srgb);
+        assert!(srgb.to_string().ends_with("[sRGB]"));
+    }
+
+    #[test]
+    fn test_rendering_intent() {
+        let chunk = RenderingIntent::Saturation.to_chunk().unwrap();
+        assert_eq!(chunk.data(), [2]);
+        assert_eq!(
+            RenderingIntent::try_from(&chunk).unwrap(),
+            RenderingIntent::Saturation
+        );
+        let bad = Chunk::new(ChunkType::from_str("sRGB").unwrap(), vec![4]);
+        assert!(RenderingIntent::try_from(&bad).is_err());
+    }
+
+    #[test]
+    fn test_icc_profile_round_trip() {
+        let icc = IccProfile::new("Display", icc_profile(b"RGB ")).unwrap();
+        let chunk = icc.to_chunk().unwrap();
+        assert_eq!(&chunk.data()[..9], b"Display\0\0");
+        assert_eq!(IccProfile::try_from(&chunk).unwrap(), icc);
+        assert!(IccProfile::new("Display", vec![0; 200]).is_err());
+        assert!(IccProfile::new("", icc_profile(b"RGB ")).is_err());
+    }
+
+    #[test]
+    fn test_cicp_round_trip() {
+        let cicp = CodingIndependentCodePoints::new(9, 16, true);
+        let chunk = cicp.to_chunk().unwrap();
+        assert_eq!(chunk.data(), [9, 16, 0, 1]);
+        assert_eq!(CodingIndependentCodePoints::try_from(&chunk).unwrap(), cicp);
+        assert_eq!(
+            cicp.to_string(),
+            "cICP primaries 9 (BT.2020), transfer 16 (PQ), full range"
+        );
+        let ycbcr = Chunk::new(ChunkType::from_str("cICP").unwrap(), vec![9, 16, 9, 1]);
+        assert!(CodingIndependentCodePoints::try_from(&ycbcr).is_err());
+    }
+
+    fn bt2020_display() -> MasteringDisplay {
+        let primaries = [0.708, 0.292, 0.17, 0.797, 0.131, 0.046, 0.3127, 0.329];
+        MasteringDisplay::new(primaries, 1000.0, 0.005).unwrap()
+    }
+
+    #[test]
+    fn test_mastering_display_round_trip() {
+        let display = bt2020_display();
+        let chunk = display.to_chunk().unwrap();
+        assert_eq!(chunk.length(), 24);
+        assert_eq!(&chunk.data()[..2], &35400u16.to_be_bytes());
+        assert_eq!(&chunk.data()[16..20], &10_000_000u32.to_be_bytes());
+        assert_eq!(MasteringDisplay::try_from(&chunk).unwrap(), display);
+        assert!(display.to_string().ends_with("luminance 0.005–1000 cd/m²"));
+        assert!(MasteringDisplay::new([1.5; 8], 1000.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_content_light_level_round_trip() {
+        let level = ContentLightLevel::new(1000.0, 400.0).unwrap();
+        let chunk = level.to_chunk().unwrap();
+        assert_eq!(chunk.data()[..4], 10_000_000u32.to_be_bytes());
+        assert_eq!(ContentLightLevel::try_from(&chunk).unwrap(), level);
+        assert_eq!(
+            level.to_string(),
+            "content light level MaxCLL 1000 cd/m², MaxFALL 400 cd/m²"
+        );
+    }
+
+    #[test]
+    fn test_check_hdr_against_cicp() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        set_color_chunk(&mut png, bt2020_display().to_chunk().unwrap());
+        let level = ContentLightLevel::new(1200.0, 1300.0).unwrap();
+        set_color_chunk(&mut png, level.to_chunk().unwrap());
+        assert_eq!(
+            check_color_chunks(&png),
+            [
+                "mDCv has no effect without a cICP chunk",
+                "cLLi has no effect without a cICP chunk",
+                "cLLi MaxFALL exceeds MaxCLL",
+                "cLLi MaxCLL 1200 cd/m² exceeds the mastering display's 1000 cd/m²",
+            ]
+        );
+
+        let sdr = CodingIndependentCodePoints::new(1, 13, true);
+        set_color_chunk(&mut png, sdr.to_chunk().unwrap());
+        let level = ContentLightLevel::new(800.0, 300.0).unwrap();
+        set_color_chunk(&mut png, level.to_chunk().unwrap());
+        assert_eq!(check_color_chunks(&png).len(), 2);
+
+        let pq = CodingIndependentCodePoints::new(9, 16, true);
+        set_color_chunk(&mut png, pq.to_chunk().unwrap());
+        assert!(check_color_chunks(&png).is_empty());
+    }
+
+    #[test]
+    fn test_set_color_chunk_ordering_and_exclusion() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.remove_chunk("sRGB").unwrap();
+        png.insert_before_end(RenderingIntent::Perceptual.to_chunk().unwrap());
+        assert_eq!(
+            check_color_chunks(&png),
+            ["sRGB must come before PLTE and IDAT"]
+        );
+
+        let icc = IccProfile::new("Display", icc_profile(b"RGB ")).unwrap();
+        let removed = set_color_chunk(&mut png, icc.to_chunk().unwrap());
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].chunk_type().bytes(), b"sRGB");
+        let types = types(&png);
+        let iccp = types.iter().position(|t| t == "iCCP").unwrap();
+        let idat = types.iter().position(|t| t == "IDAT").unwrap();
+        assert!(iccp < idat);
+        assert!(check_color_chunks(&png).is_empty());
+        assert_eq!(ColorChunks::from_png(&png).unwrap().icc(), Some(&icc));
+    }
+
+    #[test]
+    fn test_check_profile_color_space() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let icc = IccProfile::new("Gray", icc_profile(b"GRAY")).unwrap();
+        set_color_chunk(&mut png, icc.to_chunk().unwrap());
+        assert_eq!(
+            check_color_chunks(&png),
+            ["ICC profile is for GRAY data but the image is rgba"]
+        );
+    }
+}
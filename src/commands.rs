@@ -1,36 +1,102 @@
+use crate::analysis;
+use crate::apng::Animation;
 use crate::args::*;
-use crate::png::{Chunk, ChunkType, Png};
+use crate::codec::{ChunkCodec, CodecRegistry, HexCodec, TextCodec};
+use crate::color::{
+    check_color_chunks, set_color_chunk, Chromaticities, CodingIndependentCodePoints, ColorChunks,
+    ContentLightLevel, Gamma, IccProfile, MasteringDisplay, RenderingIntent,
+};
+use crate::diff::Diff;
+use crate::exif::Exif;
+use crate::hexdump::hex_dump;
+use crate::image::ImageData;
+use crate::manifest::{self, Manifest};
+use crate::output::{self, Capacity, DecodedChunks, DecodedMessage, Inspection};
+use crate::patch::Patch;
+use crate::phys::{set_physical_dimensions, PhysicalDimensions};
+use crate::png::{Chunk, ChunkType, ParseOptions, Png};
+use crate::scrub::ScrubPolicy;
+use crate::stego;
+use crate::text::{CompressedTextChunk, InternationalTextChunk, TextChunk, TextualChunk};
+use crate::timestamp::{self, LastModified};
+use crate::xmp::Xmp;
 use crate::Result;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::str::FromStr;
 
 pub fn encode(args: EncodeArgs) -> Result<()> {
-    let chunk = Chunk::new(
-        ChunkType::from_str(&args.chunk_type)?,
-        args.message.as_bytes().into(),
-    );
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
     let mut png = from_file(&args.path)?;
-    let removed = png.remove_chunk("IEND");
-    png.append_chunk(chunk.clone());
-    if let Ok(end_chunk) = removed {
-        png.append_chunk(end_chunk);
-    };
+    match args.method {
+        Method::Chunk => {
+            let chunk = Chunk::new(chunk_type, args.message.as_bytes().into());
+            let removed = png.remove_chunk("IEND");
+            png.append_chunk(chunk.clone());
+            if let Ok(end_chunk) = removed {
+                png.append_chunk(end_chunk);
+            };
+            println!("added new chunk {}", chunk);
+        }
+        Method::Palette => {
+            stego::embed_in_palette(&mut png, &chunk_type, args.message.as_bytes())?;
+            println!(
+                "hid {} bytes in palette order as {}",
+                args.message.len(),
+                chunk_type
+            );
+        }
+        Method::Filter => {
+            stego::embed_in_filters(&mut png, &chunk_type, args.message.as_bytes())?;
+            println!(
+                "hid {} bytes in scanline filter types as {}",
+                args.message.len(),
+                chunk_type
+            );
+        }
+        Method::Trailer => {
+            stego::embed_in_trailer(&mut png, &chunk_type, args.message.as_bytes());
+            println!(
+                "appended {} bytes after IEND as {}",
+                args.message.len(),
+                chunk_type
+            );
+        }
+    }
+    touch_if(&mut png, args.touch)?;
     if let Some(out_path) = &args.output_file {
         to_file(out_path, png)?;
     } else {
         to_file(&args.path, png)?;
     }
-    println!("added new chunk {}", chunk);
     Ok(())
 }
 
 pub fn decode(args: DecodeArgs) -> Result<()> {
     let png = from_file(args.path)?;
-    let res = png.chunk_by_type(&args.chunk_type);
-
-    if let Some(chunk) = res {
-        println!("{}", String::from_utf8_lossy(chunk.data()));
+    let message = match args.method {
+        Method::Chunk => png
+            .chunk_by_type(&args.chunk_type)
+            .map(|chunk| chunk.data().to_vec()),
+        Method::Palette => {
+            let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+            Some(stego::extract_from_palette(&png, &chunk_type)?)
+        }
+        Method::Filter => {
+            let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+            Some(stego::extract_from_filters(&png, &chunk_type)?)
+        }
+        Method::Trailer => {
+            let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+            Some(stego::extract_from_trailer(&png, &chunk_type)?)
+        }
+    };
+    if args.format != Format::Text {
+        let decoded = DecodedMessage::new(&args.chunk_type, message.as_deref());
+        println!("{}", output::render(&decoded, args.format)?);
+    } else if let Some(message) = message {
+        println!("{}", String::from_utf8_lossy(&message));
     }
     Ok(())
 }
@@ -41,6 +107,7 @@ pub fn remove(args: RemoveArgs) -> Result<()> {
 
     if let Some(_chunk) = res {
         let removed = png.remove_chunk(&args.chunk_type)?;
+        touch_if(&mut png, args.touch)?;
         to_file(args.path, png)?;
         println!("removed chunk: {}", removed);
     } else {
@@ -49,9 +116,806 @@ pub fn remove(args: RemoveArgs) -> Result<()> {
     Ok(())
 }
 
+pub fn update(args: UpdateArgs) -> Result<()> {
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    let mut png = from_file(&args.path)?;
+    let old = png
+        .update_chunk(&chunk_type, args.message.as_bytes(), args.append)
+        .map_err(|_| format!("no {} chunk to update, use encode to add one", chunk_type))?;
+    let kept = if args.append { old.data().len() } else { 0 };
+    println!(
+        "updated {} in place: {} bytes, was {}",
+        chunk_type,
+        kept + args.message.len(),
+        old.length()
+    );
+    touch_if(&mut png, args.touch)?;
+    to_file(args.output_file.as_ref().unwrap_or(&args.path), png)
+}
+
 pub fn print(args: PrintArgs) -> Result<()> {
-    let png = from_file(args.path)?;
+    let (png, diagnostics) = if args.lenient {
+        Png::parse_with(&fs::read(&args.path)?, ParseOptions::default())?
+    } else {
+        (from_file(args.path)?, vec![])
+    };
+    let mut registry = CodecRegistry::with_builtins();
+    for decode_as in &args.decode_as {
+        let (chunk_type, codec) = decode_as
+            .split_once('=')
+            .ok_or_else(|| format!("expected TYPE=CODEC, found {:?}", decode_as))?;
+        let codec: Box<dyn ChunkCodec> = match codec {
+            "text" => Box::new(TextCodec),
+            "hex" => Box::new(HexCodec),
+            _ => return Err(format!("unknown codec {:?}, expected text or hex", codec).into()),
+        };
+        registry.register(ChunkType::from_str(chunk_type)?, codec);
+    }
+    if args.format != Format::Text {
+        let inspection = Inspection::new(&png, &registry).with_diagnostics(&diagnostics);
+        println!("{}", output::render(&inspection, args.format)?);
+        return Ok(());
+    }
     println!("{}", png);
+    let offsets = png.chunk_offsets();
+    for (index, (chunk, offset)) in png.chunks().iter().zip(offsets).enumerate() {
+        let position = format!("{} {} at offset {}", index, chunk.chunk_type(), offset);
+        match registry.decode(chunk, &png) {
+            Some(Ok(fields)) => {
+                println!("{}:", position);
+                for (name, value) in fields {
+                    println!("    {}: {}", name, value);
+                }
+            }
+            Some(Err(e)) => println!("{}: invalid: {}", position, e),
+            None => {}
+        }
+    }
+    if !diagnostics.is_empty() {
+        println!("diagnostics:");
+        for diagnostic in &diagnostics {
+            println!("    {}", diagnostic);
+        }
+    }
+    Ok(())
+}
+
+pub fn analyze(args: AnalyzeArgs) -> Result<()> {
+    let report = analysis::analyze(&fs::read(&args.path)?)?;
+    if args.format != Format::Text {
+        println!("{}", output::render(&report, args.format)?);
+        return Ok(());
+    }
+    println!("{}", args.path);
+    print!("{}", report);
+    Ok(())
+}
+
+pub fn strip_trailer(args: StripTrailerArgs) -> Result<()> {
+    let mut png = from_file(&args.path)?;
+    let removed = png.trailing_data().len();
+    png.set_trailing_data(vec![]);
+    touch_if(&mut png, args.touch)?;
+    to_file(args.output_file.as_ref().unwrap_or(&args.path), png)?;
+    println!("removed {} bytes after IEND", removed);
+    Ok(())
+}
+
+pub fn capacity(args: CapacityArgs) -> Result<()> {
+    let png = from_file(&args.path)?;
+    let header = png.image_header()?;
+    // Decoded once; a failure is reported against every method that needs the pixels.
+    let image = ImageData::decode(&png);
+    let with_image = |capacity: &dyn Fn(&ImageData) -> Result<usize>| match &image {
+        Ok(image) => capacity(image),
+        Err(error) => Err(error.to_string().into()),
+    };
+    let mut methods: Vec<(String, Result<usize>)> = (1..=4)
+        .map(|planes| {
+            let capacity = with_image(&|image| stego::lsb_capacity(image, planes));
+            (format!("lsb_{}", planes), capacity)
+        })
+        .collect();
+    methods.push(("palette".to_string(), stego::palette_capacity(&png)));
+    methods.push(("filter".to_string(), with_image(&stego::filter_capacity)));
+    if args.format != Format::Text {
+        let capacity = Capacity::new(&header, &methods);
+        println!("{}", output::render(&capacity, args.format)?);
+        return Ok(());
+    }
+    println!(
+        "{}: {}x{} {}-bit {}",
+        args.path,
+        header.width(),
+        header.height(),
+        header.bit_depth(),
+        header.color_type()
+    );
+    println!("  chunk:           {} bytes per chunk", Chunk::MAX_LENGTH);
+    println!(
+        "  trailer:         unlimited, {} bytes framing per message",
+        stego::TRAILER_OVERHEAD
+    );
+    for (name, capacity) in &methods {
+        let label = match name.strip_prefix("lsb_") {
+            Some(planes) => format!(
+                "lsb ({} plane{}):",
+                planes,
+                if planes == "1" { "" } else { "s" }
+            ),
+            None => format!("{}:", name),
+        };
+        print_capacity(&label, capacity);
+    }
+    println!(
+        "  (bounded methods exclude their {}-byte type and length header)",
+        stego::FRAME_OVERHEAD
+    );
+    Ok(())
+}
+
+fn print_capacity(label: &str, capacity: &Result<usize>) {
+    match capacity {
+        Ok(bytes) => println!("  {:<16} {} bytes", label, bytes),
+        Err(e) => println!("  {:<16} n/a ({})", label, e),
+    }
+}
+
+pub fn text(args: TextArgs) -> Result<()> {
+    match args.command {
+        TextCommand::List(list_args) => text_list(list_args),
+        TextCommand::Get(get_args) => text_get(get_args),
+        TextCommand::Set(set_args) => text_set(set_args),
+        TextCommand::Delete(delete_args) => text_delete(delete_args),
+    }
+}
+
+/// Every text chunk in `png` with its index, skipping any that fail to parse.
+fn text_chunks(png: &Png) -> Vec<(usize, TextualChunk)> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| TextualChunk::is_text_type(chunk.chunk_type()))
+        .filter_map(|(index, chunk)| Some((index, TextualChunk::try_from(chunk).ok()?)))
+        .collect()
+}
+
+fn text_list(args: TextListArgs) -> Result<()> {
+    let png = from_file(args.path)?;
+    if args.format != Format::Text {
+        let texts = DecodedChunks::new(&png, &["tEXt", "zTXt", "iTXt"]);
+        println!("{}", output::render(&texts, args.format)?);
+        return Ok(());
+    }
+    for (index, text) in text_chunks(&png) {
+        println!("[{}] {}", index, text);
+    }
+    Ok(())
+}
+
+fn text_get(args: TextGetArgs) -> Result<()> {
+    let png = from_file(args.path)?;
+    match text_chunks(&png)
+        .into_iter()
+        .find(|(_, text)| text.keyword() == args.keyword)
+    {
+        Some((_, text)) => println!("{}", text.text()),
+        None => println!("keyword not found: {}", args.keyword),
+    }
+    Ok(())
+}
+
+fn text_set(args: TextSetArgs) -> Result<()> {
+    let mut png = from_file(&args.path)?;
+    let international =
+        args.compress || !args.language.is_empty() || !args.translated_keyword.is_empty();
+    let kind = match args.kind {
+        Some(kind) => kind,
+        None if international || TextChunk::new(&args.keyword, &args.text).is_err() => {
+            TextKind::Itxt
+        }
+        None => TextKind::Text,
+    };
+    let text = match kind {
+        TextKind::Text => TextualChunk::Text(TextChunk::new(&args.keyword, &args.text)?),
+        TextKind::Ztxt => {
+            TextualChunk::Compressed(CompressedTextChunk::new(&args.keyword, &args.text)?)
+        }
+        TextKind::Itxt => TextualChunk::International(InternationalTextChunk::new(
+            &args.keyword,
+            args.compress,
+            &args.language,
+            &args.translated_keyword,
+            &args.text,
+        )?),
+    };
+
+    let existing: Vec<usize> = text_chunks(&png)
+        .into_iter()
+        .filter(|(_, existing)| existing.keyword() == args.keyword)
+        .map(|(index, _)| index)
+        .collect();
+    for &index in existing.iter().skip(1).rev() {
+        png.remove_chunk_at(index)?;
+    }
+    match existing.first() {
+        Some(&index) => {
+            png.remove_chunk_at(index)?;
+            png.insert_chunk(index, text.to_chunk()?);
+        }
+        None => png.insert_before_end(text.to_chunk()?),
+    }
+    touch_if(&mut png, args.touch)?;
+    to_file(&args.path, png)?;
+    println!("set {}", text);
+    Ok(())
+}
+
+fn text_delete(args: TextDeleteArgs) -> Result<()> {
+    let mut png = from_file(&args.path)?;
+    let existing: Vec<usize> = text_chunks(&png)
+        .into_iter()
+        .filter(|(_, text)| text.keyword() == args.keyword)
+        .map(|(index, _)| index)
+        .collect();
+    for &index in existing.iter().rev() {
+        png.remove_chunk_at(index)?;
+    }
+    touch_if(&mut png, args.touch)?;
+    to_file(&args.path, png)?;
+    println!(
+        "removed {} text chunks with keyword {}",
+        existing.len(),
+        args.keyword
+    );
+    Ok(())
+}
+
+pub fn xmp(args: XmpArgs) -> Result<()> {
+    match args.command {
+        XmpCommand::Show(show_args) => xmp_show(show_args),
+        XmpCommand::Set(set_args) => xmp_set(set_args),
+        XmpCommand::Delete(delete_args) => xmp_delete(delete_args),
+    }
+}
+
+fn read_xmp(png: &Png) -> Result<Option<(usize, Xmp)>> {
+    match png.chunks().iter().position(Xmp::is_xmp_chunk) {
+        Some(index) => Ok(Some((index, Xmp::try_from(&png.chunks()[index])?))),
+        None => Ok(None),
+    }
+}
+
+/// Stores `xmp` in place of the chunk at `index`, moving it ahead of IDAT if it was after.
+fn write_xmp(png: &mut Png, index: Option<usize>, xmp: &Xmp) -> Result<()> {
+    let chunk = xmp.to_chunk()?;
+    let idat = png
+        .chunks()
+        .iter()
+        .position(|chunk| &chunk.chunk_type().bytes() == b"IDAT");
+    if let Some(index) = index {
+        png.remove_chunk_at(index)?;
+        if idat.is_some_and(|idat| index < idat) {
+            png.insert_chunk(index, chunk);
+            return Ok(());
+        }
+    }
+    png.insert_before_first(&["IDAT"], chunk);
+    Ok(())
+}
+
+fn xmp_show(args: XmpShowArgs) -> Result<()> {
+    let png = from_file(args.path)?;
+    let Some((_, xmp)) = read_xmp(&png)? else {
+        println!("no XMP packet");
+        return Ok(());
+    };
+    if args.format != Format::Text {
+        let rendered = match &args.property {
+            Some(property) => output::render(&xmp.get(property)?, args.format)?,
+            None => output::render(&xmp, args.format)?,
+        };
+        println!("{}", rendered);
+        return Ok(());
+    }
+    match args.property {
+        Some(property) => match xmp.get(&property)? {
+            Some(value) => print!("{}", value),
+            None => println!("property not found: {}", property),
+        },
+        None => print!("{}", xmp),
+    }
+    Ok(())
+}
+
+fn xmp_set(args: XmpSetArgs) -> Result<()> {
+    let mut png = from_file(&args.path)?;
+    let (index, mut xmp) = match read_xmp(&png)? {
+        Some((index, xmp)) => (Some(index), xmp),
+        None => (None, Xmp::default()),
+    };
+    xmp.set(&args.property, &args.value, args.namespace.as_deref())?;
+    write_xmp(&mut png, index, &xmp)?;
+    touch_if(&mut png, args.touch)?;
+    to_file(&args.path, png)?;
+    println!("set {} = {}", args.property, args.value);
+    Ok(())
+}
+
+fn xmp_delete(args: XmpDeleteArgs) -> Result<()> {
+    let mut png = from_file(&args.path)?;
+    let Some((index, mut xmp)) = read_xmp(&png)? else {
+        println!("no XMP packet");
+        return Ok(());
+    };
+    match &args.property {
+        Some(property) => {
+            xmp.remove(property)?;
+            write_xmp(&mut png, Some(index), &xmp)?;
+            println!("removed {}", property);
+        }
+        None => {
+            png.remove_chunk_at(index)?;
+            println!("removed XMP packet");
+        }
+    }
+    touch_if(&mut png, args.touch)?;
+    to_file(&args.path, png)?;
+    Ok(())
+}
+
+pub fn exif(args: ExifArgs) -> Result<()> {
+    match args.command {
+        ExifCommand::Show(show_args) => exif_show(show_args),
+        ExifCommand::Scrub(scrub_args) => exif_scrub(scrub_args),
+    }
+}
+
+fn read_exif(png: &Png) -> Result<Option<Exif>> {
+    match png
+        .chunks()
+        .iter()
+        .find(|chunk| &chunk.chunk_type().bytes() == b"eXIf")
+    {
+        Some(chunk) => Ok(Some(Exif::try_from(chunk)?)),
+        None => Ok(None),
+    }
+}
+
+fn exif_show(args: ExifShowArgs) -> Result<()> {
+    let png = from_file(args.path)?;
+    let Some(exif) = read_exif(&png)? else {
+        println!("no eXIf chunk");
+        return Ok(());
+    };
+    if args.format != Format::Text {
+        println!("{}", output::render(&exif, args.format)?);
+        return Ok(());
+    }
+    if let Some(camera) = exif.camera() {
+        println!("camera: {}", camera);
+    }
+    if let Some(timestamp) = exif.timestamp() {
+        println!("taken: {}", timestamp);
+    }
+    if let Some(orientation) = exif.orientation() {
+        println!("orientation: {}", orientation);
+    }
+    print!("{}", exif);
+    Ok(())
+}
+
+fn exif_scrub(args: ExifScrubArgs) -> Result<()> {
+    if !args.gps && !args.serials {
+        return Err("nothing to scrub: pass --gps and/or --serials".into());
+    }
+    let mut png = from_file(&args.path)?;
+    let Some(mut exif) = read_exif(&png)? else {
+        println!("no eXIf chunk");
+        return Ok(());
+    };
+    if args.gps {
+        match exif.remove_gps()? {
+            true => println!("removed GPS data"),
+            false => println!("no GPS data"),
+        }
+    }
+    if args.serials {
+        println!("removed {} owner/serial tags", exif.remove_serials()?);
+    }
+    png.replace_chunk(exif.to_chunk()?)?;
+    touch_if(&mut png, args.touch)?;
+    to_file(args.output_file.as_ref().unwrap_or(&args.path), png)?;
+    Ok(())
+}
+
+pub fn scrub(args: ScrubArgs) -> Result<()> {
+    let parse = |types: &[String]| -> Result<Vec<ChunkType>> {
+        types.iter().map(|t| ChunkType::from_str(t)).collect()
+    };
+    let policy = ScrubPolicy::new(parse(&args.keep)?, parse(&args.drop)?)?;
+    let mut png = from_file(&args.path)?;
+    let report = crate::scrub::scrub(&mut png, &policy)?;
+    if report.is_empty() {
+        println!("nothing to remove");
+    }
+    for line in report {
+        println!("{}", line);
+    }
+    to_file(args.output_file.as_ref().unwrap_or(&args.path), png)?;
+    Ok(())
+}
+
+pub fn color(args: ColorArgs) -> Result<()> {
+    match args.command {
+        ColorCommand::Show(show_args) => color_show(show_args),
+        ColorCommand::Validate(validate_args) => color_validate(validate_args),
+        ColorCommand::Set(set_args) => color_set(set_args),
+        ColorCommand::ExtractIcc(extract_args) => extract_icc(extract_args),
+        ColorCommand::EmbedIcc(embed_args) => embed_icc(embed_args),
+    }
+}
+
+/// The chunk types `color show` covers.
+const COLOR_TYPES: [&str; 7] = ["gAMA", "cHRM", "sRGB", "iCCP", "cICP", "mDCv", "cLLi"];
+
+fn color_show(args: ColorShowArgs) -> Result<()> {
+    let png = from_file(args.path)?;
+    if args.format != Format::Text {
+        let colors = DecodedChunks::new(&png, &COLOR_TYPES).with_warnings(check_color_chunks(&png));
+        println!("{}", output::render(&colors, args.format)?);
+        return Ok(());
+    }
+    let colors = ColorChunks::from_png(&png)?.to_string();
+    if colors.is_empty() {
+        println!("no color chunks");
+    }
+    print!("{}", colors);
+    for problem in check_color_chunks(&png) {
+        println!("warning: {}", problem);
+    }
+    Ok(())
+}
+
+fn color_validate(args: ColorShowArgs) -> Result<()> {
+    let png = from_file(args.path)?;
+    let problems = check_color_chunks(&png);
+    if args.format != Format::Text {
+        let colors = DecodedChunks::new(&png, &COLOR_TYPES).with_warnings(problems.clone());
+        println!("{}", output::render(&colors, args.format)?);
+    } else {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+    }
+    match problems.len() {
+        0 => {
+            if args.format == Format::Text {
+                println!("color chunks are valid");
+            }
+            Ok(())
+        }
+        count => Err(format!("{} problem(s) found", count).into()),
+    }
+}
+
+fn color_set(args: ColorSetArgs) -> Result<()> {
+    let mut png = from_file(&args.path)?;
+    let mut chunks = vec![];
+    if let Some(gamma) = args.gamma {
+        chunks.push(Gamma::new(gamma)?.to_chunk()?);
+    }
+    if !args.chromaticities.is_empty() {
+        let points = args.chromaticities[..]
+            .try_into()
+            .map_err(|_| "--chromaticities takes 8 values")?;
+        chunks.push(Chromaticities::new(points)?.to_chunk()?);
+    }
+    if let Some(intent) = args.srgb {
+        let intent = match intent {
+            Intent::Perceptual => RenderingIntent::Perceptual,
+            Intent::Relative => RenderingIntent::RelativeColorimetric,
+            Intent::Saturation => RenderingIntent::Saturation,
+            Intent::Absolute => RenderingIntent::AbsoluteColorimetric,
+        };
+        chunks.push(intent.to_chunk()?);
+    }
+    if !args.cicp.is_empty() {
+        if args.cicp.len() != 4 {
+            return Err("--cicp takes 4 values".into());
+        }
+        let chunk = Chunk::new(ChunkType::from_str("cICP")?, args.cicp.clone());
+        chunks.push(CodingIndependentCodePoints::try_from(&chunk)?.to_chunk()?);
+    }
+    if !args.mastering.is_empty() {
+        let [chromaticities @ .., max, min]: [f64; 10] = args.mastering[..]
+            .try_into()
+            .map_err(|_| "--mastering takes 10 values")?;
+        chunks.push(MasteringDisplay::new(chromaticities, max, min)?.to_chunk()?);
+    }
+    if !args.light_level.is_empty() {
+        let [max_cll, max_fall]: [f64; 2] = args.light_level[..]
+            .try_into()
+            .map_err(|_| "--light-level takes 2 values")?;
+        chunks.push(ContentLightLevel::new(max_cll, max_fall)?.to_chunk()?);
+    }
+    if chunks.is_empty() {
+        return Err("nothing to set: pass --gamma, --chromaticities, --srgb, --cicp, --mastering or --light-level".into());
+    }
+    for chunk in chunks {
+        for removed in set_color_chunk(&mut png, chunk.clone()) {
+            println!("removed {} chunk", removed.chunk_type());
+        }
+        println!("set {} chunk", chunk.chunk_type());
+    }
+    for problem in check_color_chunks(&png) {
+        println!("warning: {}", problem);
+    }
+    touch_if(&mut png, args.touch)?;
+    to_file(&args.path, png)?;
+    Ok(())
+}
+
+fn extract_icc(args: ExtractIccArgs) -> Result<()> {
+    let png = from_file(args.path)?;
+    let colors = ColorChunks::from_png(&png)?;
+    let icc = colors.icc().ok_or("no iCCP chunk")?;
+    fs::write(&args.output_file, icc.profile())?;
+    println!("wrote {}", icc);
+    Ok(())
+}
+
+fn embed_icc(args: EmbedIccArgs) -> Result<()> {
+    let mut png = from_file(&args.path)?;
+    let icc = IccProfile::new(&args.name, fs::read(&args.profile)?)?;
+    for removed in set_color_chunk(&mut png, icc.to_chunk()?) {
+        println!("removed {} chunk", removed.chunk_type());
+    }
+    for problem in check_color_chunks(&png) {
+        println!("warning: {}", problem);
+    }
+    touch_if(&mut png, args.touch)?;
+    to_file(&args.path, png)?;
+    println!("embedded {}", icc);
+    Ok(())
+}
+
+pub fn dpi(args: DpiArgs) -> Result<()> {
+    match args.command {
+        DpiCommand::Get(get_args) => {
+            let png = from_file(get_args.path)?;
+            let chunk = png.unique_chunk("pHYs")?;
+            if get_args.format != Format::Text {
+                let dimensions = DecodedChunks::new(&png, &["pHYs"]);
+                println!("{}", output::render(&dimensions, get_args.format)?);
+                return Ok(());
+            }
+            match chunk {
+                Some(chunk) => println!("{}", PhysicalDimensions::try_from(chunk)?),
+                None => println!("no pHYs chunk"),
+            }
+        }
+        DpiCommand::Set(set_args) => {
+            let mut png = from_file(&set_args.path)?;
+            let dimensions = PhysicalDimensions::from_dpi(set_args.value)?;
+            set_physical_dimensions(&mut png, dimensions)?;
+            touch_if(&mut png, set_args.touch)?;
+            to_file(&set_args.path, png)?;
+            println!("set {}", dimensions);
+        }
+    }
+    Ok(())
+}
+
+pub fn touch(args: TouchArgs) -> Result<()> {
+    let mut png = from_file(&args.path)?;
+    let time = match &args.time {
+        Some(time) => LastModified::from_str(time)?,
+        None => LastModified::now()?,
+    };
+    timestamp::touch(&mut png, time)?;
+    to_file(&args.path, png)?;
+    println!("set tIME to {}", time);
+    Ok(())
+}
+
+pub fn apng(args: ApngArgs) -> Result<()> {
+    match args.command {
+        ApngCommand::Info(info_args) => apng_info(info_args),
+        ApngCommand::Extract(extract_args) => apng_extract(extract_args),
+        ApngCommand::Assemble(assemble_args) => apng_assemble(assemble_args),
+    }
+}
+
+fn read_animation(png: &Png) -> Result<Animation> {
+    Animation::from_png(png)?.ok_or_else(|| "not an animated PNG: no acTL chunk".into())
+}
+
+fn apng_info(args: ApngInfoArgs) -> Result<()> {
+    let png = from_file(args.path)?;
+    let animation = read_animation(&png)?;
+    if args.format != Format::Text {
+        println!("{}", output::render(&animation, args.format)?);
+        return Ok(());
+    }
+    println!("{}", animation.control());
+    for (index, frame) in animation.frames().iter().enumerate() {
+        let default = if frame.is_default_image() {
+            " (default image)"
+        } else {
+            ""
+        };
+        println!(
+            "frame {}: {}, {} bytes{}",
+            index,
+            frame.control(),
+            frame.data().len(),
+            default
+        );
+    }
+    Ok(())
+}
+
+fn apng_extract(args: ApngExtractArgs) -> Result<()> {
+    let png = from_file(&args.path)?;
+    let animation = read_animation(&png)?;
+    fs::create_dir_all(&args.output_dir)?;
+    for index in 0..animation.frames().len() {
+        let path = Path::new(&args.output_dir).join(format!("frame_{:03}.png", index));
+        to_file(&path, animation.frame_png(&png, index)?)?;
+        println!("wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn apng_assemble(args: ApngAssembleArgs) -> Result<()> {
+    let frames = args
+        .frames
+        .iter()
+        .map(from_file)
+        .collect::<Result<Vec<Png>>>()?;
+    let apng = Animation::assemble(&frames, &args.delay, args.plays)?;
+    to_file(&args.output_file, apng)?;
+    println!("wrote {} frames to {}", frames.len(), args.output_file);
+    Ok(())
+}
+
+pub fn export_meta(args: ExportMetaArgs) -> Result<()> {
+    let json = Manifest::from_png(&from_file(&args.path)?).to_json()?;
+    match args.output_file {
+        Some(output_file) => fs::write(output_file, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+pub fn import_meta(args: ImportMetaArgs) -> Result<()> {
+    let manifest = Manifest::from_json(&fs::read_to_string(&args.manifest)?)?;
+    let mut png = from_file(&args.path)?;
+    for line in manifest.apply(&mut png, args.merge)? {
+        println!("{}", line);
+    }
+    touch_if(&mut png, args.touch)?;
+    to_file(args.output_file.as_ref().unwrap_or(&args.path), png)?;
+    Ok(())
+}
+
+pub fn copy_chunks(args: CopyChunksArgs) -> Result<()> {
+    let types = args
+        .types
+        .iter()
+        .map(|t| ChunkType::from_str(t))
+        .collect::<Result<Vec<ChunkType>>>()?;
+    let source = from_file(&args.source)?;
+    let mut target = from_file(&args.target)?;
+    let report = manifest::copy_chunks(&source, &mut target, &types, args.force)?;
+    if report.is_empty() {
+        println!("nothing to copy");
+    }
+    for line in report {
+        println!("{}", line);
+    }
+    touch_if(&mut target, args.touch)?;
+    to_file(args.output_file.as_ref().unwrap_or(&args.target), target)?;
+    Ok(())
+}
+
+pub fn diff(args: DiffArgs) -> Result<()> {
+    let (old, new) = (from_file(&args.old)?, from_file(&args.new)?);
+    if args.patch {
+        std::io::stdout().write_all(&Patch::new(&old, &new).as_bytes())?;
+    } else if args.format != Format::Text {
+        println!("{}", output::render(&Diff::new(&old, &new), args.format)?);
+    } else {
+        print!("{}", Diff::new(&old, &new));
+    }
+    Ok(())
+}
+
+pub fn apply(args: ApplyArgs) -> Result<()> {
+    let patch = Patch::try_from(&fs::read(&args.patch)?[..])?;
+    let mut png = patch.apply(&from_file(&args.path)?)?;
+    touch_if(&mut png, args.touch)?;
+    to_file(args.output_file.as_ref().unwrap_or(&args.path), png)?;
+    println!("applied {} operations", patch.ops().len());
+    Ok(())
+}
+
+pub fn repair(args: RepairArgs) -> Result<()> {
+    let (mut png, report) = crate::repair::repair(&fs::read(&args.input_file)?, args.fix_crc)?;
+    if report.is_empty() {
+        println!("no damage found");
+    }
+    for line in report {
+        println!("{}", line);
+    }
+    touch_if(&mut png, args.touch)?;
+    to_file(&args.output_file, png)?;
+    Ok(())
+}
+
+pub fn dump(args: DumpArgs) -> Result<()> {
+    let png = from_file(&args.path)?;
+    let index = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| chunk.chunk_type().bytes() == args.chunk_type.as_bytes())
+        .map(|(index, _)| index)
+        .nth(args.index)
+        .ok_or_else(|| format!("no {} chunk number {}", args.chunk_type, args.index))?;
+    let chunk = &png.chunks()[index];
+    let offset = png.chunk_offsets()[index];
+    let (bytes, start) = if args.framed {
+        (chunk.as_bytes(), offset)
+    } else {
+        (chunk.data().to_vec(), offset + 8)
+    };
+    match (&args.raw, args.hex) {
+        (Some(file), false) => {
+            fs::write(file, &bytes)?;
+            println!(
+                "wrote {} bytes of {} to {}",
+                bytes.len(),
+                chunk.chunk_type(),
+                file
+            );
+        }
+        _ => print!("{}", hex_dump(&bytes, start)),
+    }
+    Ok(())
+}
+
+pub fn inject(args: InjectArgs) -> Result<()> {
+    let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+    let mut png = from_file(&args.path)?;
+    if chunk_type.is_critical() && !args.force {
+        return Err(format!(
+            "{} is a critical chunk type; use --force to add it before IEND anyway",
+            chunk_type
+        )
+        .into());
+    }
+    let data = fs::read(&args.file)?;
+    if data.len() > Chunk::MAX_LENGTH as usize {
+        return Err(format!("{} is too large for a chunk", args.file).into());
+    }
+    let length = data.len();
+    png.insert_before_end(Chunk::new(chunk_type.clone(), data));
+    touch_if(&mut png, args.touch)?;
+    to_file(args.output_file.as_ref().unwrap_or(&args.path), png)?;
+    println!(
+        "added {} bytes from {} as {}",
+        length, args.file, chunk_type
+    );
+    Ok(())
+}
+
+/// Sets the tIME chunk to the current time when `--touch` was given.
+fn touch_if(png: &mut Png, touch: bool) -> Result<()> {
+    if touch {
+        timestamp::touch(png, LastModified::now()?)?;
+    }
     Ok(())
 }
 
@@ -0,0 +1,534 @@
+use crate::image::ImageData;
+use crate::png::{Chunk, ChunkType, Png};
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// Payloads up to this size get a byte-level diff.
+const SMALL_PAYLOAD: usize = 256;
+/// Most differing byte runs shown per chunk.
+const MAX_RUNS: usize = 8;
+/// Largest LCS table `align` builds; beyond it chunks are paired greedily.
+const MAX_CELLS: usize = 1 << 22;
+
+/// A stretch of bytes that differs between two versions of a chunk, at the same offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteRun {
+    offset: usize,
+    old: Vec<u8>,
+    new: Vec<u8>,
+}
+impl Display for ByteRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = |bytes: &[u8]| -> String {
+            if bytes.is_empty() {
+                return "(none)".to_string();
+            }
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            hex.join(" ")
+        };
+        let len = self.old.len().max(self.new.len());
+        write!(
+            f,
+            "bytes {}..{}: {} -> {}",
+            self.offset,
+            self.offset + len,
+            hex(&self.old),
+            hex(&self.new)
+        )
+    }
+}
+/// The offset and the old and new bytes in hex.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ByteRun {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let hex = |bytes: &[u8]| -> String { bytes.iter().map(|b| format!("{:02x}", b)).collect() };
+        let mut state = serializer.serialize_struct("ByteRun", 3)?;
+        state.serialize_field("offset", &self.offset)?;
+        state.serialize_field("old", &hex(&self.old))?;
+        state.serialize_field("new", &hex(&self.new))?;
+        state.end()
+    }
+}
+
+/// Runs of differing bytes, comparing position by position; a length change is one last run.
+fn byte_runs(old: &[u8], new: &[u8]) -> Vec<ByteRun> {
+    let mut runs: Vec<ByteRun> = vec![];
+    let common = old.len().min(new.len());
+    for i in 0..common {
+        if old[i] == new[i] {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.offset + run.old.len() == i => {
+                run.old.push(old[i]);
+                run.new.push(new[i]);
+            }
+            _ => runs.push(ByteRun {
+                offset: i,
+                old: vec![old[i]],
+                new: vec![new[i]],
+            }),
+        }
+    }
+    if old.len() != new.len() {
+        runs.push(ByteRun {
+            offset: common,
+            old: old[common..].to_vec(),
+            new: new[common..].to_vec(),
+        });
+    }
+    runs
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "change", rename_all = "lowercase")
+)]
+pub enum ChunkChange {
+    Removed {
+        index: usize,
+        chunk_type: ChunkType,
+        length: u32,
+    },
+    Added {
+        index: usize,
+        chunk_type: ChunkType,
+        length: u32,
+    },
+    /// The same bytes at a different place in the chunk order.
+    Moved {
+        chunk_type: ChunkType,
+        from: usize,
+        to: usize,
+    },
+    /// A chunk of the same type with different data. `runs` is empty for large payloads.
+    Changed {
+        chunk_type: ChunkType,
+        from: usize,
+        to: usize,
+        old_length: u32,
+        new_length: u32,
+        runs: Vec<ByteRun>,
+    },
+}
+impl Display for ChunkChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkChange::Removed {
+                index,
+                chunk_type,
+                length,
+            } => write!(f, "removed {} {} ({} bytes)", index, chunk_type, length),
+            ChunkChange::Added {
+                index,
+                chunk_type,
+                length,
+            } => write!(f, "added {} {} ({} bytes)", index, chunk_type, length),
+            ChunkChange::Moved {
+                chunk_type,
+                from,
+                to,
+            } => write!(f, "moved {} from {} to {}", chunk_type, from, to),
+            ChunkChange::Changed {
+                chunk_type,
+                from,
+                to,
+                old_length,
+                new_length,
+                runs,
+            } => {
+                write!(f, "changed {} {}", from, chunk_type)?;
+                if from != to {
+                    write!(f, " (now {})", to)?;
+                }
+                write!(f, ": {} -> {} bytes", old_length, new_length)?;
+                for run in runs.iter().take(MAX_RUNS) {
+                    write!(f, "\n    {}", run)?;
+                }
+                if runs.len() > MAX_RUNS {
+                    write!(f, "\n    … {} more runs", runs.len() - MAX_RUNS)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Whether two files decode to the same scanlines, whatever their IDAT bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "result", content = "reason", rename_all = "lowercase")
+)]
+pub enum PixelComparison {
+    Identical,
+    Different(String),
+    Undecodable(String),
+}
+impl PixelComparison {
+    fn compare(a: &Png, b: &Png) -> PixelComparison {
+        let (a_data, b_data) = match (ImageData::decode(a), ImageData::decode(b)) {
+            (Ok(a_data), Ok(b_data)) => (a_data, b_data),
+            (Err(e), _) | (_, Err(e)) => return PixelComparison::Undecodable(e.to_string()),
+        };
+        if a_data.header() != b_data.header() {
+            return PixelComparison::Different("the headers differ".to_string());
+        }
+        match (a.palette(), b.palette()) {
+            (Err(e), _) | (_, Err(e)) => return PixelComparison::Undecodable(e.to_string()),
+            (Ok(a_palette), Ok(b_palette)) if a_palette != b_palette => {
+                return PixelComparison::Different("the palettes differ".to_string());
+            }
+            _ => {}
+        }
+        match (a.transparency(), b.transparency()) {
+            (Err(e), _) | (_, Err(e)) => return PixelComparison::Undecodable(e.to_string()),
+            (Ok(a_alpha), Ok(b_alpha)) if a_alpha != b_alpha => {
+                return PixelComparison::Different("the transparency differs".to_string());
+            }
+            _ => {}
+        }
+        let rows_differ = a_data
+            .passes()
+            .iter()
+            .zip(b_data.passes())
+            .any(|(a_pass, b_pass)| a_pass.rows() != b_pass.rows());
+        if rows_differ {
+            PixelComparison::Different("the scanlines differ".to_string())
+        } else {
+            PixelComparison::Identical
+        }
+    }
+}
+
+/// The chunk-level differences between two files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Diff {
+    changes: Vec<ChunkChange>,
+    image_data_differs: bool,
+    trailing: Option<(usize, usize)>,
+    pixels: PixelComparison,
+}
+impl Diff {
+    pub fn new(a: &Png, b: &Png) -> Diff {
+        let (a_chunks, b_chunks) = (a.chunks(), b.chunks());
+        let mut a_matched = vec![false; a_chunks.len()];
+        let mut b_matched = vec![false; b_chunks.len()];
+        for (i, j) in align(a_chunks, b_chunks) {
+            a_matched[i] = true;
+            b_matched[j] = true;
+        }
+
+        let mut changes = vec![];
+        // Identical chunks outside the common subsequence were moved, then same-typed ones changed.
+        let pairings: [fn(&Chunk, &Chunk) -> bool; 2] = [
+            |a, b| key(a) == key(b) && a.data() == b.data(),
+            |a, b| a.chunk_type() == b.chunk_type(),
+        ];
+        for (pass, same) in pairings.iter().enumerate() {
+            for i in 0..a_chunks.len() {
+                if a_matched[i] {
+                    continue;
+                }
+                let found = (0..b_chunks.len())
+                    .find(|&j| !b_matched[j] && same(&a_chunks[i], &b_chunks[j]));
+                if let Some(j) = found {
+                    a_matched[i] = true;
+                    b_matched[j] = true;
+                    let (old, new) = (&a_chunks[i], &b_chunks[j]);
+                    changes.push(if pass == 0 {
+                        ChunkChange::Moved {
+                            chunk_type: old.chunk_type().clone(),
+                            from: i,
+                            to: j,
+                        }
+                    } else {
+                        let small =
+                            old.data().len() <= SMALL_PAYLOAD && new.data().len() <= SMALL_PAYLOAD;
+                        ChunkChange::Changed {
+                            chunk_type: old.chunk_type().clone(),
+                            from: i,
+                            to: j,
+                            old_length: old.length(),
+                            new_length: new.length(),
+                            runs: if small {
+                                byte_runs(old.data(), new.data())
+                            } else {
+                                vec![]
+                            },
+                        }
+                    });
+                }
+            }
+        }
+        for (index, chunk) in a_chunks.iter().enumerate() {
+            if !a_matched[index] {
+                changes.push(ChunkChange::Removed {
+                    index,
+                    chunk_type: chunk.chunk_type().clone(),
+                    length: chunk.length(),
+                });
+            }
+        }
+        for (index, chunk) in b_chunks.iter().enumerate() {
+            if !b_matched[index] {
+                changes.push(ChunkChange::Added {
+                    index,
+                    chunk_type: chunk.chunk_type().clone(),
+                    length: chunk.length(),
+                });
+            }
+        }
+
+        let trailing = (a.trailing_data() != b.trailing_data())
+            .then(|| (a.trailing_data().len(), b.trailing_data().len()));
+        Diff {
+            changes,
+            image_data_differs: a.image_data() != b.image_data(),
+            trailing,
+            pixels: PixelComparison::compare(a, b),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.trailing.is_none()
+    }
+}
+impl Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            writeln!(f, "no chunk differences")?;
+        }
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        if let Some((old, new)) = self.trailing {
+            writeln!(f, "trailing data: {} -> {} bytes", old, new)?;
+        }
+        let image_data = if self.image_data_differs {
+            "the image data differs"
+        } else {
+            "the image data is identical"
+        };
+        match &self.pixels {
+            PixelComparison::Identical => writeln!(f, "pixels: identical ({})", image_data),
+            PixelComparison::Different(reason) => writeln!(f, "pixels: different, {}", reason),
+            PixelComparison::Undecodable(e) => writeln!(f, "pixels: not compared: {}", e),
+        }
+    }
+}
+
+/// What `align` compares chunks by: their type, length and CRC.
+type ChunkKey = ([u8; 4], u32, u32);
+
+fn key(chunk: &Chunk) -> ChunkKey {
+    (chunk.chunk_type().bytes(), chunk.length(), chunk.crc())
+}
+
+/// Index pairs of identical chunks forming a common subsequence of `a` and `b`. Common leading
+/// and trailing chunks are paired first; the rest get a longest common subsequence, or a greedy
+/// pairing when there are too many for the LCS table.
+pub(crate) fn align(a: &[Chunk], b: &[Chunk]) -> Vec<(usize, usize)> {
+    let a_keys: Vec<ChunkKey> = a.iter().map(key).collect();
+    let b_keys: Vec<ChunkKey> = b.iter().map(key).collect();
+    let prefix = a_keys
+        .iter()
+        .zip(&b_keys)
+        .take_while(|(x, y)| x == y)
+        .count();
+    let suffix = a_keys[prefix..]
+        .iter()
+        .rev()
+        .zip(b_keys[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let a_middle = &a_keys[prefix..a.len() - suffix];
+    let b_middle = &b_keys[prefix..b.len() - suffix];
+    let middle = if a_middle.len().saturating_mul(b_middle.len()) <= MAX_CELLS {
+        longest_common(a_middle, b_middle)
+    } else {
+        greedy_common(a_middle, b_middle)
+    };
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    pairs.extend(middle.into_iter().map(|(i, j)| (prefix + i, prefix + j)));
+    pairs.extend((0..suffix).map(|k| (a.len() - suffix + k, b.len() - suffix + k)));
+    pairs
+}
+
+fn longest_common(a: &[ChunkKey], b: &[ChunkKey]) -> Vec<(usize, usize)> {
+    // lengths[i * width + j] is the LCS length of a[i..] and b[j..].
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = vec![];
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Pairs each chunk of `a` with the next identical chunk of `b`, in linearithmic time.
+fn greedy_common(a: &[ChunkKey], b: &[ChunkKey]) -> Vec<(usize, usize)> {
+    let mut positions: HashMap<&ChunkKey, Vec<usize>> = HashMap::new();
+    for (j, key) in b.iter().enumerate() {
+        positions.entry(key).or_default().push(j);
+    }
+    let mut pairs = vec![];
+    let mut next = 0;
+    for (i, key) in a.iter().enumerate() {
+        let Some(candidates) = positions.get(key) else {
+            continue;
+        };
+        if let Some(&j) = candidates.get(candidates.partition_point(|&j| j < next)) {
+            pairs.push((i, j));
+            next = j + 1;
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{chunk, PNG_FILE};
+
+    #[test]
+    fn test_identical_files() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let diff = Diff::new(&png, &Png::try_from(&PNG_FILE[..]).unwrap());
+        assert!(diff.is_empty());
+        assert_eq!(diff.pixels, PixelComparison::Identical);
+    }
+
+    #[test]
+    fn test_added_removed_moved_and_changed() {
+        let a = Png::from_chunks(vec![
+            chunk("IHDR", &[1]),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("sRGB", &[0]),
+            chunk("tEXt", b"a\0one"),
+            chunk("ruSt", b"gone"),
+            chunk("IEND", &[]),
+        ]);
+        let b = Png::from_chunks(vec![
+            chunk("IHDR", &[1]),
+            chunk("sRGB", &[0]),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("tEXt", b"a\0two!"),
+            chunk("tIME", &[7, 232, 1, 1, 0, 0, 0]),
+            chunk("IEND", &[]),
+        ]);
+        let diff = Diff::new(&a, &b);
+        let text: Vec<String> = diff.changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            text,
+            [
+                "moved gAMA from 1 to 2",
+                "changed 3 tEXt: 5 -> 6 bytes\n    bytes 2..5: 6f 6e 65 -> 74 77 6f\n    bytes 5..6: (none) -> 21",
+                "removed 4 ruSt (4 bytes)",
+                "added 4 tIME (7 bytes)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_palette_is_undecodable() {
+        let indexed = |palettes: &[&[u8]]| {
+            let mut chunks = vec![chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0])];
+            chunks.extend(palettes.iter().map(|data| chunk("PLTE", data)));
+            chunks.push(chunk("IDAT", &crate::zlib::compress(&[0, 0]).unwrap()));
+            Png::from_chunks(chunks)
+        };
+        let diff = Diff::new(&indexed(&[&[1, 2, 3], &[4, 5, 6]]), &indexed(&[&[1, 2, 3]]));
+        assert_eq!(
+            diff.pixels,
+            PixelComparison::Undecodable("duplicate PLTE chunks".to_string())
+        );
+    }
+
+    #[test]
+    fn test_transparency_differs() {
+        let indexed = |alphas: &[u8]| {
+            Png::from_chunks(vec![
+                chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]),
+                chunk("PLTE", &[1, 2, 3]),
+                chunk("tRNS", alphas),
+                chunk("IDAT", &crate::zlib::compress(&[0, 0]).unwrap()),
+            ])
+        };
+        let diff = Diff::new(&indexed(&[0]), &indexed(&[128]));
+        assert_eq!(
+            diff.pixels,
+            PixelComparison::Different("the transparency differs".to_string())
+        );
+        assert_eq!(
+            Diff::new(&indexed(&[0]), &indexed(&[0])).pixels,
+            PixelComparison::Identical
+        );
+    }
+
+    #[test]
+    fn test_align_many_chunks() {
+        let idats: Vec<Chunk> = (0..12_000u32)
+            .map(|i| chunk("IDAT", &i.to_be_bytes()))
+            .collect();
+        let mut edited = idats.clone();
+        edited[6_000] = chunk("IDAT", b"edit");
+        let pairs = align(&idats, &edited);
+        assert_eq!(pairs.len(), 11_999);
+        assert!(!pairs.contains(&(6_000, 6_000)));
+
+        let reversed: Vec<Chunk> = idats[..3_000].iter().rev().cloned().collect();
+        let pairs = align(&idats[..3_000], &reversed);
+        assert!(!pairs.is_empty());
+        assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        assert!(pairs
+            .iter()
+            .all(|&(i, j)| idats[i].data() == reversed[j].data()));
+    }
+
+    #[test]
+    fn test_pixels_identical_when_idat_split_differs() {
+        let a = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut b = Png::try_from(&PNG_FILE[..]).unwrap();
+        let data = b.image_data();
+        let (first, second) = data.split_at(data.len() / 2);
+        let idat = b
+            .chunks()
+            .iter()
+            .position(|c| &c.chunk_type().bytes() == b"IDAT")
+            .unwrap();
+        b.remove_chunk_at(idat).unwrap();
+        b.insert_chunk(idat, chunk("IDAT", second));
+        b.insert_chunk(idat, chunk("IDAT", first));
+        let diff = Diff::new(&a, &b);
+        assert_eq!(diff.pixels, PixelComparison::Identical);
+        assert!(!diff.image_data_differs);
+        assert!(diff.to_string().contains("added"));
+    }
+}
@@ -0,0 +1,806 @@
+use crate::chunk::{check_chunk, invalid};
+use crate::png::{Chunk, ChunkType};
+use crate::{Error, Result};
+use std::fmt::Display;
+use std::str::FromStr;
+
+const EXIF_POINTER: u16 = 0x8769;
+const GPS_POINTER: u16 = 0x8825;
+const INTEROP_POINTER: u16 = 0xa005;
+
+/// Tags naming the camera's owner or identifying the physical camera and lens.
+const SERIAL_TAGS: [u16; 3] = [0xa430, 0xa431, 0xa435];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteOrder {
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfdKind {
+    Primary,
+    Thumbnail,
+    Exif,
+    Gps,
+    Interop,
+}
+impl Display for IfdKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IfdKind::Primary => "IFD0",
+            IfdKind::Thumbnail => "IFD1",
+            IfdKind::Exif => "Exif",
+            IfdKind::Gps => "GPS",
+            IfdKind::Interop => "Interop",
+        };
+        f.pad(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExifValue {
+    Bytes(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SignedBytes(Vec<i8>),
+    Undefined(Vec<u8>),
+    SignedShort(Vec<i16>),
+    SignedLong(Vec<i32>),
+    SignedRational(Vec<(i32, i32)>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+}
+impl ExifValue {
+    fn first_u32(&self) -> Option<u32> {
+        match self {
+            ExifValue::Short(values) => values.first().map(|&v| v as u32),
+            ExifValue::Long(values) => values.first().copied(),
+            _ => None,
+        }
+    }
+    fn rationals(&self) -> Option<Vec<f64>> {
+        match self {
+            ExifValue::Rational(values) => {
+                Some(values.iter().map(|&(n, d)| n as f64 / d as f64).collect())
+            }
+            _ => None,
+        }
+    }
+}
+impl Display for ExifValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn list<T: Display>(f: &mut std::fmt::Formatter<'_>, values: &[T]) -> std::fmt::Result {
+            let shown: Vec<String> = values.iter().take(16).map(|v| v.to_string()).collect();
+            write!(f, "{}", shown.join(", "))?;
+            if values.len() > 16 {
+                write!(f, ", … ({} values)", values.len())?;
+            }
+            Ok(())
+        }
+        match self {
+            ExifValue::Ascii(text) => write!(f, "{}", text),
+            ExifValue::Bytes(values) | ExifValue::Undefined(values) => {
+                if values.len() > 16 {
+                    write!(f, "{} bytes", values.len())
+                } else {
+                    list(f, values)
+                }
+            }
+            ExifValue::Short(values) => list(f, values),
+            ExifValue::Long(values) => list(f, values),
+            ExifValue::SignedBytes(values) => list(f, values),
+            ExifValue::SignedShort(values) => list(f, values),
+            ExifValue::SignedLong(values) => list(f, values),
+            ExifValue::Float(values) => list(f, values),
+            ExifValue::Double(values) => list(f, values),
+            ExifValue::Rational(values) => {
+                let shown: Vec<String> =
+                    values.iter().map(|(n, d)| format!("{}/{}", n, d)).collect();
+                list(f, &shown)
+            }
+            ExifValue::SignedRational(values) => {
+                let shown: Vec<String> =
+                    values.iter().map(|(n, d)| format!("{}/{}", n, d)).collect();
+                list(f, &shown)
+            }
+        }
+    }
+}
+
+/// One IFD entry, with its slot in the IFD and the absolute offset of its value bytes in the
+/// TIFF stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExifEntry {
+    tag: u16,
+    slot: usize,
+    value: ExifValue,
+    value_offset: usize,
+    value_len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ifd {
+    kind: IfdKind,
+    offset: usize,
+    /// Entries as stored, including those of unknown types that `entries` leaves out.
+    count: usize,
+    entries: Vec<ExifEntry>,
+}
+
+/// Decoded eXIf chunk: a TIFF stream whose IFDs hold the EXIF tags.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exif {
+    data: Vec<u8>,
+    order: ByteOrder,
+    ifds: Vec<Ifd>,
+}
+impl Exif {
+    pub fn parse(data: &[u8]) -> Result<Exif> {
+        let order = match data.get(..4) {
+            Some(b"II\x2a\x00") => ByteOrder::Little,
+            Some(b"MM\x00\x2a") => ByteOrder::Big,
+            _ => return Err(invalid("invalid eXIf: missing TIFF header".to_string())),
+        };
+        let mut exif = Exif {
+            data: data.to_vec(),
+            order,
+            ifds: vec![],
+        };
+        let first = exif.u32_at(4)? as usize;
+        let mut pending = vec![(IfdKind::Primary, first)];
+        while let Some((kind, offset)) = pending.pop() {
+            if exif.ifds.iter().any(|ifd| ifd.offset == offset) || exif.ifds.len() > 16 {
+                return Err(invalid("invalid eXIf: IFDs form a loop".to_string()));
+            }
+            let (ifd, next) = exif.read_ifd(kind, offset)?;
+            for entry in &ifd.entries {
+                let child = match entry.tag {
+                    EXIF_POINTER if kind == IfdKind::Primary => IfdKind::Exif,
+                    GPS_POINTER if kind == IfdKind::Primary => IfdKind::Gps,
+                    INTEROP_POINTER if kind == IfdKind::Exif => IfdKind::Interop,
+                    _ => continue,
+                };
+                if let Some(pointer) = entry.value.first_u32() {
+                    pending.push((child, pointer as usize));
+                }
+            }
+            if kind == IfdKind::Primary && next != 0 {
+                pending.push((IfdKind::Thumbnail, next));
+            }
+            exif.ifds.push(ifd);
+        }
+        exif.ifds.sort_by_key(|ifd| ifd.offset);
+        Ok(exif)
+    }
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        Ok(Chunk::new(ChunkType::from_str("eXIf")?, self.data.clone()))
+    }
+
+    pub fn get(&self, kind: IfdKind, tag: u16) -> Option<&ExifValue> {
+        self.ifds
+            .iter()
+            .filter(|ifd| ifd.kind == kind)
+            .flat_map(|ifd| &ifd.entries)
+            .find(|entry| entry.tag == tag)
+            .map(|entry| &entry.value)
+    }
+    pub fn orientation(&self) -> Option<u16> {
+        self.get(IfdKind::Primary, 0x0112)
+            .and_then(|value| value.first_u32())
+            .map(|value| value as u16)
+    }
+    /// Make and model, e.g. "Canon EOS 5D".
+    pub fn camera(&self) -> Option<String> {
+        let ascii = |tag| match self.get(IfdKind::Primary, tag) {
+            Some(ExifValue::Ascii(text)) if !text.is_empty() => Some(text.clone()),
+            _ => None,
+        };
+        match (ascii(0x010f), ascii(0x0110)) {
+            (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.or(model),
+        }
+    }
+    /// When the picture was taken, falling back to when the file was last changed.
+    pub fn timestamp(&self) -> Option<&str> {
+        [(IfdKind::Exif, 0x9003), (IfdKind::Primary, 0x0132)]
+            .iter()
+            .find_map(|&(kind, tag)| match self.get(kind, tag) {
+                Some(ExifValue::Ascii(text)) => Some(text.as_str()),
+                _ => None,
+            })
+    }
+    /// Latitude and longitude in signed decimal degrees.
+    pub fn gps_position(&self) -> Option<(f64, f64)> {
+        let coordinate = |ref_tag, tag, negative: &str| {
+            let parts = self.get(IfdKind::Gps, tag)?.rationals()?;
+            let degrees = parts.first()?
+                + parts.get(1).unwrap_or(&0.0) / 60.0
+                + parts.get(2).unwrap_or(&0.0) / 3600.0;
+            match self.get(IfdKind::Gps, ref_tag) {
+                Some(ExifValue::Ascii(r)) if r == negative => Some(-degrees),
+                _ => Some(degrees),
+            }
+        };
+        Some((
+            coordinate(0x0001, 0x0002, "S")?,
+            coordinate(0x0003, 0x0004, "W")?,
+        ))
+    }
+
+    /// Removes the GPS IFD and the pointer to it.
+    pub fn remove_gps(&mut self) -> Result<bool> {
+        let removed = self.remove_tag(IfdKind::Primary, GPS_POINTER)?;
+        if let Some(index) = self.ifds.iter().position(|ifd| ifd.kind == IfdKind::Gps) {
+            let ifd = self.ifds.remove(index);
+            for entry in &ifd.entries {
+                self.clear(entry.value_offset, entry.value_len);
+            }
+            self.clear(ifd.offset, 2 + 12 * ifd.count + 4);
+        }
+        Ok(removed)
+    }
+    /// Removes the camera owner name and body and lens serial numbers.
+    pub fn remove_serials(&mut self) -> Result<usize> {
+        let mut removed = 0;
+        for tag in SERIAL_TAGS {
+            removed += self.remove_tag(IfdKind::Exif, tag)? as usize;
+        }
+        Ok(removed)
+    }
+    /// Drops an entry in place: later entries move up and freed bytes are zeroed, so every
+    /// other offset in the stream (including inside maker notes) stays valid.
+    pub fn remove_tag(&mut self, kind: IfdKind, tag: u16) -> Result<bool> {
+        let Some(ifd_index) = self.ifds.iter().position(|ifd| ifd.kind == kind) else {
+            return Ok(false);
+        };
+        let Some(position) = self.ifds[ifd_index]
+            .entries
+            .iter()
+            .position(|e| e.tag == tag)
+        else {
+            return Ok(false);
+        };
+        let entry = self.ifds[ifd_index].entries.remove(position);
+        let (offset, count) = (self.ifds[ifd_index].offset, self.ifds[ifd_index].count);
+        let start = offset + 2 + 12 * entry.slot;
+        let end = offset + 2 + 12 * count + 4; // through the next-IFD pointer
+
+        if entry.value_len > 4 {
+            self.clear(entry.value_offset, entry.value_len);
+        }
+        self.data.copy_within(start + 12..end, start);
+        self.clear(end - 12, 12);
+        self.write_u16(offset, (count - 1) as u16);
+        self.ifds[ifd_index].count -= 1;
+        for later in &mut self.ifds[ifd_index].entries[position..] {
+            later.slot -= 1;
+            if later.value_len <= 4 {
+                later.value_offset -= 12;
+            }
+        }
+        Ok(true)
+    }
+
+    fn clear(&mut self, offset: usize, len: usize) {
+        self.data[offset..offset + len].fill(0);
+    }
+    fn bytes_at(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        self.data
+            .get(
+                offset
+                    ..offset
+                        .checked_add(len)
+                        .ok_or_else(|| invalid("invalid eXIf: offset overflow".to_string()))?,
+            )
+            .ok_or_else(|| invalid("invalid eXIf: offset out of range".to_string()))
+    }
+    fn u16_at(&self, offset: usize) -> Result<u16> {
+        let bytes: [u8; 2] = self.bytes_at(offset, 2)?.try_into()?;
+        Ok(match self.order {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        })
+    }
+    fn u32_at(&self, offset: usize) -> Result<u32> {
+        let bytes: [u8; 4] = self.bytes_at(offset, 4)?.try_into()?;
+        Ok(match self.order {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        })
+    }
+    fn write_u16(&mut self, offset: usize, value: u16) {
+        let bytes = match self.order {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        };
+        self.data[offset..offset + 2].copy_from_slice(&bytes);
+    }
+
+    fn read_ifd(&self, kind: IfdKind, offset: usize) -> Result<(Ifd, usize)> {
+        let count = self.u16_at(offset)? as usize;
+        let mut entries = vec![];
+        for i in 0..count {
+            let at = offset + 2 + 12 * i;
+            let tag = self.u16_at(at)?;
+            let field_type = self.u16_at(at + 2)?;
+            let values = self.u32_at(at + 4)? as usize;
+            let size = match field_type {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 => 4,
+                5 | 10 | 12 => 8,
+                _ => continue, // unknown types are skipped, as TIFF readers must
+            };
+            let value_len = size * values;
+            let value_offset = if value_len <= 4 {
+                at + 8
+            } else {
+                self.u32_at(at + 8)? as usize
+            };
+            let value = self.read_value(field_type, values, value_offset)?;
+            entries.push(ExifEntry {
+                tag,
+                slot: i,
+                value,
+                value_offset,
+                value_len,
+            });
+        }
+        let next = self.u32_at(offset + 2 + 12 * count)? as usize;
+        Ok((
+            Ifd {
+                kind,
+                offset,
+                count,
+                entries,
+            },
+            next,
+        ))
+    }
+
+    fn read_value(&self, field_type: u16, count: usize, offset: usize) -> Result<ExifValue> {
+        let read = |size: usize| -> Result<Vec<usize>> {
+            self.bytes_at(offset, size * count)?;
+            Ok((0..count).map(|i| offset + size * i).collect())
+        };
+        Ok(match field_type {
+            1 => ExifValue::Bytes(self.bytes_at(offset, count)?.to_vec()),
+            2 => {
+                let bytes = self.bytes_at(offset, count)?;
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                ExifValue::Ascii(
+                    String::from_utf8_lossy(&bytes[..end])
+                        .trim_end()
+                        .to_string(),
+                )
+            }
+            3 => ExifValue::Short(
+                read(2)?
+                    .into_iter()
+                    .map(|at| self.u16_at(at))
+                    .collect::<Result<_>>()?,
+            ),
+            4 => ExifValue::Long(
+                read(4)?
+                    .into_iter()
+                    .map(|at| self.u32_at(at))
+                    .collect::<Result<_>>()?,
+            ),
+            5 => ExifValue::Rational(
+                read(8)?
+                    .into_iter()
+                    .map(|at| Ok((self.u32_at(at)?, self.u32_at(at + 4)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            6 => ExifValue::SignedBytes(
+                self.bytes_at(offset, count)?
+                    .iter()
+                    .map(|&b| b as i8)
+                    .collect(),
+            ),
+            7 => ExifValue::Undefined(self.bytes_at(offset, count)?.to_vec()),
+            8 => ExifValue::SignedShort(
+                read(2)?
+                    .into_iter()
+                    .map(|at| Ok(self.u16_at(at)? as i16))
+                    .collect::<Result<_>>()?,
+            ),
+            9 => ExifValue::SignedLong(
+                read(4)?
+                    .into_iter()
+                    .map(|at| Ok(self.u32_at(at)? as i32))
+                    .collect::<Result<_>>()?,
+            ),
+            10 => ExifValue::SignedRational(
+                read(8)?
+                    .into_iter()
+                    .map(|at| Ok((self.u32_at(at)? as i32, self.u32_at(at + 4)? as i32)))
+                    .collect::<Result<_>>()?,
+            ),
+            11 => ExifValue::Float(
+                read(4)?
+                    .into_iter()
+                    .map(|at| Ok(f32::from_bits(self.u32_at(at)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            _ => ExifValue::Double(
+                read(8)?
+                    .into_iter()
+                    .map(|at| {
+                        let high = self.u32_at(at)? as u64;
+                        let low = self.u32_at(at + 4)? as u64;
+                        Ok(f64::from_bits(match self.order {
+                            ByteOrder::Big => (high << 32) | low,
+                            ByteOrder::Little => (low << 32) | high,
+                        }))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
+impl TryFrom<&Chunk> for Exif {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "eXIf", None)?;
+        Exif::parse(chunk.data())
+    }
+}
+impl Display for Exif {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for ifd in &self.ifds {
+            for entry in &ifd.entries {
+                let name = tag_name(ifd.kind, entry.tag)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("0x{:04x}", entry.tag));
+                write!(f, "{:<8}{:<28}", ifd.kind, name)?;
+                match (ifd.kind, entry.tag) {
+                    (IfdKind::Primary | IfdKind::Thumbnail, 0x0112) => {
+                        let meaning = entry.value.first_u32().map(orientation_name);
+                        writeln!(f, "{} ({})", entry.value, meaning.unwrap_or("unknown"))?
+                    }
+                    _ => writeln!(f, "{}", entry.value)?,
+                }
+            }
+        }
+        if let Some((latitude, longitude)) = self.gps_position() {
+            writeln!(f, "GPS position: {:.6}, {:.6}", latitude, longitude)?;
+        }
+        Ok(())
+    }
+}
+/// The decoded summary, then every tag with its IFD, name and value as `exif show` prints it.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Exif {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::{SerializeMap, SerializeStruct};
+        struct Tags<'a>(&'a [Ifd]);
+        impl serde::Serialize for Tags<'_> {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                use serde::ser::SerializeSeq;
+                let mut seq = serializer.serialize_seq(None)?;
+                for ifd in self.0 {
+                    for entry in &ifd.entries {
+                        let name = tag_name(ifd.kind, entry.tag)
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("0x{:04x}", entry.tag));
+                        seq.serialize_element(&Tag(ifd.kind, entry.tag, name, &entry.value))?;
+                    }
+                }
+                seq.end()
+            }
+        }
+        struct Tag<'a>(IfdKind, u16, String, &'a ExifValue);
+        impl serde::Serialize for Tag<'_> {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                let Tag(kind, tag, name, value) = self;
+                let mut map = serializer.serialize_map(Some(4))?;
+                map.serialize_entry("ifd", &kind.to_string())?;
+                map.serialize_entry("tag", tag)?;
+                map.serialize_entry("name", name)?;
+                map.serialize_entry("value", &value.to_string())?;
+                map.end()
+            }
+        }
+        let mut state = serializer.serialize_struct("Exif", 5)?;
+        state.serialize_field("camera", &self.camera())?;
+        state.serialize_field("taken", &self.timestamp())?;
+        state.serialize_field(
+            "orientation",
+            &self.orientation().map(|o| o as u32).map(orientation_name),
+        )?;
+        state.serialize_field("gps", &self.gps_position())?;
+        state.serialize_field("tags", &Tags(&self.ifds))?;
+        state.end()
+    }
+}
+
+fn orientation_name(value: u32) -> &'static str {
+    match value {
+        1 => "top-left",
+        2 => "top-right, mirrored",
+        3 => "bottom-right, rotated 180°",
+        4 => "bottom-left, mirrored",
+        5 => "left-top, mirrored",
+        6 => "right-top, rotated 90° CW",
+        7 => "right-bottom, mirrored",
+        8 => "left-bottom, rotated 90° CCW",
+        _ => "invalid",
+    }
+}
+
+fn tag_name(kind: IfdKind, tag: u16) -> Option<&'static str> {
+    let name = match (kind, tag) {
+        (IfdKind::Gps, 0x0000) => "GPSVersionID",
+        (IfdKind::Gps, 0x0001) => "GPSLatitudeRef",
+        (IfdKind::Gps, 0x0002) => "GPSLatitude",
+        (IfdKind::Gps, 0x0003) => "GPSLongitudeRef",
+        (IfdKind::Gps, 0x0004) => "GPSLongitude",
+        (IfdKind::Gps, 0x0005) => "GPSAltitudeRef",
+        (IfdKind::Gps, 0x0006) => "GPSAltitude",
+        (IfdKind::Gps, 0x0007) => "GPSTimeStamp",
+        (IfdKind::Gps, 0x0010) => "GPSImgDirectionRef",
+        (IfdKind::Gps, 0x0011) => "GPSImgDirection",
+        (IfdKind::Gps, 0x001d) => "GPSDateStamp",
+        (IfdKind::Gps, _) => return None,
+        (IfdKind::Interop, 0x0001) => "InteropIndex",
+        (IfdKind::Interop, _) => return None,
+        (_, 0x010e) => "ImageDescription",
+        (_, 0x010f) => "Make",
+        (_, 0x0110) => "Model",
+        (_, 0x0112) => "Orientation",
+        (_, 0x011a) => "XResolution",
+        (_, 0x011b) => "YResolution",
+        (_, 0x0128) => "ResolutionUnit",
+        (_, 0x0131) => "Software",
+        (_, 0x0132) => "DateTime",
+        (_, 0x013b) => "Artist",
+        (_, 0x0201) => "JPEGInterchangeFormat",
+        (_, 0x0202) => "JPEGInterchangeFormatLength",
+        (_, 0x8298) => "Copyright",
+        (_, 0x829a) => "ExposureTime",
+        (_, 0x829d) => "FNumber",
+        (_, 0x8769) => "ExifIFDPointer",
+        (_, 0x8825) => "GPSInfoIFDPointer",
+        (_, 0x8827) => "ISOSpeedRatings",
+        (_, 0x9000) => "ExifVersion",
+        (_, 0x9003) => "DateTimeOriginal",
+        (_, 0x9004) => "DateTimeDigitized",
+        (_, 0x9010) => "OffsetTime",
+        (_, 0x9011) => "OffsetTimeOriginal",
+        (_, 0x920a) => "FocalLength",
+        (_, 0x927c) => "MakerNote",
+        (_, 0x9286) => "UserComment",
+        (_, 0xa001) => "ColorSpace",
+        (_, 0xa002) => "PixelXDimension",
+        (_, 0xa003) => "PixelYDimension",
+        (_, 0xa005) => "InteropIFDPointer",
+        (_, 0xa420) => "ImageUniqueID",
+        (_, 0xa430) => "CameraOwnerName",
+        (_, 0xa431) => "BodySerialNumber",
+        (_, 0xa433) => "LensMake",
+        (_, 0xa434) => "LensModel",
+        (_, 0xa435) => "LensSerialNumber",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal TIFF writer for building fixtures: (tag, type, count, value bytes) per entry.
+    struct TiffBuilder {
+        little: bool,
+    }
+    impl TiffBuilder {
+        fn u16(&self, v: u16) -> [u8; 2] {
+            if self.little {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            }
+        }
+        fn u32(&self, v: u32) -> [u8; 4] {
+            if self.little {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            }
+        }
+        fn ascii(&self, tag: u16, text: &str) -> (u16, u16, u32, Vec<u8>) {
+            let mut bytes = text.as_bytes().to_vec();
+            bytes.push(0);
+            (tag, 2, bytes.len() as u32, bytes)
+        }
+        fn short(&self, tag: u16, v: u16) -> (u16, u16, u32, Vec<u8>) {
+            (tag, 3, 1, self.u16(v).to_vec())
+        }
+        fn long(&self, tag: u16, v: u32) -> (u16, u16, u32, Vec<u8>) {
+            (tag, 4, 1, self.u32(v).to_vec())
+        }
+        fn rationals(&self, tag: u16, values: &[(u32, u32)]) -> (u16, u16, u32, Vec<u8>) {
+            let bytes = values
+                .iter()
+                .flat_map(|&(n, d)| self.u32(n).into_iter().chain(self.u32(d)))
+                .collect();
+            (tag, 5, values.len() as u32, bytes)
+        }
+        /// Appends an IFD at the end of `data`, placing large values right after it.
+        fn ifd(&self, data: &mut Vec<u8>, entries: &[(u16, u16, u32, Vec<u8>)]) -> usize {
+            let offset = data.len();
+            let mut extra_at = offset + 2 + 12 * entries.len() + 4;
+            let mut extra: Vec<u8> = vec![];
+            data.extend(self.u16(entries.len() as u16));
+            for (tag, field_type, count, value) in entries {
+                data.extend(self.u16(*tag));
+                data.extend(self.u16(*field_type));
+                data.extend(self.u32(*count));
+                if value.len() <= 4 {
+                    let mut inline = value.clone();
+                    inline.resize(4, 0);
+                    data.extend(inline);
+                } else {
+                    data.extend(self.u32(extra_at as u32));
+                    extra.extend(value);
+                    extra_at += value.len();
+                }
+            }
+            data.extend(self.u32(0));
+            data.extend(extra);
+            offset
+        }
+    }
+
+    /// IFD0 → Exif IFD and GPS IFD, as a phone would write them.
+    fn phone_exif(little: bool) -> Vec<u8> {
+        let b = TiffBuilder { little };
+        let mut data = if little {
+            b"II\x2a\x00".to_vec()
+        } else {
+            b"MM\x00\x2a".to_vec()
+        };
+        data.extend(b.u32(8));
+
+        // IFD0 is written first with placeholder pointers, patched once the sub-IFDs exist.
+        let ifd0 = b.ifd(
+            &mut data,
+            &[
+                b.ascii(0x010f, "Acme"),
+                b.ascii(0x0110, "Acme Phone 3"),
+                b.short(0x0112, 6),
+                b.long(EXIF_POINTER, 0),
+                b.long(GPS_POINTER, 0),
+            ],
+        );
+        let exif_ifd = b.ifd(
+            &mut data,
+            &[
+                b.ascii(0x9003, "2024:05:01 12:34:56"),
+                b.ascii(0xa431, "SN-123456"),
+            ],
+        );
+        let gps_ifd = b.ifd(
+            &mut data,
+            &[
+                b.ascii(0x0001, "N"),
+                b.rationals(0x0002, &[(51, 1), (30, 1), (2640, 100)]),
+                b.ascii(0x0003, "W"),
+                b.rationals(0x0004, &[(0, 1), (7, 1), (3960, 100)]),
+            ],
+        );
+        let patch = |data: &mut Vec<u8>, index: usize, value: usize| {
+            let at = ifd0 + 2 + 12 * index + 8;
+            data[at..at + 4].copy_from_slice(&b.u32(value as u32));
+        };
+        patch(&mut data, 3, exif_ifd);
+        patch(&mut data, 4, gps_ifd);
+        data
+    }
+
+    #[test]
+    fn test_parse_both_byte_orders() {
+        for little in [true, false] {
+            let exif = Exif::parse(&phone_exif(little)).unwrap();
+            let kinds: Vec<IfdKind> = exif.ifds.iter().map(|ifd| ifd.kind).collect();
+            assert_eq!(kinds, [IfdKind::Primary, IfdKind::Exif, IfdKind::Gps]);
+            assert_eq!(exif.orientation(), Some(6));
+            assert_eq!(exif.camera().as_deref(), Some("Acme Phone 3"));
+            assert_eq!(exif.timestamp(), Some("2024:05:01 12:34:56"));
+            let (latitude, longitude) = exif.gps_position().unwrap();
+            assert!((latitude - 51.5073).abs() < 1e-4);
+            assert!((longitude + 0.1277).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_scrub_gps() {
+        let mut exif = Exif::parse(&phone_exif(true)).unwrap();
+        assert!(exif.remove_gps().unwrap());
+        assert_eq!(exif.gps_position(), None);
+
+        let reparsed = Exif::parse(&exif.data[..]).unwrap();
+        assert_eq!(reparsed.ifds.len(), 2);
+        assert_eq!(reparsed.get(IfdKind::Primary, GPS_POINTER), None);
+        assert_eq!(reparsed.orientation(), Some(6));
+        assert_eq!(reparsed.timestamp(), Some("2024:05:01 12:34:56"));
+        let seconds = [0x50, 0x0a, 0, 0, 100, 0, 0, 0];
+        assert!(!exif.data.windows(8).any(|w| w == seconds));
+    }
+
+    #[test]
+    fn test_scrub_serials() {
+        let mut exif = Exif::parse(&phone_exif(false)).unwrap();
+        assert_eq!(exif.remove_serials().unwrap(), 1);
+        let reparsed = Exif::parse(&exif.data[..]).unwrap();
+        assert_eq!(reparsed.get(IfdKind::Exif, 0xa431), None);
+        assert_eq!(reparsed.timestamp(), Some("2024:05:01 12:34:56"));
+        assert!(!&exif.data[..].windows(9).any(|w| w == b"SN-123456"));
+    }
+
+    #[test]
+    fn test_remove_after_unknown_type() {
+        let b = TiffBuilder { little: true };
+        let mut data = b"II\x2a\x00".to_vec();
+        data.extend(b.u32(8));
+        b.ifd(
+            &mut data,
+            &[
+                (0xc000, 99, 1, vec![1, 2, 3, 4]),
+                b.ascii(0x010f, "Acme"),
+                b.ascii(0x0110, "Acme Phone 3"),
+                b.short(0x0112, 6),
+            ],
+        );
+        let mut exif = Exif::parse(&data).unwrap();
+        assert_eq!(exif.ifds[0].entries.len(), 3);
+        assert!(exif.remove_tag(IfdKind::Primary, 0x010f).unwrap());
+
+        let reparsed = Exif::parse(&exif.data[..]).unwrap();
+        assert_eq!(reparsed.ifds[0].count, 3);
+        assert_eq!(reparsed.get(IfdKind::Primary, 0x010f), None);
+        assert_eq!(reparsed.camera().as_deref(), Some("Acme Phone 3"));
+        assert_eq!(reparsed.orientation(), Some(6));
+        assert_eq!(&exif.data[10..16], &[0x00, 0xc0, 99, 0, 1, 0]);
+        assert!(exif.remove_tag(IfdKind::Primary, 0x0112).unwrap());
+        assert_eq!(Exif::parse(&exif.data[..]).unwrap().orientation(), None);
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let exif = Exif::parse(&phone_exif(true)).unwrap();
+        let chunk = exif.to_chunk().unwrap();
+        assert_eq!(Exif::try_from(&chunk).unwrap(), exif);
+    }
+
+    #[test]
+    fn test_invalid_exif() {
+        assert!(Exif::parse(b"not tiff").is_err());
+        let mut truncated = phone_exif(true);
+        truncated.truncate(40);
+        assert!(Exif::parse(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let exif = Exif::parse(&phone_exif(true)).unwrap();
+        let shown = exif.to_string();
+        assert!(shown.contains("Orientation                 6 (right-top, rotated 90° CW)"));
+        assert!(shown.contains("GPS     GPSLatitude                 51/1, 30/1, 2640/100"));
+        assert!(shown.contains("GPS position: 51.507333, -0.127667"));
+    }
+}
@@ -0,0 +1,52 @@
+/// Formats `bytes` like `hexdump -C`: an offset, sixteen bytes in two groups of eight and their
+/// printable ASCII, ending with the offset just past the last byte. Offsets count from `start`,
+/// so a dump of part of a file lines up with the file in a hex editor.
+pub fn hex_dump(bytes: &[u8], start: usize) -> String {
+    let mut dump = String::new();
+    for (row, line) in bytes.chunks(16).enumerate() {
+        dump.push_str(&format!("{:08x} ", start + row * 16));
+        for column in 0..16 {
+            if column == 8 {
+                dump.push(' ');
+            }
+            match line.get(column) {
+                Some(byte) => dump.push_str(&format!(" {:02x}", byte)),
+                None => dump.push_str("   "),
+            }
+        }
+        let ascii: String = line
+            .iter()
+            .map(|&byte| match byte {
+                0x20..=0x7e => byte as char,
+                _ => '.',
+            })
+            .collect();
+        dump.push_str(&format!("  |{}|\n", ascii));
+    }
+    dump.push_str(&format!("{:08x}\n", start + bytes.len()));
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_hexdump_layout() {
+        let dump = hex_dump(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0", 0);
+        assert_eq!(
+            dump,
+            "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|\n\
+             00000010  00 00                                             |..|\n\
+             00000012\n"
+        );
+    }
+
+    #[test]
+    fn test_offsets_count_from_start() {
+        let dump = hex_dump(b"abc", 0x21);
+        assert!(dump.starts_with("00000021  61 62 63 "));
+        assert!(dump.ends_with("|abc|\n00000024\n"));
+        assert_eq!(hex_dump(&[], 8), "00000008\n");
+    }
+}
@@ -0,0 +1,376 @@
+use crate::png::{Chunk, Png};
+use crate::{zlib, Result};
+use std::fmt::Display;
+
+/// Adam7 pass origins and strides as (x0, y0, dx, dy).
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+impl ColorType {
+    pub fn channels(&self) -> u32 {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+}
+impl TryFrom<u8> for ColorType {
+    type Error = crate::Error;
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(Box::new(std::io::Error::other(format!(
+                "invalid color type: {}",
+                value
+            )))),
+        }
+    }
+}
+impl Display for ColorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "rgb",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale+alpha",
+            ColorType::Rgba => "rgba",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Decoded IHDR chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageHeader {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: ColorType,
+    interlaced: bool,
+}
+impl ImageHeader {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+    pub fn color_type(&self) -> ColorType {
+        self.color_type
+    }
+    pub fn interlaced(&self) -> bool {
+        self.interlaced
+    }
+    /// Largest value a sample can hold at this bit depth.
+    pub fn max_sample(&self) -> u16 {
+        (((1u32 << self.bit_depth) - 1) as u16).max(1)
+    }
+    pub fn bits_per_pixel(&self) -> u32 {
+        self.color_type.channels() * self.bit_depth as u32
+    }
+    /// Byte distance to the corresponding byte of the previous pixel, as used by the filters.
+    pub fn filter_stride(&self) -> usize {
+        ((self.bits_per_pixel() as usize) / 8).max(1)
+    }
+    /// Length in bytes of one unfiltered scanline `width` pixels wide.
+    pub fn row_bytes(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel() as usize).div_ceil(8)
+    }
+    /// Pixel dimensions of each reduced image, one for non-interlaced images and seven for Adam7.
+    pub fn pass_sizes(&self) -> Vec<(u32, u32)> {
+        if !self.interlaced {
+            return vec![(self.width, self.height)];
+        }
+        ADAM7
+            .iter()
+            .map(|&(x0, y0, dx, dy)| {
+                (
+                    self.width.saturating_sub(x0).div_ceil(dx),
+                    self.height.saturating_sub(y0).div_ceil(dy),
+                )
+            })
+            .map(|(w, h)| if w == 0 || h == 0 { (0, 0) } else { (w, h) })
+            .collect()
+    }
+}
+impl TryFrom<&Chunk> for ImageHeader {
+    type Error = crate::Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        if &chunk.chunk_type().bytes() != b"IHDR" || data.len() != 13 {
+            return Err(Box::new(std::io::Error::other("invalid IHDR chunk")));
+        }
+        let width = u32::from_be_bytes(data[0..4].try_into()?);
+        let height = u32::from_be_bytes(data[4..8].try_into()?);
+        let bit_depth = data[8];
+        let color_type = ColorType::try_from(data[9])?;
+        let allowed: &[u8] = match color_type {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            _ => &[8, 16],
+        };
+        if width == 0 || height == 0 || !allowed.contains(&bit_depth) {
+            return Err(Box::new(std::io::Error::other(format!(
+                "invalid IHDR: {}x{} {}-bit {}",
+                width, height, bit_depth, color_type
+            ))));
+        }
+        if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+            return Err(Box::new(std::io::Error::other(
+                "unsupported IHDR compression, filter or interlace method",
+            )));
+        }
+        Ok(ImageHeader {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced: data[12] == 1,
+        })
+    }
+}
+
+/// One reduced image: its unfiltered scanlines and the filter type each was stored with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pass {
+    width: u32,
+    filter_types: Vec<u8>,
+    rows: Vec<Vec<u8>>,
+}
+impl Pass {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn filter_types(&self) -> &[u8] {
+        &self.filter_types
+    }
+    pub fn filter_types_mut(&mut self) -> &mut [u8] {
+        &mut self.filter_types
+    }
+    pub fn rows(&self) -> &[Vec<u8>] {
+        &self.rows
+    }
+    pub fn rows_mut(&mut self) -> &mut [Vec<u8>] {
+        &mut self.rows
+    }
+}
+
+/// The decompressed and unfiltered contents of a PNG's IDAT stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageData {
+    header: ImageHeader,
+    passes: Vec<Pass>,
+}
+impl ImageData {
+    pub fn decode(png: &Png) -> Result<ImageData> {
+        let header = png.image_header()?;
+        let inflated = zlib::decompress(&png.image_data())?;
+        let expected = header
+            .pass_sizes()
+            .iter()
+            .try_fold(0usize, |total, &(width, height)| {
+                (header.row_bytes(width) + 1)
+                    .checked_mul(height as usize)
+                    .and_then(|len| total.checked_add(len))
+            });
+        if expected.is_none_or(|expected| expected > inflated.len()) {
+            return Err(Box::new(std::io::Error::other("image data is truncated")));
+        }
+
+        let stride = header.filter_stride();
+        let mut index = 0;
+        let mut passes = vec![];
+        for (width, height) in header.pass_sizes() {
+            let row_len = header.row_bytes(width);
+            let mut pass = Pass {
+                width,
+                filter_types: vec![],
+                rows: vec![],
+            };
+            let mut prev = vec![0; row_len];
+            for _ in 0..height {
+                if index + 1 + row_len > inflated.len() {
+                    return Err(Box::new(std::io::Error::other("image data is truncated")));
+                }
+                let filter_type = inflated[index];
+                let mut row = inflated[index + 1..index + 1 + row_len].to_vec();
+                unfilter(filter_type, &mut row, &prev, stride)?;
+                index += 1 + row_len;
+                pass.filter_types.push(filter_type);
+                prev.clone_from(&row);
+                pass.rows.push(row);
+            }
+            passes.push(pass);
+        }
+        Ok(ImageData { header, passes })
+    }
+    pub fn header(&self) -> &ImageHeader {
+        &self.header
+    }
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+    pub fn passes_mut(&mut self) -> &mut [Pass] {
+        &mut self.passes
+    }
+    /// Refilters every scanline with its stored filter type and deflates the result.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let stride = self.header.filter_stride();
+        let mut filtered = vec![];
+        for pass in &self.passes {
+            let mut prev = vec![0; self.header.row_bytes(pass.width)];
+            for (filter_type, row) in pass.filter_types.iter().zip(&pass.rows) {
+                filtered.push(*filter_type);
+                filtered.extend(filter(*filter_type, row, &prev, stride)?);
+                prev.clone_from(row);
+            }
+        }
+        zlib::compress(&filtered)
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn predictor(filter_type: u8, a: u8, b: u8, c: u8) -> Result<u8> {
+    match filter_type {
+        0 => Ok(0),
+        1 => Ok(a),
+        2 => Ok(b),
+        3 => Ok(((a as u16 + b as u16) / 2) as u8),
+        4 => Ok(paeth(a, b, c)),
+        _ => Err(Box::new(std::io::Error::other(format!(
+            "invalid filter type: {}",
+            filter_type
+        )))),
+    }
+}
+
+fn unfilter(filter_type: u8, row: &mut [u8], prev: &[u8], stride: usize) -> Result<()> {
+    for i in 0..row.len() {
+        let a = if i >= stride { row[i - stride] } else { 0 };
+        let c = if i >= stride { prev[i - stride] } else { 0 };
+        row[i] = row[i].wrapping_add(predictor(filter_type, a, prev[i], c)?);
+    }
+    Ok(())
+}
+
+fn filter(filter_type: u8, row: &[u8], prev: &[u8], stride: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(row.len());
+    for i in 0..row.len() {
+        let a = if i >= stride { row[i - stride] } else { 0 };
+        let c = if i >= stride { prev[i - stride] } else { 0 };
+        out.push(row[i].wrapping_sub(predictor(filter_type, a, prev[i], c)?));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::ChunkType;
+    use std::str::FromStr;
+
+    fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8, interlace: u8) -> Chunk {
+        let mut data = vec![];
+        data.extend(width.to_be_bytes());
+        data.extend(height.to_be_bytes());
+        data.extend([bit_depth, color_type, 0, 0, interlace]);
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), data)
+    }
+
+    #[test]
+    fn test_image_header_from_chunk() {
+        let header = ImageHeader::try_from(&ihdr(50, 40, 8, 6, 0)).unwrap();
+        assert_eq!(header.width(), 50);
+        assert_eq!(header.height(), 40);
+        assert_eq!(header.color_type(), ColorType::Rgba);
+        assert_eq!(header.bits_per_pixel(), 32);
+        assert_eq!(header.row_bytes(50), 200);
+        assert!(!header.interlaced());
+    }
+
+    #[test]
+    fn test_invalid_bit_depth() {
+        assert!(ImageHeader::try_from(&ihdr(1, 1, 16, 3, 0)).is_err());
+    }
+
+    #[test]
+    fn test_adam7_pass_sizes() {
+        let header = ImageHeader::try_from(&ihdr(10, 3, 8, 0, 1)).unwrap();
+        assert_eq!(
+            header.pass_sizes(),
+            vec![(2, 1), (1, 1), (0, 0), (2, 1), (5, 1), (5, 2), (10, 1)]
+        );
+    }
+
+    #[test]
+    fn test_huge_dimensions() {
+        let header = ImageHeader::try_from(&ihdr(u32::MAX, u32::MAX, 8, 0, 1)).unwrap();
+        assert_eq!(header.pass_sizes()[0], (u32::MAX / 8 + 1, u32::MAX / 8 + 1));
+        assert_eq!(header.pass_sizes()[6], (u32::MAX, u32::MAX / 2));
+
+        let mut png = Png::from_chunks(vec![ihdr(0xffff_fff0, 1, 16, 6, 0)]);
+        png.set_image_data(zlib::compress(&[0; 64]).unwrap());
+        let error = ImageData::decode(&png).unwrap_err();
+        assert_eq!(error.to_string(), "image data is truncated");
+    }
+
+    #[test]
+    fn test_filter_round_trip() {
+        let prev = [10, 20, 30, 40, 50, 60];
+        let row = [200, 7, 99, 3, 255, 0];
+        for filter_type in 0..5 {
+            let mut filtered = filter(filter_type, &row, &prev, 2).unwrap();
+            unfilter(filter_type, &mut filtered, &prev, 2).unwrap();
+            assert_eq!(filtered, row);
+        }
+    }
+
+    #[test]
+    fn test_decode_encode_round_trip() {
+        let png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        let image = ImageData::decode(&png).unwrap();
+        assert_eq!(image.passes().len(), 1);
+        assert_eq!(image.passes()[0].rows().len(), 50);
+
+        let mut rewritten = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        rewritten.set_image_data(image.encode().unwrap());
+        assert_eq!(ImageData::decode(&rewritten).unwrap(), image);
+    }
+}
@@ -1,10 +1,34 @@
+mod analysis;
+mod ancillary;
+mod apng;
 mod args;
 mod chunk;
 mod chunk_type;
+mod codec;
+mod color;
 mod commands;
+mod diff;
+mod exif;
+mod hexdump;
+mod image;
+mod manifest;
+mod output;
+mod patch;
+mod phys;
 mod png;
+mod repair;
+mod scrub;
+mod stego;
+mod text;
+mod timestamp;
+mod xmp;
+mod zlib;
 use crate::args::Args;
-use crate::commands::{decode, encode, print, remove};
+use crate::commands::{
+    analyze, apng, apply, capacity, color, copy_chunks, decode, diff, dpi, dump, encode, exif,
+    export_meta, import_meta, inject, print, remove, repair, scrub, strip_trailer, text, touch,
+    update, xmp,
+};
 use clap::Parser;
 
 pub type Error = Box<dyn std::error::Error>;
@@ -17,7 +41,27 @@ fn main() -> Result<()> {
         Args::Encode(encode_args) => encode(encode_args)?,
         Args::Decode(decode_args) => decode(decode_args)?,
         Args::Remove(remove_args) => remove(remove_args)?,
+        Args::Update(update_args) => update(update_args)?,
         Args::Print(print_args) => print(print_args)?,
+        Args::Analyze(analyze_args) => analyze(analyze_args)?,
+        Args::StripTrailer(strip_args) => strip_trailer(strip_args)?,
+        Args::Capacity(capacity_args) => capacity(capacity_args)?,
+        Args::Text(text_args) => text(text_args)?,
+        Args::Xmp(xmp_args) => xmp(xmp_args)?,
+        Args::Exif(exif_args) => exif(exif_args)?,
+        Args::Scrub(scrub_args) => scrub(scrub_args)?,
+        Args::Color(color_args) => color(color_args)?,
+        Args::Dpi(dpi_args) => dpi(dpi_args)?,
+        Args::Touch(touch_args) => touch(touch_args)?,
+        Args::Apng(apng_args) => apng(apng_args)?,
+        Args::ExportMeta(export_args) => export_meta(export_args)?,
+        Args::ImportMeta(import_args) => import_meta(import_args)?,
+        Args::CopyChunks(copy_args) => copy_chunks(copy_args)?,
+        Args::Diff(diff_args) => diff(diff_args)?,
+        Args::Apply(apply_args) => apply(apply_args)?,
+        Args::Repair(repair_args) => repair(repair_args)?,
+        Args::Dump(dump_args) => dump(dump_args)?,
+        Args::Inject(inject_args) => inject(inject_args)?,
     }
     Ok(())
 }
@@ -0,0 +1,344 @@
+use crate::png::{Chunk, ChunkType, Png};
+use crate::Result;
+use std::str::FromStr;
+
+/// Ancillary types that must come after PLTE when there is one.
+const AFTER_PALETTE_TYPES: [&str; 3] = ["tRNS", "bKGD", "hIST"];
+/// Ancillary types that may appear more than once, so merging adds to them instead of replacing.
+const REPEATABLE_TYPES: [&str; 6] = ["tEXt", "zTXt", "iTXt", "sPLT", "fcTL", "fdAT"];
+
+/// Where a chunk sits relative to the image data, which is what import preserves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Position {
+    /// Before PLTE, or before the first IDAT if there is no palette.
+    BeforePalette,
+    /// Between PLTE and the first IDAT.
+    BeforeImageData,
+    /// After the image data, before IEND.
+    AfterImageData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestChunk {
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    chunk_type: String,
+    position: Position,
+    #[cfg_attr(feature = "serde", serde(with = "base64_data"))]
+    data: Vec<u8>,
+}
+
+/// Every chunk of a file except IDAT, in order, as written by `export-meta`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    chunks: Vec<ManifestChunk>,
+}
+impl Manifest {
+    pub fn from_png(png: &Png) -> Manifest {
+        Manifest::from_chunks_where(png, |chunk| &chunk.chunk_type().bytes() != b"IDAT")
+    }
+    /// The chunks of `png` for which `include` returns true, with their positions.
+    pub fn from_chunks_where<F: FnMut(&Chunk) -> bool>(png: &Png, mut include: F) -> Manifest {
+        let types: Vec<[u8; 4]> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().bytes())
+            .collect();
+        let palette = types.iter().position(|t| t == b"PLTE");
+        let image_data = types.iter().position(|t| t == b"IDAT");
+        let chunks = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| include(chunk))
+            .map(|(index, chunk)| {
+                let chunk_type = chunk.chunk_type().to_string();
+                let position = if image_data.is_none_or(|idat| index > idat) {
+                    Position::AfterImageData
+                } else if palette
+                    .map_or(AFTER_PALETTE_TYPES.contains(&chunk_type.as_str()), |plte| {
+                        index > plte
+                    })
+                {
+                    Position::BeforeImageData
+                } else {
+                    Position::BeforePalette
+                };
+                ManifestChunk {
+                    chunk_type,
+                    position,
+                    data: chunk.data().to_vec(),
+                }
+            })
+            .collect();
+        Manifest { chunks }
+    }
+    /// Rebuilds the manifest's ancillary chunks in `png`, with fresh CRCs. Replacing first removes
+    /// every ancillary chunk from `png`; merging keeps them, except those of a non-repeatable type
+    /// the manifest also has. Critical chunks describe the source's own pixels and are skipped.
+    /// Returns a line per change.
+    pub fn apply(&self, png: &mut Png, merge: bool) -> Result<Vec<String>> {
+        let mut report = vec![];
+        let mut incoming = vec![];
+        for entry in &self.chunks {
+            let chunk_type = ChunkType::from_str(&entry.chunk_type)?;
+            if chunk_type.is_critical() {
+                report.push(format!("skipped critical {}", chunk_type));
+            } else {
+                incoming.push((entry.position, Chunk::new(chunk_type, entry.data.clone())));
+            }
+        }
+
+        let mut index = 0;
+        while index < png.chunks().len() {
+            let existing = &png.chunks()[index];
+            let chunk_type = existing.chunk_type();
+            let remove = !chunk_type.is_critical()
+                && if merge {
+                    !REPEATABLE_TYPES
+                        .iter()
+                        .any(|t| t.as_bytes() == chunk_type.bytes())
+                        && incoming.iter().any(|(_, c)| {
+                            c.chunk_type() == chunk_type && c.data() != existing.data()
+                        })
+                } else {
+                    true
+                };
+            if remove {
+                report.push(format!(
+                    "removed {}",
+                    png.remove_chunk_at(index)?.chunk_type()
+                ));
+            } else {
+                index += 1;
+            }
+        }
+
+        for (position, chunk) in incoming {
+            let duplicate = png.chunks().iter().any(|existing| {
+                existing.chunk_type() == chunk.chunk_type() && existing.data() == chunk.data()
+            });
+            if duplicate {
+                continue;
+            }
+            report.push(format!("added {}", chunk.chunk_type()));
+            match position {
+                Position::BeforePalette => png.insert_before_first(&["PLTE", "IDAT"], chunk),
+                Position::BeforeImageData => png.insert_before_first(&["IDAT"], chunk),
+                Position::AfterImageData => png.insert_before_end(chunk),
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Copies the ancillary chunks of `source`, or only those of `types` if given, into `target`,
+/// merging as `import-meta --merge` does. Unsafe-to-copy chunks depend on critical chunks, so
+/// when those differ between the files they are skipped, or copied anyway with `force`.
+/// Returns a line per change or warning.
+pub fn copy_chunks(
+    source: &Png,
+    target: &mut Png,
+    types: &[ChunkType],
+    force: bool,
+) -> Result<Vec<String>> {
+    let critical = |png: &Png| -> Vec<Vec<u8>> {
+        png.chunks()
+            .iter()
+            .filter(|chunk| {
+                chunk.chunk_type().is_critical() && &chunk.chunk_type().bytes() != b"IEND"
+            })
+            .map(|chunk| chunk.as_bytes())
+            .collect()
+    };
+    let same_image = critical(source) == critical(target);
+    let mut report = vec![];
+    let manifest = Manifest::from_chunks_where(source, |chunk| {
+        let chunk_type = chunk.chunk_type();
+        if chunk_type.is_critical() || !(types.is_empty() || types.contains(chunk_type)) {
+            return false;
+        }
+        if same_image || chunk_type.is_safe_to_copy() {
+            return true;
+        }
+        if force {
+            report.push(format!(
+                "warning: copied unsafe-to-copy {} although the critical chunks differ",
+                chunk_type
+            ));
+        } else {
+            report.push(format!(
+                "skipped unsafe-to-copy {}: the critical chunks differ, use --force to copy it",
+                chunk_type
+            ));
+        }
+        force
+    });
+    report.extend(manifest.apply(target, true)?);
+    Ok(report)
+}
+
+#[cfg(feature = "serde")]
+mod base64_data {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Manifest {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+    pub fn from_json(json: &str) -> Result<Manifest> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+#[cfg(not(feature = "serde"))]
+impl Manifest {
+    pub fn to_json(&self) -> Result<String> {
+        Err("metadata manifests need the serde feature".into())
+    }
+    pub fn from_json(_json: &str) -> Result<Manifest> {
+        Err("metadata manifests need the serde feature".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{chunk, types};
+
+    fn indexed_png(extra: Vec<Chunk>) -> Png {
+        let mut chunks = vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("PLTE", &[0, 0, 0]),
+            chunk("tRNS", &[0]),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("tEXt", b"Title\0source"),
+        ];
+        chunks.extend(extra);
+        chunks.push(chunk("IEND", &[]));
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_positions() {
+        let manifest = Manifest::from_png(&indexed_png(vec![]));
+        let positions: Vec<(&str, Position)> = manifest
+            .chunks
+            .iter()
+            .map(|c| (c.chunk_type.as_str(), c.position))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                ("IHDR", Position::BeforePalette),
+                ("gAMA", Position::BeforePalette),
+                ("PLTE", Position::BeforePalette),
+                ("tRNS", Position::BeforeImageData),
+                ("tEXt", Position::AfterImageData),
+                ("IEND", Position::AfterImageData),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_keeps_ordering_around_palette() {
+        let source = indexed_png(vec![]);
+        let mut target = indexed_png(vec![chunk("tIME", &[7, 232, 1, 1, 0, 0, 0])]);
+        target.remove_chunk("gAMA").unwrap();
+        target.remove_chunk("tRNS").unwrap();
+        let report = Manifest::from_png(&source)
+            .apply(&mut target, false)
+            .unwrap();
+        assert!(report.contains(&"skipped critical PLTE".to_string()));
+        assert!(report.contains(&"removed tIME".to_string()));
+        assert_eq!(
+            types(&target),
+            ["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "tEXt", "IEND"]
+        );
+        let source_crc = source.chunk_by_type("gAMA").unwrap().crc();
+        assert_eq!(target.chunk_by_type("gAMA").unwrap().crc(), source_crc);
+    }
+
+    #[test]
+    fn test_merge_keeps_target_text() {
+        let mut source = indexed_png(vec![]);
+        source.remove_chunk("tEXt").unwrap();
+        source.insert_before_end(chunk("tEXt", b"Author\0someone"));
+        let mut target = indexed_png(vec![]);
+        Manifest::from_png(&source)
+            .apply(&mut target, true)
+            .unwrap();
+        let texts = target
+            .chunks()
+            .iter()
+            .filter(|c| &c.chunk_type().bytes() == b"tEXt")
+            .count();
+        assert_eq!(texts, 2);
+        assert_eq!(
+            target
+                .chunks()
+                .iter()
+                .filter(|c| &c.chunk_type().bytes() == b"gAMA")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_copy_chunks_honors_safe_to_copy() {
+        let mut source = indexed_png(vec![chunk("ruSt", b"safe"), chunk("ruST", b"unsafe")]);
+        let mut target = indexed_png(vec![]);
+        target.remove_chunk("tEXt").unwrap();
+        let report = copy_chunks(&source, &mut target, &[], false).unwrap();
+        assert!(report.contains(&"added ruSt".to_string()));
+        assert!(report.contains(&"added ruST".to_string()));
+
+        source.remove_chunk("PLTE").unwrap();
+        source.insert_before_first(&["tRNS"], chunk("PLTE", &[255, 0, 0]));
+        let mut target = indexed_png(vec![]);
+        let report = copy_chunks(&source, &mut target, &[], false).unwrap();
+        assert!(report
+            .iter()
+            .any(|line| line.starts_with("skipped unsafe-to-copy ruST")));
+        assert!(report.contains(&"added ruSt".to_string()));
+        assert!(target.chunk_by_type("ruST").is_none());
+        assert!(target.chunk_by_type("ruSt").is_some());
+
+        let mut target = indexed_png(vec![]);
+        let only = [ChunkType::from_str("ruST").unwrap()];
+        let report = copy_chunks(&source, &mut target, &only, true).unwrap();
+        assert!(report[0].starts_with("warning:"));
+        assert_eq!(
+            types(&target),
+            ["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "tEXt", "ruST", "IEND"]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_round_trip() {
+        let png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        let manifest = Manifest::from_png(&png);
+        let json = manifest.to_json().unwrap();
+        assert!(json.contains("\"position\": \"before_palette\""));
+        assert_eq!(Manifest::from_json(&json).unwrap(), manifest);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::args::Format;
+use crate::codec::CodecRegistry;
+use crate::image::ImageHeader;
+use crate::png::{Diagnostic, Png};
+use crate::Result;
+
+/// A file and the decoded fields of each chunk a codec understands, as `print` shows them.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub struct Inspection<'a> {
+    png: &'a Png,
+    registry: &'a CodecRegistry,
+    diagnostics: &'a [Diagnostic],
+}
+impl<'a> Inspection<'a> {
+    pub fn new(png: &'a Png, registry: &'a CodecRegistry) -> Inspection<'a> {
+        Inspection {
+            png,
+            registry,
+            diagnostics: &[],
+        }
+    }
+    /// Adds the problems a lenient parse found, listed only when there are some.
+    pub fn with_diagnostics(self, diagnostics: &'a [Diagnostic]) -> Inspection<'a> {
+        Inspection {
+            diagnostics,
+            ..self
+        }
+    }
+}
+
+/// A message read back by `decode`.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub struct DecodedMessage<'a> {
+    chunk_type: &'a str,
+    message: Option<&'a [u8]>,
+}
+impl<'a> DecodedMessage<'a> {
+    pub fn new(chunk_type: &'a str, message: Option<&'a [u8]>) -> DecodedMessage<'a> {
+        DecodedMessage {
+            chunk_type,
+            message,
+        }
+    }
+}
+
+/// The chunks of a few types with their decoded fields, as the metadata commands show them,
+/// and any problems found with them.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub struct DecodedChunks<'a> {
+    png: &'a Png,
+    registry: CodecRegistry,
+    types: &'a [&'a str],
+    warnings: Vec<String>,
+}
+impl<'a> DecodedChunks<'a> {
+    pub fn new(png: &'a Png, types: &'a [&'a str]) -> DecodedChunks<'a> {
+        DecodedChunks {
+            png,
+            registry: CodecRegistry::with_builtins(),
+            types,
+            warnings: vec![],
+        }
+    }
+    pub fn with_warnings(self, warnings: Vec<String>) -> DecodedChunks<'a> {
+        DecodedChunks { warnings, ..self }
+    }
+}
+
+/// What `capacity` reports: the image and how many message bytes each method can hide.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub struct Capacity<'a> {
+    header: &'a ImageHeader,
+    methods: &'a [(String, Result<usize>)],
+}
+impl<'a> Capacity<'a> {
+    pub fn new(header: &'a ImageHeader, methods: &'a [(String, Result<usize>)]) -> Capacity<'a> {
+        Capacity { header, methods }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serialize {
+    use super::*;
+    use crate::codec::Fields;
+    use crate::png::Chunk;
+    use crate::stego;
+    use base64::Engine;
+    use serde::ser::{Serialize, SerializeMap, SerializeStruct, Serializer};
+
+    /// Decoded fields as a map from name to value, in display order.
+    struct FieldMap<'a>(&'a [(String, String)]);
+    impl Serialize for FieldMap<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().map(|(name, value)| (name, value)))
+        }
+    }
+
+    impl Serialize for Inspection<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            struct Decoded<'a>(&'a Inspection<'a>);
+            impl Serialize for Decoded<'_> {
+                fn serialize<S: Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> std::result::Result<S::Ok, S::Error> {
+                    let Inspection { png, registry, .. } = self.0;
+                    let mut map = serializer.serialize_map(None)?;
+                    for (index, chunk) in png.chunks().iter().enumerate() {
+                        match registry.decode(chunk, png) {
+                            Some(Ok(fields)) => map.serialize_entry(&index, &FieldMap(&fields))?,
+                            Some(Err(e)) => map.serialize_entry(
+                                &index,
+                                &FieldMap(&[("error".to_string(), e.to_string())]),
+                            )?,
+                            None => {}
+                        }
+                    }
+                    map.end()
+                }
+            }
+            let mut state = serializer.serialize_struct("Inspection", 3)?;
+            state.serialize_field("png", self.png)?;
+            state.serialize_field("decoded", &Decoded(self))?;
+            if self.diagnostics.is_empty() {
+                state.skip_field("diagnostics")?;
+            } else {
+                state.serialize_field("diagnostics", self.diagnostics)?;
+            }
+            state.end()
+        }
+    }
+
+    impl Serialize for DecodedChunks<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            struct Entry<'a>(usize, &'a Chunk, Option<Result<Fields>>);
+            impl Serialize for Entry<'_> {
+                fn serialize<S: Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> std::result::Result<S::Ok, S::Error> {
+                    let Entry(index, chunk, decoded) = self;
+                    let mut state = serializer.serialize_struct("Entry", 3)?;
+                    state.serialize_field("index", index)?;
+                    state.serialize_field("type", chunk.chunk_type())?;
+                    match decoded {
+                        Some(Ok(fields)) => state.serialize_field("fields", &FieldMap(fields))?,
+                        Some(Err(e)) => state.serialize_field("error", &e.to_string())?,
+                        None => state.skip_field("fields")?,
+                    }
+                    state.end()
+                }
+            }
+            let entries: Vec<Entry> = self
+                .png
+                .chunks()
+                .iter()
+                .enumerate()
+                .filter(|(_, chunk)| {
+                    self.types
+                        .iter()
+                        .any(|t| t.as_bytes() == chunk.chunk_type().bytes())
+                })
+                .map(|(index, chunk)| Entry(index, chunk, self.registry.decode(chunk, self.png)))
+                .collect();
+            let mut state = serializer.serialize_struct("DecodedChunks", 2)?;
+            state.serialize_field("chunks", &entries)?;
+            if self.warnings.is_empty() {
+                state.skip_field("warnings")?;
+            } else {
+                state.serialize_field("warnings", &self.warnings)?;
+            }
+            state.end()
+        }
+    }
+
+    impl Serialize for Capacity<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            struct Methods<'a>(&'a [(String, Result<usize>)]);
+            impl Serialize for Methods<'_> {
+                fn serialize<S: Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> std::result::Result<S::Ok, S::Error> {
+                    let mut map = serializer.serialize_map(Some(self.0.len()))?;
+                    for (name, capacity) in self.0 {
+                        match capacity {
+                            Ok(bytes) => map.serialize_entry(name, bytes)?,
+                            Err(e) => map.serialize_entry(
+                                name,
+                                &FieldMap(&[("error".to_string(), e.to_string())]),
+                            )?,
+                        }
+                    }
+                    map.end()
+                }
+            }
+            let mut state = serializer.serialize_struct("Capacity", 8)?;
+            state.serialize_field("width", &self.header.width())?;
+            state.serialize_field("height", &self.header.height())?;
+            state.serialize_field("bit_depth", &self.header.bit_depth())?;
+            state.serialize_field("color_type", &self.header.color_type().to_string())?;
+            state.serialize_field("chunk", &Chunk::MAX_LENGTH)?;
+            state.serialize_field("trailer_overhead", &stego::TRAILER_OVERHEAD)?;
+            state.serialize_field("frame_overhead", &stego::FRAME_OVERHEAD)?;
+            state.serialize_field("methods", &Methods(self.methods))?;
+            state.end()
+        }
+    }
+
+    impl Serialize for DecodedMessage<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            let mut state = serializer.serialize_struct("DecodedMessage", 4)?;
+            state.serialize_field("chunk_type", self.chunk_type)?;
+            state.serialize_field("found", &self.message.is_some())?;
+            state.serialize_field(
+                "text",
+                &self.message.map(|message| String::from_utf8_lossy(message)),
+            )?;
+            state.serialize_field(
+                "data",
+                &self
+                    .message
+                    .map(|message| base64::engine::general_purpose::STANDARD.encode(message)),
+            )?;
+            state.end()
+        }
+    }
+}
+
+/// Renders `value` as JSON or YAML.
+#[cfg(feature = "serde")]
+pub fn render<T: serde::Serialize>(value: &T, format: Format) -> Result<String> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(value)?),
+        Format::Yaml => Ok(serde_yaml::to_string(value)?),
+        Format::Text => Err("text output is written by each command".into()),
+    }
+}
+#[cfg(not(feature = "serde"))]
+pub fn render<T>(_value: &T, format: Format) -> Result<String> {
+    Err(format!("{:?} output needs the serde feature", format).into())
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+
+    #[test]
+    fn test_png_json_has_offsets_and_property_bits() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let value = serde_json::to_value(&png).unwrap();
+        let chunks = value["chunks"].as_array().unwrap();
+        assert_eq!(chunks.len(), png.chunks().len());
+        assert_eq!(chunks[0]["type"], "IHDR");
+        assert_eq!(chunks[0]["offset"], 8);
+        assert_eq!(chunks[0]["critical"], true);
+        assert_eq!(chunks[1]["offset"], 8 + 12 + 13);
+        assert_eq!(chunks[0]["crc"], format!("{:08x}", png.chunks()[0].crc()));
+        let rust = chunks.iter().find(|c| c["type"] == "RuSt").unwrap();
+        assert_eq!(rust["public"], false);
+        assert_eq!(value["signature_valid"], true);
+    }
+
+    #[test]
+    fn test_inspection_includes_decoded_fields() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let registry = CodecRegistry::with_builtins();
+        let json = render(&Inspection::new(&png, &registry), Format::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["decoded"]["0"]["width"], "50");
+        let yaml = render(&Inspection::new(&png, &registry), Format::Yaml).unwrap();
+        assert!(yaml.contains("type: IHDR"));
+    }
+
+    #[test]
+    fn test_decoded_message() {
+        let json = render(&DecodedMessage::new("ruSt", Some(b"hi")), Format::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["text"], "hi");
+        assert_eq!(value["data"], "aGk=");
+        let missing = serde_json::to_value(DecodedMessage::new("ruSt", None)).unwrap();
+        assert_eq!(missing["found"], false);
+    }
+
+    #[test]
+    fn test_decoded_chunks_keep_index_and_warnings() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let types = ["pHYs"];
+        let chunks = DecodedChunks::new(&png, &types).with_warnings(vec!["odd".to_string()]);
+        let value = serde_json::to_value(&chunks).unwrap();
+        let decoded = value["chunks"].as_array().unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0]["index"], 3);
+        assert_eq!(decoded[0]["type"], "pHYs");
+        assert_eq!(value["warnings"][0], "odd");
+    }
+
+    #[test]
+    fn test_capacity_reports_bytes_and_errors() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let header = png.image_header().unwrap();
+        let methods = vec![
+            ("lsb_1".to_string(), Ok(42)),
+            ("palette".to_string(), Err("no palette".into())),
+        ];
+        let value = serde_json::to_value(Capacity::new(&header, &methods)).unwrap();
+        assert_eq!(value["width"], 50);
+        assert_eq!(value["methods"]["lsb_1"], 42);
+        assert_eq!(value["methods"]["palette"]["error"], "no palette");
+    }
+}
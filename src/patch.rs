@@ -0,0 +1,292 @@
+use crate::chunk::invalid;
+use crate::diff::align;
+use crate::png::{Chunk, Png};
+use crate::{Error, Result};
+use sha2::{Digest, Sha256};
+
+const MAGIC: &[u8; 8] = b"PNGPATCH";
+const VERSION: u8 = 1;
+
+fn digest(png: &Png) -> [u8; 32] {
+    Sha256::digest(png.as_bytes()).into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// One step of a patch, applied with a cursor moving through the base file's chunks.
+#[derive(Debug, Clone)]
+pub enum PatchOp {
+    /// Keep the next n base chunks.
+    Copy(u32),
+    /// Drop the next n base chunks.
+    Delete(u32),
+    Insert(Chunk),
+    /// Drop the next base chunk and insert this one in its place.
+    Replace(Chunk),
+    /// Set the data after IEND.
+    Trailing(Vec<u8>),
+}
+impl PatchOp {
+    fn tag(&self) -> u8 {
+        match self {
+            PatchOp::Copy(_) => 0,
+            PatchOp::Delete(_) => 1,
+            PatchOp::Insert(_) => 2,
+            PatchOp::Replace(_) => 3,
+            PatchOp::Trailing(_) => 4,
+        }
+    }
+}
+
+/// Chunk-level edits that turn one file into another, with SHA-256 digests of both.
+///
+/// The format is `PNGPATCH`, a version byte, the base and result digests, a big-endian u32 op
+/// count, then each op as a tag byte followed by a u32 count, a whole chunk including its CRC,
+/// or a u32 length and the trailing bytes.
+#[derive(Debug, Clone)]
+pub struct Patch {
+    base_digest: [u8; 32],
+    result_digest: [u8; 32],
+    ops: Vec<PatchOp>,
+}
+impl Patch {
+    pub fn new(base: &Png, result: &Png) -> Patch {
+        let (old, new) = (base.chunks(), result.chunks());
+        let mut ops = vec![];
+        let (mut i, mut j) = (0, 0);
+        let pairs = align(old, new);
+        for (next_i, next_j) in pairs.into_iter().chain([(old.len(), new.len())]) {
+            let (deleted, inserted) = (next_i - i, next_j - j);
+            let replaced = deleted.min(inserted);
+            for chunk in &new[j..j + replaced] {
+                ops.push(PatchOp::Replace(chunk.clone()));
+            }
+            if deleted > replaced {
+                ops.push(PatchOp::Delete((deleted - replaced) as u32));
+            }
+            for chunk in &new[j + replaced..next_j] {
+                ops.push(PatchOp::Insert(chunk.clone()));
+            }
+            if next_i < old.len() {
+                match ops.last_mut() {
+                    Some(PatchOp::Copy(n)) => *n += 1,
+                    _ => ops.push(PatchOp::Copy(1)),
+                }
+            }
+            (i, j) = (next_i + 1, next_j + 1);
+        }
+        if base.trailing_data() != result.trailing_data() {
+            ops.push(PatchOp::Trailing(result.trailing_data().to_vec()));
+        }
+        Patch {
+            base_digest: digest(base),
+            result_digest: digest(result),
+            ops,
+        }
+    }
+    pub fn ops(&self) -> &[PatchOp] {
+        &self.ops
+    }
+    /// Applies the patch to `base`, which must be the file it was made from.
+    pub fn apply(&self, base: &Png) -> Result<Png> {
+        let found = digest(base);
+        if found != self.base_digest {
+            return Err(invalid(format!(
+                "patch is for a file with SHA-256 {}, this one is {}",
+                hex(&self.base_digest),
+                hex(&found)
+            )));
+        }
+        let old = base.chunks();
+        let mut chunks = vec![];
+        let mut cursor = 0;
+        let mut trailing = base.trailing_data().to_vec();
+        let take = |n: usize, cursor: &mut usize| -> Result<std::ops::Range<usize>> {
+            if *cursor + n > old.len() {
+                return Err(invalid(format!(
+                    "patch reads past the {} chunks of the base file",
+                    old.len()
+                )));
+            }
+            *cursor += n;
+            Ok(*cursor - n..*cursor)
+        };
+        for op in &self.ops {
+            match op {
+                PatchOp::Copy(n) => chunks.extend_from_slice(&old[take(*n as usize, &mut cursor)?]),
+                PatchOp::Delete(n) => {
+                    take(*n as usize, &mut cursor)?;
+                }
+                PatchOp::Insert(chunk) => chunks.push(chunk.clone()),
+                PatchOp::Replace(chunk) => {
+                    take(1, &mut cursor)?;
+                    chunks.push(chunk.clone());
+                }
+                PatchOp::Trailing(data) => trailing.clone_from(data),
+            }
+        }
+        if cursor != old.len() {
+            return Err(invalid(format!(
+                "patch leaves {} base chunks unaccounted for",
+                old.len() - cursor
+            )));
+        }
+        let mut result = Png::from_chunks(chunks);
+        result.set_trailing_data(trailing);
+        if digest(&result) != self.result_digest {
+            return Err(invalid(
+                "patched file does not match the expected digest".to_string(),
+            ));
+        }
+        Ok(result)
+    }
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend(self.base_digest);
+        bytes.extend(self.result_digest);
+        bytes.extend((self.ops.len() as u32).to_be_bytes());
+        for op in &self.ops {
+            bytes.push(op.tag());
+            match op {
+                PatchOp::Copy(n) | PatchOp::Delete(n) => bytes.extend(n.to_be_bytes()),
+                PatchOp::Insert(chunk) | PatchOp::Replace(chunk) => bytes.extend(chunk.as_bytes()),
+                PatchOp::Trailing(data) => {
+                    bytes.extend((data.len() as u32).to_be_bytes());
+                    bytes.extend(data);
+                }
+            }
+        }
+        bytes
+    }
+}
+
+/// Reads a patch front to back.
+struct Reader<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .index
+            .checked_add(n)
+            .and_then(|end| self.data.get(self.index..end))
+            .ok_or_else(|| invalid("patch is truncated".to_string()))?;
+        self.index += n;
+        Ok(bytes)
+    }
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+}
+
+impl TryFrom<&[u8]> for Patch {
+    type Error = Error;
+    fn try_from(value: &[u8]) -> Result<Self> {
+        let mut reader = Reader {
+            data: value,
+            index: 0,
+        };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a PNG patch".to_string()));
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(invalid(format!("unsupported patch version {}", version)));
+        }
+        let base_digest = reader.take(32)?.try_into()?;
+        let result_digest = reader.take(32)?.try_into()?;
+        let count = reader.u32()?;
+        let mut ops = vec![];
+        for _ in 0..count {
+            let tag = reader.take(1)?[0];
+            ops.push(match tag {
+                0 => PatchOp::Copy(reader.u32()?),
+                1 => PatchOp::Delete(reader.u32()?),
+                2 | 3 => {
+                    let start = reader.index;
+                    let length = reader.u32()? as usize;
+                    reader.take(length.saturating_add(8))?;
+                    let chunk = Chunk::try_from(&value[start..reader.index])?;
+                    if tag == 2 {
+                        PatchOp::Insert(chunk)
+                    } else {
+                        PatchOp::Replace(chunk)
+                    }
+                }
+                4 => {
+                    let length = reader.u32()? as usize;
+                    PatchOp::Trailing(reader.take(length)?.to_vec())
+                }
+                _ => return Err(invalid(format!("unknown patch operation {}", tag))),
+            });
+        }
+        if reader.index != value.len() {
+            return Err(invalid(format!(
+                "{} unexpected bytes after the patch",
+                value.len() - reader.index
+            )));
+        }
+        Ok(Patch {
+            base_digest,
+            result_digest,
+            ops,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{chunk, PNG_FILE};
+
+    fn edited() -> (Png, Png) {
+        let base = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut result = Png::try_from(&PNG_FILE[..]).unwrap();
+        result.remove_chunk("sRGB").unwrap();
+        result
+            .replace_chunk(chunk("gAMA", &[0, 0, 177, 144]))
+            .unwrap();
+        result.insert_before_end(chunk("tEXt", b"Comment\0patched"));
+        result.set_trailing_data(b"tail".to_vec());
+        (base, result)
+    }
+
+    #[test]
+    fn test_round_trip_through_bytes() {
+        let (base, result) = edited();
+        let patch = Patch::new(&base, &result);
+        let bytes = patch.as_bytes();
+        assert!(bytes.len() < result.as_bytes().len() / 2);
+        let parsed = Patch::try_from(&bytes[..]).unwrap();
+        assert_eq!(parsed.as_bytes(), bytes);
+        let applied = parsed.apply(&base).unwrap();
+        assert_eq!(applied.as_bytes(), result.as_bytes());
+    }
+
+    #[test]
+    fn test_identical_files_only_copy() {
+        let (base, _) = edited();
+        let patch = Patch::new(&base, &base);
+        assert!(matches!(patch.ops(), [PatchOp::Copy(n)] if *n as usize == base.chunks().len()));
+    }
+
+    #[test]
+    fn test_rejects_wrong_base() {
+        let (base, result) = edited();
+        let patch = Patch::new(&base, &result);
+        let error = patch.apply(&result).unwrap_err().to_string();
+        assert!(error.starts_with("patch is for a file with SHA-256"));
+    }
+
+    #[test]
+    fn test_rejects_truncated_patch() {
+        let (base, result) = edited();
+        let bytes = Patch::new(&base, &result).as_bytes();
+        assert!(Patch::try_from(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Patch::try_from(&b"PNGPATCX"[..]).is_err());
+    }
+}
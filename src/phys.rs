@@ -0,0 +1,147 @@
+use crate::chunk::{check_chunk, invalid, u32_at};
+use crate::png::{Chunk, ChunkType, Png};
+use crate::{Error, Result};
+use std::fmt::Display;
+use std::str::FromStr;
+
+const METERS_PER_INCH: f64 = 0.0254;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Only the pixel aspect ratio is known.
+    Unknown,
+    Meter,
+}
+
+/// pHYs: intended pixel size or aspect ratio, as pixels per unit along each axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalDimensions {
+    x: u32,
+    y: u32,
+    unit: Unit,
+}
+impl PhysicalDimensions {
+    pub fn new(x: u32, y: u32, unit: Unit) -> Result<PhysicalDimensions> {
+        if x == 0 || y == 0 {
+            return Err(invalid("pixels per unit must be positive".to_string()));
+        }
+        Ok(PhysicalDimensions { x, y, unit })
+    }
+    /// Square pixels at `dpi` dots per inch, stored as the nearest whole pixels per meter.
+    pub fn from_dpi(dpi: f64) -> Result<PhysicalDimensions> {
+        let per_meter = (dpi / METERS_PER_INCH).round();
+        if !(1.0..=u32::MAX as f64).contains(&per_meter) {
+            return Err(invalid(format!("{} dpi is out of range", dpi)));
+        }
+        PhysicalDimensions::new(per_meter as u32, per_meter as u32, Unit::Meter)
+    }
+    /// Horizontal and vertical dots per inch, if the unit is known.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            Unit::Meter => Some((
+                self.x as f64 * METERS_PER_INCH,
+                self.y as f64 * METERS_PER_INCH,
+            )),
+            Unit::Unknown => None,
+        }
+    }
+    pub fn to_chunk(self) -> Result<Chunk> {
+        let mut data = self.x.to_be_bytes().to_vec();
+        data.extend(self.y.to_be_bytes());
+        data.push(match self.unit {
+            Unit::Unknown => 0,
+            Unit::Meter => 1,
+        });
+        Ok(Chunk::new(ChunkType::from_str("pHYs")?, data))
+    }
+}
+impl TryFrom<&Chunk> for PhysicalDimensions {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "pHYs", Some(9))?;
+        let data = chunk.data();
+        let x = u32_at(data, 0);
+        let y = u32_at(data, 4);
+        let unit = match data[8] {
+            0 => Unit::Unknown,
+            1 => Unit::Meter,
+            unit => return Err(invalid(format!("invalid pHYs unit {}", unit))),
+        };
+        PhysicalDimensions::new(x, y, unit)
+    }
+}
+impl Display for PhysicalDimensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.dpi() {
+            Some((x, _)) if self.x == self.y => {
+                write!(f, "{} pixels per meter ({:.2} dpi)", self.x, x)
+            }
+            Some((x, y)) => write!(
+                f,
+                "{}x{} pixels per meter ({:.2}x{:.2} dpi)",
+                self.x, self.y, x, y
+            ),
+            None => write!(f, "pixel aspect ratio {}:{}", self.y, self.x),
+        }
+    }
+}
+
+/// Writes `dimensions` before IDAT, replacing any existing pHYs chunk.
+pub fn set_physical_dimensions(png: &mut Png, dimensions: PhysicalDimensions) -> Result<()> {
+    png.set_chunk_before(&["IDAT"], dimensions.to_chunk()?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+
+    #[test]
+    fn test_dpi_round_trip() {
+        let dimensions = PhysicalDimensions::from_dpi(300.0).unwrap();
+        let chunk = dimensions.to_chunk().unwrap();
+        assert_eq!(chunk.data(), [0, 0, 46, 35, 0, 0, 46, 35, 1]);
+        assert_eq!(PhysicalDimensions::try_from(&chunk).unwrap(), dimensions);
+        let (x, _) = dimensions.dpi().unwrap();
+        assert!((x - 300.0).abs() < 0.01);
+        assert_eq!(
+            dimensions.to_string(),
+            "11811 pixels per meter (300.00 dpi)"
+        );
+    }
+
+    #[test]
+    fn test_aspect_ratio_only() {
+        let dimensions = PhysicalDimensions::new(2, 1, Unit::Unknown).unwrap();
+        assert_eq!(dimensions.dpi(), None);
+        assert_eq!(dimensions.to_string(), "pixel aspect ratio 1:2");
+        assert!(PhysicalDimensions::new(0, 1, Unit::Meter).is_err());
+        let bad_unit = Chunk::new(
+            ChunkType::from_str("pHYs").unwrap(),
+            vec![0, 0, 0, 1, 0, 0, 0, 1, 2],
+        );
+        assert!(PhysicalDimensions::try_from(&bad_unit).is_err());
+    }
+
+    #[test]
+    fn test_set_physical_dimensions_before_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_before_end(
+            PhysicalDimensions::from_dpi(72.0)
+                .unwrap()
+                .to_chunk()
+                .unwrap(),
+        );
+        set_physical_dimensions(&mut png, PhysicalDimensions::from_dpi(150.0).unwrap()).unwrap();
+        let types: Vec<[u8; 4]> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().bytes())
+            .collect();
+        let phys: Vec<usize> = (0..types.len()).filter(|&i| &types[i] == b"pHYs").collect();
+        let idat = types.iter().position(|t| t == b"IDAT").unwrap();
+        assert_eq!(phys.len(), 1);
+        assert!(phys[0] < idat);
+    }
+}
@@ -1,12 +1,15 @@
 pub use crate::chunk::Chunk;
 pub use crate::chunk_type::ChunkType;
+use crate::image::{ColorType, ImageHeader};
 use crate::{Error, Result};
 use std::fmt::Display;
+use std::str::FromStr;
 
 #[derive(Debug)]
 pub struct Png {
     header: [u8; 8],
     chunks: Vec<Chunk>,
+    trailing: Vec<u8>,
 }
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
@@ -14,11 +17,51 @@ impl Png {
         Png {
             header: Png::STANDARD_HEADER,
             chunks,
+            trailing: vec![],
         }
     }
     pub fn append_chunk(&mut self, chunk: Chunk) {
         self.chunks.push(chunk);
     }
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) {
+        self.chunks.insert(index, chunk);
+    }
+    /// Inserts `chunk` just before IEND, or at the end if there is none.
+    pub fn insert_before_end(&mut self, chunk: Chunk) {
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| &chunk.chunk_type().bytes() == b"IEND")
+            .unwrap_or(self.chunks.len());
+        self.chunks.insert(index, chunk);
+    }
+    /// Inserts `chunk` before the first chunk of any of `chunk_types`, or before IEND.
+    pub fn insert_before_first(&mut self, chunk_types: &[&str], chunk: Chunk) {
+        match self.chunks.iter().position(|existing| {
+            chunk_types
+                .iter()
+                .any(|t| t.as_bytes() == existing.chunk_type().bytes())
+        }) {
+            Some(index) => self.chunks.insert(index, chunk),
+            None => self.insert_before_end(chunk),
+        }
+    }
+    /// Replaces every chunk of `chunk`'s type with `chunk`, placed before the first of `before`.
+    pub fn set_chunk_before(&mut self, before: &[&str], chunk: Chunk) {
+        self.chunks
+            .retain(|existing| existing.chunk_type() != chunk.chunk_type());
+        self.insert_before_first(before, chunk);
+    }
+    pub fn remove_chunk_at(&mut self, index: usize) -> Result<Chunk> {
+        if index < self.chunks.len() {
+            Ok(self.chunks.remove(index))
+        } else {
+            Err(Box::new(std::io::Error::other(format!(
+                "no chunk at index {}",
+                index
+            ))))
+        }
+    }
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
         let pos = self
             .chunks
@@ -30,12 +73,44 @@ impl Png {
             self.chunks.remove(index);
             Ok(removed)
         } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "chunk type not found",
-            )))
+            Err(Box::new(std::io::Error::other("chunk type not found")))
+        }
+    }
+    /// Swaps `chunk` in for the first chunk of the same type, keeping its position.
+    pub fn replace_chunk(&mut self, chunk: Chunk) -> Result<Chunk> {
+        let pos = self
+            .chunks
+            .iter()
+            .position(|existing| existing.chunk_type() == chunk.chunk_type());
+
+        if let Some(index) = pos {
+            Ok(std::mem::replace(&mut self.chunks[index], chunk))
+        } else {
+            Err(Box::new(std::io::Error::other("chunk type not found")))
         }
     }
+    /// Sets the data of the first chunk of `chunk_type`, or with `append` adds `data` to the end
+    /// of it, keeping the chunk's position and recomputing its length and CRC. Returns the chunk
+    /// as it was.
+    pub fn update_chunk(
+        &mut self,
+        chunk_type: &ChunkType,
+        data: &[u8],
+        append: bool,
+    ) -> Result<Chunk> {
+        let mut new_data = match self
+            .chunks
+            .iter()
+            .find(|chunk| chunk.chunk_type() == chunk_type)
+        {
+            Some(existing) if append => existing.data().to_vec(),
+            Some(_) => vec![],
+            None => return Err(Box::new(std::io::Error::other("chunk type not found"))),
+        };
+        new_data.extend_from_slice(data);
+        self.replace_chunk(Chunk::new(chunk_type.clone(), new_data))
+    }
+    #[allow(dead_code)]
     pub fn header(&self) -> &[u8; 8] {
         &self.header
     }
@@ -54,36 +129,281 @@ impl Png {
             _ => panic!("duplicate chunk types found!"),
         }
     }
+    /// The chunk of a type that may appear only once, or an error if there are several.
+    pub(crate) fn unique_chunk(&self, chunk_type: &str) -> Result<Option<&Chunk>> {
+        let mut found = self
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes());
+        match (found.next(), found.next()) {
+            (first, None) => Ok(first),
+            _ => Err(Box::new(std::io::Error::other(format!(
+                "duplicate {} chunks",
+                chunk_type
+            )))),
+        }
+    }
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut data: Vec<u8> = vec![];
         data.extend(self.header.iter());
         for chunk in &self.chunks {
             data.extend(chunk.as_bytes());
         }
+        data.extend(&self.trailing);
         data
     }
+    /// Byte offset of each chunk's length field: where it was read from, or for a chunk added
+    /// since, just after the chunk before it.
+    pub fn chunk_offsets(&self) -> Vec<usize> {
+        let mut next = self.header.len();
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                let offset = chunk.offset().unwrap_or(next);
+                next = offset + chunk.length() as usize + 12;
+                offset
+            })
+            .collect()
+    }
+    /// Bytes found after the IEND chunk, written back out unchanged.
+    pub fn trailing_data(&self) -> &[u8] {
+        &self.trailing
+    }
+    pub fn set_trailing_data(&mut self, data: Vec<u8>) {
+        self.trailing = data;
+    }
+    pub fn image_header(&self) -> Result<ImageHeader> {
+        match self.chunks.first() {
+            Some(chunk) => ImageHeader::try_from(chunk),
+            None => Err(Box::new(std::io::Error::other("missing IHDR chunk"))),
+        }
+    }
+    /// Concatenated contents of every IDAT chunk.
+    pub fn image_data(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .filter(|chunk| &chunk.chunk_type().bytes() == b"IDAT")
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect()
+    }
+    /// Replaces every IDAT chunk with a single one holding `data`, placed where the first was.
+    pub fn set_image_data(&mut self, data: Vec<u8>) {
+        let is_idat = |chunk: &Chunk| &chunk.chunk_type().bytes() == b"IDAT";
+        let index = self
+            .chunks
+            .iter()
+            .position(is_idat)
+            .or_else(|| {
+                self.chunks
+                    .iter()
+                    .position(|chunk| &chunk.chunk_type().bytes() == b"IEND")
+            })
+            .unwrap_or(self.chunks.len());
+        self.chunks.retain(|chunk| !is_idat(chunk));
+        let idat = Chunk::new(ChunkType::from_str("IDAT").unwrap(), data);
+        self.chunks.insert(index, idat);
+    }
+    pub fn palette(&self) -> Result<Option<Palette>> {
+        self.unique_chunk("PLTE")?
+            .map(Palette::try_from)
+            .transpose()
+    }
+    pub fn transparency(&self) -> Result<Option<Transparency>> {
+        let Some(chunk) = self.unique_chunk("tRNS")? else {
+            return Ok(None);
+        };
+        let transparency = Transparency::parse(chunk, &self.image_header()?)?;
+        let palette = self.palette()?;
+        transparency.check_palette(palette.map(|palette| palette.entries().len()))?;
+        Ok(Some(transparency))
+    }
+}
+
+/// Which checks `Png::parse_with` makes. The default makes all of them.
+#[derive(Debug, Clone, Copy)]
+pub struct ParseOptions {
+    /// Report chunks whose stored CRC does not match.
+    pub verify_crc: bool,
+    /// Accept critical chunk types the specification does not define.
+    pub allow_unknown_critical: bool,
+    /// Accept data after IEND.
+    pub allow_trailing: bool,
+    /// Longest data length to trust; a longer one is taken as damage and ends the scan.
+    pub max_chunk_len: u32,
+}
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            verify_crc: true,
+            allow_unknown_critical: false,
+            allow_trailing: false,
+            max_chunk_len: Chunk::MAX_LENGTH,
+        }
+    }
+}
+
+/// A problem found by `Png::parse_with`, at the byte offset of the chunk's length field.
+/// `index` is the chunk's position among those kept, so skipped chunks do not shift it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Diagnostic {
+    offset: usize,
+    index: usize,
+    chunk_type: Option<String>,
+    message: String,
+}
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "offset {} chunk {}", self.offset, self.index)?;
+        if let Some(chunk_type) = &self.chunk_type {
+            write!(f, " ({})", chunk_type)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl Png {
+    /// Parses as much of `bytes` as can be read, reporting problems instead of failing on them.
+    /// Chunks with a bad CRC or unknown critical type are kept as they are. A chunk with invalid
+    /// type bytes is skipped, and a truncated or oversized one ends the scan. Only a missing
+    /// signature is an error.
+    pub fn parse_with(bytes: &[u8], options: ParseOptions) -> Result<(Png, Vec<Diagnostic>)> {
+        if bytes.get(..Png::STANDARD_HEADER.len()) != Some(&Png::STANDARD_HEADER[..]) {
+            return Err(Box::new(std::io::Error::other("invalid magic bytes")));
+        }
+        let mut png = Png::from_chunks(vec![]);
+        let mut diagnostics = vec![];
+        let mut offset = Png::STANDARD_HEADER.len();
+        let mut index = 0;
+        let mut ended = false;
+        while offset < bytes.len() {
+            let mut report = |chunk_type: Option<&[u8]>, message: String| {
+                diagnostics.push(Diagnostic {
+                    offset,
+                    index,
+                    chunk_type: chunk_type.map(|t| String::from_utf8_lossy(t).into_owned()),
+                    message,
+                })
+            };
+            let remaining = bytes.len() - offset;
+            if remaining < 12 {
+                report(
+                    None,
+                    format!("truncated chunk header, {} bytes left", remaining),
+                );
+                break;
+            }
+            let length = crate::chunk::to_u32(bytes[offset..offset + 4].try_into()?);
+            let type_bytes: [u8; 4] = bytes[offset + 4..offset + 8].try_into()?;
+            if length > options.max_chunk_len {
+                report(
+                    Some(&type_bytes),
+                    format!(
+                        "length {} exceeds the limit of {}, {} bytes left unread",
+                        length, options.max_chunk_len, remaining
+                    ),
+                );
+                break;
+            }
+            let end = offset + 12 + length as usize;
+            if end > bytes.len() {
+                report(
+                    Some(&type_bytes),
+                    format!(
+                        "truncated chunk, {} data bytes declared but only {} left",
+                        length,
+                        remaining - 12
+                    ),
+                );
+                break;
+            }
+            let Ok(chunk_type) = ChunkType::try_new(type_bytes) else {
+                report(None, format!("invalid type bytes {:02x?}", type_bytes));
+                offset = end;
+                continue;
+            };
+            let data = bytes[offset + 8..end - 4].to_vec();
+            let crc = crate::chunk::to_u32(bytes[end - 4..end].try_into()?);
+            let chunk = Chunk::with_crc(chunk_type, data, crc).at_offset(offset);
+            if options.verify_crc && !chunk.crc_is_valid() {
+                report(
+                    Some(&type_bytes),
+                    format!("CRC mismatch, stored {:08x}", crc),
+                );
+            }
+            if !chunk.chunk_type().is_valid() {
+                report(Some(&type_bytes), "reserved bit is set".to_string());
+            }
+            if !options.allow_unknown_critical
+                && chunk.chunk_type().is_critical()
+                && !chunk.chunk_type().is_standard()
+            {
+                report(Some(&type_bytes), "unknown critical chunk".to_string());
+            }
+            png.chunks.push(chunk);
+            offset = end;
+            index += 1;
+            if &type_bytes == b"IEND" {
+                ended = true;
+                break;
+            }
+        }
+        if ended {
+            png.trailing = bytes[offset..].to_vec();
+            if !options.allow_trailing && !png.trailing.is_empty() {
+                diagnostics.push(Diagnostic {
+                    offset,
+                    index,
+                    chunk_type: None,
+                    message: format!("{} bytes after IEND", png.trailing.len()),
+                });
+            }
+        } else {
+            diagnostics.push(Diagnostic {
+                offset: bytes.len(),
+                index,
+                chunk_type: None,
+                message: "missing IEND".to_string(),
+            });
+        }
+        Ok((png, diagnostics))
+    }
 }
+
 impl TryFrom<&[u8]> for Png {
     type Error = Error;
     fn try_from(value: &[u8]) -> Result<Self> {
         let mut png = Png {
             header: Png::STANDARD_HEADER,
             chunks: vec![],
+            trailing: vec![],
         };
 
-        let mut index = 0;
-        if &value[0..8] != Png::STANDARD_HEADER {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "invalid magic bytes",
-            )));
+        let mut offset = Png::STANDARD_HEADER.len();
+        if value.get(..offset) != Some(&Png::STANDARD_HEADER[..]) {
+            return Err(Box::new(std::io::Error::other("invalid magic bytes")));
         }
-        index += Png::STANDARD_HEADER.len();
 
-        while index < value.len() {
-            let chunk = Chunk::try_from(&value[index..])?;
-            index += (chunk.length() as usize) + 12; // 12 bytes for three 4-byte headers
+        while offset < value.len() {
+            let chunk = Chunk::try_from(&value[offset..]).map_err(|e| {
+                let diagnostic = Diagnostic {
+                    offset,
+                    index: png.chunks.len(),
+                    chunk_type: value
+                        .get(offset + 4..offset + 8)
+                        .map(|t| String::from_utf8_lossy(t).into_owned()),
+                    message: e.to_string(),
+                };
+                std::io::Error::other(diagnostic.to_string())
+            })?;
+            let chunk = chunk.at_offset(offset);
+            offset += (chunk.length() as usize) + 12; // 12 bytes for three 4-byte headers
+            let is_end = &chunk.chunk_type().bytes() == b"IEND";
             png.chunks.push(chunk);
+            if is_end {
+                png.trailing = value[offset..].to_vec();
+                break;
+            }
         }
         Ok(png)
     }
@@ -93,19 +413,185 @@ impl Display for Png {
         writeln!(f, "Png {{")?;
         writeln!(f, "  header: {} bytes", self.header.len())?;
         writeln!(f, "  chunks: {{")?;
-        for chunk in &self.chunks {
+        for (index, (chunk, offset)) in self.chunks.iter().zip(self.chunk_offsets()).enumerate() {
+            writeln!(f, "  chunk {} at offset {}:", index, offset)?;
             writeln!(f, "{}", chunk)?;
         }
+        if !self.trailing.is_empty() {
+            writeln!(f, "  trailing data: {} bytes", self.trailing.len())?;
+        }
         writeln!(f, "}}")?;
         Ok(())
     }
 }
 
+/// Chunks are listed with their index and the byte offset of their length field.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Png {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use base64::Engine;
+        use serde::ser::SerializeStruct;
+
+        struct Positioned<'a> {
+            index: usize,
+            offset: usize,
+            chunk: &'a Chunk,
+        }
+        impl serde::Serialize for Positioned<'_> {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                let mut state =
+                    serializer.serialize_struct("Chunk", Chunk::SERIALIZED_FIELDS + 2)?;
+                state.serialize_field("index", &self.index)?;
+                state.serialize_field("offset", &self.offset)?;
+                self.chunk.serialize_fields(&mut state)?;
+                state.end()
+            }
+        }
+
+        let chunks: Vec<Positioned> = self
+            .chunks
+            .iter()
+            .zip(self.chunk_offsets())
+            .enumerate()
+            .map(|(index, (chunk, offset))| Positioned {
+                index,
+                offset,
+                chunk,
+            })
+            .collect();
+        let mut state = serializer.serialize_struct("Png", 3)?;
+        state.serialize_field("signature_valid", &(self.header == Png::STANDARD_HEADER))?;
+        state.serialize_field("chunks", &chunks)?;
+        let trailing = base64::engine::general_purpose::STANDARD.encode(&self.trailing);
+        state.serialize_field("trailing_data", &trailing)?;
+        state.end()
+    }
+}
+
+/// Decoded PLTE chunk: one RGB triple per palette index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    entries: Vec<[u8; 3]>,
+}
+impl Palette {
+    pub fn new(entries: Vec<[u8; 3]>) -> Result<Palette> {
+        if entries.is_empty() || entries.len() > 256 {
+            return Err(Box::new(std::io::Error::other(format!(
+                "invalid palette size: {}",
+                entries.len()
+            ))));
+        }
+        Ok(Palette { entries })
+    }
+    pub fn entries(&self) -> &[[u8; 3]] {
+        &self.entries
+    }
+    pub fn to_chunk(&self) -> Chunk {
+        let data = self.entries.iter().flatten().copied().collect();
+        Chunk::new(ChunkType::from_str("PLTE").unwrap(), data)
+    }
+}
+impl TryFrom<&Chunk> for Palette {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if &chunk.chunk_type().bytes() != b"PLTE" || !chunk.data().len().is_multiple_of(3) {
+            return Err(Box::new(std::io::Error::other("invalid PLTE chunk")));
+        }
+        let entries = chunk
+            .data()
+            .chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect();
+        Palette::new(entries)
+    }
+}
+
+/// Decoded tRNS chunk, whose layout depends on the image color type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    Gray(u16),
+    Rgb([u16; 3]),
+    /// Alpha for each palette index; indices past the end are fully opaque.
+    Indexed(Vec<u8>),
+}
+impl Transparency {
+    /// Decodes tRNS for the image's color type; samples must fit its bit depth.
+    pub fn parse(chunk: &Chunk, header: &ImageHeader) -> Result<Transparency> {
+        let data = chunk.data();
+        let sample = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        let transparency = match (header.color_type(), data.len()) {
+            (ColorType::Grayscale, 2) => Transparency::Gray(sample(0)),
+            (ColorType::Rgb, 6) => Transparency::Rgb([sample(0), sample(2), sample(4)]),
+            (ColorType::Indexed, 0..=256) => Transparency::Indexed(data.to_vec()),
+            (color_type, _) => {
+                return Err(Box::new(std::io::Error::other(format!(
+                    "invalid tRNS chunk for {} image",
+                    color_type
+                ))))
+            }
+        };
+        let samples = match &transparency {
+            Transparency::Gray(gray) => vec![*gray],
+            Transparency::Rgb(rgb) => rgb.to_vec(),
+            Transparency::Indexed(_) => vec![],
+        };
+        if samples.iter().any(|&s| s > header.max_sample()) {
+            return Err(Box::new(std::io::Error::other(format!(
+                "tRNS sample exceeds {}-bit depth",
+                header.bit_depth()
+            ))));
+        }
+        Ok(transparency)
+    }
+    /// Checks that an indexed tRNS has no more entries than the palette, when there is one.
+    pub fn check_palette(&self, palette_len: Option<usize>) -> Result<()> {
+        match (self, palette_len) {
+            (Transparency::Indexed(alphas), Some(entries)) if alphas.len() > entries => {
+                Err(Box::new(std::io::Error::other(format!(
+                    "tRNS has {} entries but PLTE has {}",
+                    alphas.len(),
+                    entries
+                ))))
+            }
+            _ => Ok(()),
+        }
+    }
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            Transparency::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Transparency::Rgb(rgb) => rgb.iter().flat_map(|s| s.to_be_bytes()).collect(),
+            Transparency::Indexed(alphas) => alphas.clone(),
+        };
+        Chunk::new(ChunkType::from_str("tRNS").unwrap(), data)
+    }
+}
+impl Display for Transparency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transparency::Gray(gray) => write!(f, "transparent gray {}", gray),
+            Transparency::Rgb([r, g, b]) => write!(f, "transparent color ({}, {}, {})", r, g, b),
+            Transparency::Indexed(alphas) => write!(
+                f,
+                "alpha for {} palette entries, {} fully transparent",
+                alphas.len(),
+                alphas.iter().filter(|&&a| a == 0).count()
+            ),
+        }
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use std::convert::TryFrom;
 
+    #[allow(clippy::vec_init_then_push)]
     fn testing_chunks() -> Vec<Chunk> {
         let mut chunks = Vec::new();
 
@@ -121,6 +607,12 @@ mod tests {
         Png::from_chunks(chunks)
     }
 
+    /// A 4x4 header with the given bit depth and color type code.
+    pub(crate) fn image_header(bit_depth: u8, color_type: u8) -> ImageHeader {
+        let data = vec![0, 0, 0, 4, 0, 0, 0, 4, bit_depth, color_type, 0, 0, 0];
+        ImageHeader::try_from(&Chunk::new(ChunkType::from_str("IHDR").unwrap(), data)).unwrap()
+    }
+
     fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
         use std::str::FromStr;
 
@@ -130,6 +622,19 @@ mod tests {
         Ok(Chunk::new(chunk_type, data))
     }
 
+    /// A chunk of `chunk_type` holding `data`.
+    pub(crate) fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    /// The type of every chunk in `png`, in order.
+    pub(crate) fn types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
     #[test]
     fn test_from_chunks() {
         let chunks = testing_chunks();
@@ -229,6 +734,139 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_insert_and_remove_at() {
+        let mut png = testing_png();
+        png.insert_chunk(1, chunk_from_strings("TeSt", "Message").unwrap());
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+        let removed = png.remove_chunk_at(1).unwrap();
+        assert_eq!(&removed.data_as_string().unwrap(), "Message");
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.remove_chunk_at(3).is_err());
+    }
+
+    #[test]
+    fn test_insert_before_end() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_before_end(chunk_from_strings("TeSt", "Message").unwrap());
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .rev()
+            .take(2)
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IEND", "TeSt"]);
+    }
+
+    #[test]
+    fn test_insert_before_first() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_before_first(
+            &["PLTE", "IDAT"],
+            chunk_from_strings("TeSt", "Message").unwrap(),
+        );
+        let idat = png
+            .chunks()
+            .iter()
+            .position(|chunk| &chunk.chunk_type().bytes() == b"IDAT")
+            .unwrap();
+        assert_eq!(&png.chunks()[idat - 1].chunk_type().to_string(), "TeSt");
+    }
+
+    #[test]
+    fn test_set_chunk_before() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "again").unwrap());
+        png.set_chunk_before(&["LASt"], chunk_from_strings("miDl", "only").unwrap());
+        assert_eq!(types(&png), ["FrSt", "miDl", "LASt"]);
+        assert_eq!(png.chunks()[1].data(), b"only");
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let replaced = png
+            .replace_chunk(chunk_from_strings("miDl", "I replaced it").unwrap())
+            .unwrap();
+        assert_eq!(&replaced.data_as_string().unwrap(), "I am another chunk");
+        let chunk = &png.chunks()[1];
+        assert_eq!(&chunk.data_as_string().unwrap(), "I replaced it");
+        assert!(png
+            .replace_chunk(chunk_from_strings("NoNe", "missing").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_update_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "a second one").unwrap());
+        let chunk_type = ChunkType::from_str("miDl").unwrap();
+        let old = png.update_chunk(&chunk_type, b"new", false).unwrap();
+        assert_eq!(&old.data_as_string().unwrap(), "I am another chunk");
+        png.update_chunk(&chunk_type, b" and more", true).unwrap();
+        let updated = &png.chunks()[1];
+        assert_eq!(&updated.data_as_string().unwrap(), "new and more");
+        assert_eq!(updated.length(), 12);
+        assert!(updated.crc_is_valid());
+        assert_eq!(&png.chunks()[3].data_as_string().unwrap(), "a second one");
+        let missing = ChunkType::from_str("NoNe").unwrap();
+        assert!(png.update_chunk(&missing, b"x", true).is_err());
+    }
+
+    #[test]
+    fn test_set_image_data() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let data = png.image_data();
+        png.set_image_data(vec![1, 2, 3]);
+        assert_eq!(png.image_data(), vec![1, 2, 3]);
+        png.set_image_data(data);
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_palette_from_chunk() {
+        let chunk = Chunk::new(ChunkType::from_str("PLTE").unwrap(), vec![1, 2, 3, 4, 5, 6]);
+        let palette = Palette::try_from(&chunk).unwrap();
+        assert_eq!(palette.entries(), &[[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(palette.to_chunk().as_bytes(), chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_invalid_palette() {
+        let chunk = Chunk::new(ChunkType::from_str("PLTE").unwrap(), vec![1, 2, 3, 4]);
+        assert!(Palette::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_transparency_per_color_type() {
+        let chunk = Chunk::new(ChunkType::from_str("tRNS").unwrap(), vec![0, 1, 0, 2, 0, 3]);
+        assert_eq!(
+            Transparency::parse(&chunk, &image_header(8, 2)).unwrap(),
+            Transparency::Rgb([1, 2, 3])
+        );
+        assert_eq!(
+            Transparency::parse(&chunk, &image_header(8, 3)).unwrap(),
+            Transparency::Indexed(vec![0, 1, 0, 2, 0, 3])
+        );
+        assert!(Transparency::parse(&chunk, &image_header(8, 0)).is_err());
+        assert!(Transparency::parse(&chunk, &image_header(8, 6)).is_err());
+        assert!(Transparency::parse(&chunk, &image_header(1, 0)).is_err());
+    }
+
+    #[test]
+    fn test_transparency_sample_depth() {
+        let gray = Transparency::Gray(3).to_chunk();
+        assert!(Transparency::parse(&gray, &image_header(2, 0)).is_ok());
+        assert!(Transparency::parse(&gray, &image_header(1, 0)).is_err());
+        assert_eq!(
+            Transparency::parse(&gray, &image_header(2, 0))
+                .unwrap()
+                .to_string(),
+            "transparent gray 3"
+        );
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -243,6 +881,22 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_trailing_data_round_trip() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend(b"after the end");
+        let mut png = Png::try_from(&bytes[..]).unwrap();
+        assert_eq!(
+            png.chunks().len(),
+            Png::try_from(&PNG_FILE[..]).unwrap().chunks().len()
+        );
+        assert_eq!(png.trailing_data(), b"after the end");
+        assert_eq!(png.as_bytes(), bytes);
+
+        png.set_trailing_data(vec![]);
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -261,8 +915,149 @@ mod tests {
         let _png_string = format!("{}", png);
     }
 
+    #[test]
+    fn test_errors_name_the_position() {
+        let mut bytes = PNG_FILE.to_vec();
+        let gama = offset_of(b"gAMA");
+        bytes[gama + 8] ^= 1;
+        let error = Png::try_from(&bytes[..]).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!("offset {} chunk 2 (gAMA): invalid checksum", gama)
+        );
+
+        let error = Png::try_from(&PNG_FILE[..PNG_FILE.len() - 6]).unwrap_err();
+        assert!(error.to_string().contains("truncated chunk header"));
+        assert!(Png::try_from(&PNG_FILE[..4]).is_err());
+        assert!(Png::try_from(&[][..]).is_err());
+    }
+
+    #[test]
+    fn test_chunk_offsets() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let offsets = png.chunk_offsets();
+        assert_eq!(offsets[0], 8);
+        assert_eq!(offsets[2], offset_of(b"gAMA"));
+        assert_eq!(png.chunks()[2].offset(), Some(offsets[2]));
+
+        let added = chunk_from_strings("teSt", "added").unwrap();
+        let end = *offsets.last().unwrap();
+        png.insert_before_end(added);
+        assert_eq!(png.chunks()[offsets.len() - 1].offset(), None);
+        assert_eq!(png.chunk_offsets()[offsets.len() - 1], end);
+    }
+
+    /// Default options, less the check that PNG_FILE's RuSt chunk would fail.
+    fn known_critical() -> ParseOptions {
+        ParseOptions {
+            allow_unknown_critical: true,
+            ..ParseOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_with_intact_file() {
+        let (png, diagnostics) = Png::parse_with(&PNG_FILE, known_critical()).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(png.as_bytes(), PNG_FILE);
+        assert!(Png::parse_with(b"GIF89a", ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_with_keeps_bad_crc() {
+        let mut bytes = PNG_FILE.to_vec();
+        let gama = offset_of(b"gAMA");
+        bytes[gama + 8] ^= 1;
+        let (png, diagnostics) = Png::parse_with(&bytes, known_critical()).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].offset, gama);
+        assert_eq!(diagnostics[0].index, 2);
+        assert!(diagnostics[0].to_string().contains("(gAMA): CRC mismatch"));
+        assert_eq!(png.as_bytes(), bytes);
+
+        let options = ParseOptions {
+            verify_crc: false,
+            ..known_critical()
+        };
+        assert!(Png::parse_with(&bytes, options).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_skips_invalid_type() {
+        let mut bytes = PNG_FILE.to_vec();
+        let gama = offset_of(b"gAMA");
+        bytes[gama + 4] = 0;
+        let (png, diagnostics) = Png::parse_with(&bytes, known_critical()).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("invalid type bytes"));
+        assert!(png.chunk_by_type("gAMA").is_none());
+        assert_eq!(
+            png.chunks().len(),
+            Png::try_from(&PNG_FILE[..]).unwrap().chunks().len() - 1
+        );
+    }
+
+    #[test]
+    fn test_parse_with_indexes_kept_chunks() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[offset_of(b"gAMA") + 4] = 0;
+        let phys = offset_of(b"pHYs");
+        bytes[phys + 8] ^= 1;
+        let (png, diagnostics) = Png::parse_with(&bytes, known_critical()).unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].offset, phys);
+        assert_eq!(diagnostics[1].index, 2);
+        assert_eq!(
+            png.chunks()[diagnostics[1].index].chunk_type().to_string(),
+            "pHYs"
+        );
+    }
+
+    #[test]
+    fn test_parse_with_truncated_and_trailing() {
+        let (png, diagnostics) =
+            Png::parse_with(&PNG_FILE[..PNG_FILE.len() - 6], known_critical()).unwrap();
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert!(messages[0].starts_with("truncated chunk header"));
+        assert_eq!(messages[1], "missing IEND");
+        assert_eq!(diagnostics[0].offset, PNG_FILE.len() - 12);
+        assert!(png.chunk_by_type("IEND").is_none());
+
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend(b"tail");
+        let (_, diagnostics) = Png::parse_with(&bytes, known_critical()).unwrap();
+        assert_eq!(diagnostics[0].message, "4 bytes after IEND");
+        assert_eq!(diagnostics[0].offset, PNG_FILE.len());
+        let options = ParseOptions {
+            allow_trailing: true,
+            ..known_critical()
+        };
+        assert!(Png::parse_with(&bytes, options).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_limits() {
+        let (_, diagnostics) = Png::parse_with(&PNG_FILE, ParseOptions::default()).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].offset, offset_of(b"RuSt"));
+        assert_eq!(diagnostics[0].message, "unknown critical chunk");
+
+        let options = ParseOptions {
+            max_chunk_len: 1024,
+            ..known_critical()
+        };
+        let (png, diagnostics) = Png::parse_with(&PNG_FILE, options).unwrap();
+        assert!(diagnostics[0].message.contains("exceeds the limit of 1024"));
+        assert!(png.chunk_by_type("IDAT").is_none());
+    }
+
+    /// Offset of the first chunk of `chunk_type` in PNG_FILE.
+    pub(crate) fn offset_of(chunk_type: &[u8; 4]) -> usize {
+        PNG_FILE.windows(4).position(|w| w == chunk_type).unwrap() - 4
+    }
+
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
-    const PNG_FILE: [u8; 4803] = [
+    pub(crate) const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
         6, 0, 0, 0, 30, 63, 136, 177, 0, 0, 0, 1, 115, 82, 71, 66, 0, 174, 206, 28, 233, 0, 0, 0,
         4, 103, 65, 77, 65, 0, 0, 177, 143, 11, 252, 97, 5, 0, 0, 0, 9, 112, 72, 89, 115, 0, 0, 14,
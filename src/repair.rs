@@ -0,0 +1,178 @@
+use crate::chunk::to_u32;
+use crate::png::{Chunk, ChunkType, Png};
+use crate::Result;
+use std::str::FromStr;
+
+/// Whether `bytes` at `offset` start a chunk worth trusting: a valid type, a length that fits in
+/// the file, and either a matching CRC or a standard type.
+fn plausible_chunk(bytes: &[u8], offset: usize) -> bool {
+    let Some(header) = bytes.get(offset..offset + 8) else {
+        return false;
+    };
+    let length = to_u32(header[..4].try_into().unwrap()) as usize;
+    let Ok(chunk_type) = ChunkType::try_new(header[4..].try_into().unwrap()) else {
+        return false;
+    };
+    if !chunk_type.is_reserved_bit_valid() || length + 12 > bytes.len() - offset {
+        return false;
+    }
+    chunk_type.is_standard() || Chunk::try_from(&bytes[offset..]).is_ok()
+}
+
+/// Rebuilds as much of a damaged file as can be trusted. Chunks with a bad CRC are dropped, or
+/// kept with a recomputed CRC if `fix_crc` is set. After damage the scan resyncs at the next
+/// plausible chunk header, and a truncated file gets a new IEND. Returns the file and a line
+/// per fix, each naming the byte offset it applies to.
+pub fn repair(bytes: &[u8], fix_crc: bool) -> Result<(Png, Vec<String>)> {
+    let mut report = vec![];
+    let mut offset = Png::STANDARD_HEADER.len();
+    if bytes.get(..offset) != Some(&Png::STANDARD_HEADER[..]) {
+        let ihdr = bytes
+            .windows(4)
+            .position(|window| window == b"IHDR")
+            .filter(|&position| position >= 4)
+            .ok_or("no PNG signature or IHDR chunk found")?;
+        offset = ihdr - 4;
+        report.push(format!(
+            "replaced a damaged signature, chunks start at offset {}",
+            offset
+        ));
+    }
+
+    let mut chunks: Vec<Chunk> = vec![];
+    let mut trailing = vec![];
+    let mut ended = false;
+    while offset < bytes.len() {
+        let remaining = bytes.len() - offset;
+        if !plausible_chunk(bytes, offset) {
+            let resync = (offset + 1..bytes.len()).find(|&next| plausible_chunk(bytes, next));
+            match resync {
+                Some(next) => {
+                    report.push(format!(
+                        "skipped {} damaged bytes at offset {}",
+                        next - offset,
+                        offset
+                    ));
+                    offset = next;
+                    continue;
+                }
+                None => {
+                    report.push(format!(
+                        "dropped {} unrecoverable bytes at offset {}",
+                        remaining, offset
+                    ));
+                    break;
+                }
+            }
+        }
+
+        let length = to_u32(bytes[offset..offset + 4].try_into()?) as usize;
+        let chunk_type = ChunkType::try_new(bytes[offset + 4..offset + 8].try_into()?)?;
+        let chunk = match Chunk::try_from(&bytes[offset..]) {
+            Ok(chunk) => Some(chunk),
+            Err(_) if fix_crc => {
+                report.push(format!(
+                    "recomputed the CRC of {} at offset {}",
+                    chunk_type, offset
+                ));
+                let data = bytes[offset + 8..offset + 8 + length].to_vec();
+                Some(Chunk::new(chunk_type.clone(), data))
+            }
+            Err(_) => {
+                report.push(format!(
+                    "dropped {} at offset {}: CRC mismatch",
+                    chunk_type, offset
+                ));
+                None
+            }
+        };
+        offset += length + 12;
+        if let Some(chunk) = chunk {
+            chunks.push(chunk);
+        }
+        if &chunk_type.bytes() == b"IEND" {
+            trailing = bytes[offset..].to_vec();
+            ended = true;
+            break;
+        }
+    }
+
+    if chunks
+        .first()
+        .is_none_or(|chunk| &chunk.chunk_type().bytes() != b"IHDR")
+    {
+        return Err("cannot repair a file whose first chunk is not IHDR".into());
+    }
+    if !ended {
+        report.push("added a missing IEND".to_string());
+        chunks.push(Chunk::new(ChunkType::from_str("IEND")?, vec![]));
+    }
+    let mut png = Png::from_chunks(chunks);
+    png.set_trailing_data(trailing);
+    Ok((png, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{offset_of, PNG_FILE};
+
+    #[test]
+    fn test_intact_file_is_unchanged() {
+        let (png, report) = repair(&PNG_FILE, false).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(png.as_bytes(), PNG_FILE);
+    }
+
+    #[test]
+    fn test_bad_crc_dropped_or_fixed() {
+        let mut bytes = PNG_FILE.to_vec();
+        let gama = offset_of(b"gAMA");
+        bytes[gama + 8] ^= 1;
+        let (png, report) = repair(&bytes, false).unwrap();
+        assert_eq!(
+            report,
+            [format!("dropped gAMA at offset {}: CRC mismatch", gama)]
+        );
+        assert!(png.chunk_by_type("gAMA").is_none());
+
+        let (png, report) = repair(&bytes, true).unwrap();
+        assert!(report[0].starts_with("recomputed the CRC of"));
+        assert_eq!(png.chunk_by_type("gAMA").unwrap().data()[0], 1);
+    }
+
+    #[test]
+    fn test_resyncs_after_garbage() {
+        let mut bytes = PNG_FILE.to_vec();
+        let gama = offset_of(b"gAMA");
+        bytes.splice(gama..gama, [0xff; 5]);
+        let (png, report) = repair(&bytes, false).unwrap();
+        assert_eq!(
+            report,
+            [format!("skipped 5 damaged bytes at offset {}", gama)]
+        );
+        assert_eq!(png.as_bytes(), PNG_FILE);
+    }
+
+    #[test]
+    fn test_truncated_file_gets_iend() {
+        let bytes = &PNG_FILE[..PNG_FILE.len() - 6];
+        let (png, report) = repair(bytes, false).unwrap();
+        assert!(report[0].starts_with("dropped 6 unrecoverable bytes"));
+        assert_eq!(report[1], "added a missing IEND");
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+    }
+
+    #[test]
+    fn test_damaged_signature() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[1] = b'Q';
+        let (png, report) = repair(&bytes, false).unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(png.as_bytes(), PNG_FILE);
+        assert!(repair(b"not a png", false).is_err());
+    }
+}
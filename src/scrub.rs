@@ -0,0 +1,241 @@
+use crate::color::IccProfile;
+use crate::png::{Chunk, ChunkType, Png};
+use crate::Result;
+use std::str::FromStr;
+
+/// Standard ancillary chunks that only carry metadata.
+pub const METADATA_TYPES: [&str; 8] = [
+    "tEXt", "zTXt", "iTXt", "eXIf", "tIME", "dSIG", "gIFx", "gIFt",
+];
+
+/// Profile name written in place of whatever the iCCP chunk was called.
+const ICC_PROFILE_NAME: &str = "ICC profile";
+
+/// Which chunks a scrub removes. `keep` always wins; `drop` adds to the defaults.
+#[derive(Debug, Default)]
+pub struct ScrubPolicy {
+    keep: Vec<ChunkType>,
+    drop: Vec<ChunkType>,
+}
+impl ScrubPolicy {
+    pub fn new(keep: Vec<ChunkType>, drop: Vec<ChunkType>) -> Result<ScrubPolicy> {
+        if let Some(chunk_type) = drop.iter().find(|t| t.is_critical() && t.is_standard()) {
+            return Err(format!(
+                "{} is needed to render the image and cannot be dropped",
+                chunk_type
+            )
+            .into());
+        }
+        if let Some(chunk_type) = drop.iter().find(|t| keep.contains(t)) {
+            return Err(format!("{} is in both the keep and drop lists", chunk_type).into());
+        }
+        Ok(ScrubPolicy { keep, drop })
+    }
+    /// Why a chunk of this type should go, or `None` to keep it.
+    fn reason(&self, chunk_type: &ChunkType) -> Option<&'static str> {
+        if self.keep.contains(chunk_type) {
+            None
+        } else if self.drop.contains(chunk_type) {
+            Some("requested")
+        } else if METADATA_TYPES
+            .iter()
+            .any(|t| t.as_bytes() == chunk_type.bytes())
+        {
+            Some("metadata")
+        } else if !chunk_type.is_public() {
+            Some("private")
+        } else if !chunk_type.is_standard() {
+            Some("non-standard")
+        } else {
+            None
+        }
+    }
+}
+
+/// Removes identifying chunks and trailing data from `png` and blanks the names and text tags
+/// of its ICC profiles, returning a line per change.
+pub fn scrub(png: &mut Png, policy: &ScrubPolicy) -> Result<Vec<String>> {
+    let mut removed: Vec<(ChunkType, &str)> = vec![];
+    for chunk in png.chunks() {
+        let chunk_type = chunk.chunk_type();
+        if let Some(reason) = policy.reason(chunk_type) {
+            if !removed.iter().any(|(t, _)| t == chunk_type) {
+                removed.push((chunk_type.clone(), reason));
+            }
+        }
+    }
+
+    let mut report = vec![];
+    for (chunk_type, reason) in removed {
+        let name = chunk_type.to_string();
+        let mut count = 0;
+        while png.remove_chunk(&name).is_ok() {
+            count += 1;
+        }
+        let plural = if count == 1 { "" } else { "s" };
+        report.push(format!(
+            "removed {} {} chunk{} ({})",
+            count, name, plural, reason
+        ));
+    }
+
+    let iccp = ChunkType::from_str("iCCP")?;
+    if !policy.keep.contains(&iccp) {
+        for index in 0..png.chunks().len() {
+            let chunk = &png.chunks()[index];
+            if chunk.chunk_type() != &iccp {
+                continue;
+            }
+            let Some(end) = chunk.data().iter().position(|&b| b == 0) else {
+                continue;
+            };
+            let name = String::from_utf8_lossy(&chunk.data()[..end]).into_owned();
+            let mut renamed = ICC_PROFILE_NAME.as_bytes().to_vec();
+            renamed.extend(&chunk.data()[end..]);
+            if name != ICC_PROFILE_NAME {
+                report.push(format!(
+                    "renamed iCCP profile {:?} to {:?}",
+                    name, ICC_PROFILE_NAME
+                ));
+            }
+            let replacement = match IccProfile::try_from(chunk) {
+                Ok(profile) => {
+                    let mut profile =
+                        IccProfile::new(ICC_PROFILE_NAME, profile.profile().to_vec())?;
+                    let tags = profile.blank_text_tags(ICC_PROFILE_NAME);
+                    if tags.is_empty() {
+                        Chunk::new(iccp.clone(), renamed)
+                    } else {
+                        report.push(format!(
+                            "blanked the ICC profile's {} text",
+                            tags.join(", ")
+                        ));
+                        profile.to_chunk()?
+                    }
+                }
+                Err(e) => {
+                    report.push(format!(
+                        "kept the text of an ICC profile that could not be read: {}",
+                        e
+                    ));
+                    Chunk::new(iccp.clone(), renamed)
+                }
+            };
+            if replacement.as_bytes() != png.chunks()[index].as_bytes() {
+                png.remove_chunk_at(index)?;
+                png.insert_chunk(index, replacement);
+            }
+        }
+    }
+
+    if !png.trailing_data().is_empty() {
+        report.push(format!(
+            "removed {} bytes after IEND",
+            png.trailing_data().len()
+        ));
+        png.set_trailing_data(vec![]);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::tests::profile_with_text;
+    use crate::png::tests::{chunk, types, PNG_FILE};
+
+    fn tagged_png() -> Png {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_before_end(chunk("tEXt", b"Author\0me"));
+        png.insert_before_end(chunk("tEXt", b"Comment\0hi"));
+        png.insert_before_end(chunk("tIME", &[7, 232, 5, 1, 12, 0, 0]));
+        png.insert_before_end(chunk("gAMA", &45455u32.to_be_bytes()));
+        let icc = IccProfile::new("Bob's monitor", profile_with_text()).unwrap();
+        png.insert_before_first(&["IDAT"], icc.to_chunk().unwrap());
+        png.set_trailing_data(b"secret".to_vec());
+        png
+    }
+
+    #[test]
+    fn test_default_scrub() {
+        let mut png = tagged_png();
+        let report = scrub(&mut png, &ScrubPolicy::default()).unwrap();
+        assert_eq!(
+            report,
+            [
+                "removed 1 RuSt chunk (private)",
+                "removed 2 tEXt chunks (metadata)",
+                "removed 1 tIME chunk (metadata)",
+                "renamed iCCP profile \"Bob's monitor\" to \"ICC profile\"",
+                "blanked the ICC profile's desc, cprt, dmdd text",
+                "removed 6 bytes after IEND",
+            ]
+        );
+        let remaining = types(&png);
+        for gone in ["RuSt", "tEXt", "tIME"] {
+            assert!(!remaining.iter().any(|t| t == gone), "{}", gone);
+        }
+        assert!(remaining.iter().any(|t| t == "gAMA"));
+        assert_eq!(
+            &png.chunk_by_type("iCCP").unwrap().data()[..12],
+            b"ICC profile\0"
+        );
+        assert!(png.trailing_data().is_empty());
+        assert!(scrub(&mut png, &ScrubPolicy::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_every_icc_profile_is_scrubbed() {
+        let mut png = tagged_png();
+        let second = IccProfile::new("Alice's laptop", profile_with_text()).unwrap();
+        png.insert_before_first(&["IDAT"], second.to_chunk().unwrap());
+        png.insert_before_first(&["IDAT"], chunk("iCCP", b"Carol\0\0not zlib"));
+        let report = scrub(&mut png, &ScrubPolicy::default()).unwrap();
+        assert_eq!(
+            report.iter().filter(|l| l.starts_with("renamed")).count(),
+            3
+        );
+        assert_eq!(
+            report.iter().filter(|l| l.starts_with("blanked")).count(),
+            2
+        );
+        assert!(report.iter().any(|l| l.starts_with("kept the text")));
+        for chunk in png.chunks() {
+            if &chunk.chunk_type().bytes() == b"iCCP" {
+                assert!(chunk.data().starts_with(b"ICC profile\0"));
+                if let Ok(icc) = IccProfile::try_from(chunk) {
+                    assert!(!icc.profile().windows(3).any(|w| w == b"Bob"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_keep_and_drop_lists() {
+        let mut png = tagged_png();
+        let keep = vec![
+            ChunkType::from_str("tEXt").unwrap(),
+            ChunkType::from_str("iCCP").unwrap(),
+        ];
+        let drop = vec![ChunkType::from_str("gAMA").unwrap()];
+        let policy = ScrubPolicy::new(keep, drop).unwrap();
+        let report = scrub(&mut png, &policy).unwrap();
+        let gama = report.iter().find(|line| line.contains("gAMA")).unwrap();
+        assert!(gama.ends_with("(requested)"));
+        let remaining = types(&png);
+        assert_eq!(remaining.iter().filter(|t| *t == "tEXt").count(), 2);
+        assert!(!remaining.iter().any(|t| t == "gAMA"));
+        assert_eq!(
+            &png.chunk_by_type("iCCP").unwrap().data()[..14],
+            b"Bob's monitor\0"
+        );
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        let idat = || vec![ChunkType::from_str("IDAT").unwrap()];
+        assert!(ScrubPolicy::new(vec![], idat()).is_err());
+        let text = || vec![ChunkType::from_str("tEXt").unwrap()];
+        assert!(ScrubPolicy::new(text(), text()).is_err());
+    }
+}
@@ -0,0 +1,610 @@
+use crate::ancillary::{Background, Histogram};
+use crate::image::{ColorType, ImageData};
+use crate::png::{Chunk, ChunkType, Palette, Png, Transparency};
+use crate::Result;
+
+/// Bytes taken by the chunk type tag and length that prefix every embedded message.
+pub const FRAME_OVERHEAD: usize = 6;
+
+/// Bytes a message appended by `embed_in_trailer` adds beyond its own length.
+pub const TRAILER_OVERHEAD: usize = 12;
+
+/// Longest message a frame of `capacity` bytes can carry.
+fn message_capacity(capacity: usize) -> usize {
+    capacity
+        .saturating_sub(FRAME_OVERHEAD)
+        .min(u16::MAX as usize)
+}
+
+/// Tags `message` with its chunk type and length, zero-padded to exactly `capacity` bytes.
+fn frame(chunk_type: &ChunkType, message: &[u8], capacity: usize) -> Result<Vec<u8>> {
+    if capacity < FRAME_OVERHEAD {
+        return Err(Box::new(std::io::Error::other(format!(
+            "image has room for only {} bytes, less than the {}-byte message header",
+            capacity, FRAME_OVERHEAD
+        ))));
+    }
+    let available = message_capacity(capacity);
+    if message.len() > available {
+        return Err(Box::new(std::io::Error::other(format!(
+            "message is {} bytes but only {} fit",
+            message.len(),
+            available
+        ))));
+    }
+    let mut payload = chunk_type.bytes().to_vec();
+    payload.extend((message.len() as u16).to_be_bytes());
+    payload.extend(message);
+    payload.resize(capacity, 0);
+    Ok(payload)
+}
+
+fn unframe(chunk_type: &ChunkType, payload: &[u8]) -> Result<Vec<u8>> {
+    let not_found = || -> crate::Error {
+        Box::new(std::io::Error::other(format!(
+            "no message with chunk type {} found",
+            chunk_type
+        )))
+    };
+    if payload.len() < FRAME_OVERHEAD || payload[..4] != chunk_type.bytes() {
+        return Err(not_found());
+    }
+    let length = u16::from_be_bytes([payload[4], payload[5]]) as usize;
+    payload
+        .get(FRAME_OVERHEAD..FRAME_OVERHEAD + length)
+        .map(|message| message.to_vec())
+        .ok_or_else(not_found)
+}
+
+/// Divides the big-endian integer `n` in place by `divisor`, returning the remainder.
+fn div_rem(n: &mut [u8], divisor: u32) -> u32 {
+    let mut rem = 0;
+    for byte in n.iter_mut() {
+        let current = (rem << 8) | *byte as u32;
+        *byte = (current / divisor) as u8;
+        rem = current % divisor;
+    }
+    rem
+}
+
+/// Sets the big-endian integer `n` to `n * factor + addend`, returning the carry out of the top.
+fn mul_add(n: &mut [u8], factor: u32, addend: u32) -> u32 {
+    let mut carry = addend;
+    for byte in n.iter_mut().rev() {
+        let current = *byte as u32 * factor + carry;
+        *byte = current as u8;
+        carry = current >> 8;
+    }
+    carry
+}
+
+/// Whole bytes representable by a mixed-radix number with the given digit radices.
+fn radix_capacity(radices: &[u32]) -> usize {
+    let mut product = vec![1u8];
+    for &radix in radices {
+        let mut carry = mul_add(&mut product, radix, 0);
+        while carry > 0 {
+            product.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    let bits = product.len() * 8 - product[0].leading_zeros() as usize;
+    (bits - 1) / 8
+}
+
+/// Splits the big-endian integer `bytes` into mixed-radix digits, least significant first.
+fn to_mixed_radix(bytes: &[u8], radices: &[u32]) -> Vec<u32> {
+    let mut n = bytes.to_vec();
+    radices
+        .iter()
+        .map(|&radix| div_rem(&mut n, radix))
+        .collect()
+}
+
+/// Inverse of `to_mixed_radix`, or `None` if the value does not fit in `len` bytes.
+fn from_mixed_radix(digits: &[u32], radices: &[u32], len: usize) -> Option<Vec<u8>> {
+    let mut n = vec![0; len];
+    for (&digit, &radix) in digits.iter().zip(radices).rev() {
+        if mul_add(&mut n, radix, digit) != 0 {
+            return None;
+        }
+    }
+    Some(n)
+}
+
+/// Palette entries as RGBA, taking alpha from tRNS, together with the parsed PLTE.
+fn palette_colors(png: &Png) -> Result<(Palette, Vec<[u8; 4]>)> {
+    if png.image_header()?.color_type() != ColorType::Indexed {
+        return Err(Box::new(std::io::Error::other(
+            "palette method requires an indexed-color image",
+        )));
+    }
+    let palette = png
+        .palette()?
+        .ok_or_else(|| Box::new(std::io::Error::other("missing PLTE chunk")))?;
+    let alphas = match png.transparency()? {
+        Some(Transparency::Indexed(alphas)) => alphas,
+        _ => vec![],
+    };
+    let colors = palette
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(i, &[r, g, b])| [r, g, b, *alphas.get(i).unwrap_or(&255)])
+        .collect();
+    Ok((palette, colors))
+}
+
+/// Palette indices ordered by color, which is the reference order permutations are measured against.
+fn canonical_order(colors: &[[u8; 4]]) -> Result<Vec<usize>> {
+    let mut order: Vec<usize> = (0..colors.len()).collect();
+    order.sort_by_key(|&i| colors[i]);
+    if order.windows(2).any(|w| colors[w[0]] == colors[w[1]]) {
+        return Err(Box::new(std::io::Error::other(
+            "palette has duplicate entries",
+        )));
+    }
+    Ok(order)
+}
+
+fn permutation_radices(n: usize) -> Vec<u32> {
+    (1..=n as u32).rev().collect()
+}
+
+/// Longest message `embed_in_palette` can hide in this image.
+pub fn palette_capacity(png: &Png) -> Result<usize> {
+    let (_, colors) = palette_colors(png)?;
+    canonical_order(&colors)?;
+    Ok(message_capacity(radix_capacity(&permutation_radices(
+        colors.len(),
+    ))))
+}
+
+/// Hides `message` in the order of the PLTE entries.
+///
+/// tRNS, bKGD and hIST are reordered and the IDAT indices remapped to match, so every pixel
+/// keeps its color.
+pub fn embed_in_palette(png: &mut Png, chunk_type: &ChunkType, message: &[u8]) -> Result<()> {
+    let (palette, colors) = palette_colors(png)?;
+    let mut remaining = canonical_order(&colors)?;
+    let radices = permutation_radices(colors.len());
+    let payload = frame(chunk_type, message, radix_capacity(&radices))?;
+
+    // order[new index] = old index
+    let order: Vec<usize> = to_mixed_radix(&payload, &radices)
+        .into_iter()
+        .map(|digit| remaining.remove(digit as usize))
+        .collect();
+    let mut remap = vec![0u8; order.len()];
+    for (new, &old) in order.iter().enumerate() {
+        remap[old] = new as u8;
+    }
+
+    // Checked before anything is rewritten, so a bad bKGD or hIST leaves the file untouched.
+    let background = match png.unique_chunk("bKGD")? {
+        Some(chunk) => match Background::parse(chunk, &png.image_header()?, Some(order.len()))? {
+            Background::Indexed(index) => Some(Background::Indexed(remap[index as usize])),
+            background => Some(background),
+        },
+        None => None,
+    };
+    let histogram = match png.unique_chunk("hIST")? {
+        Some(chunk) => {
+            let histogram = Histogram::parse(chunk, Some(order.len()))?;
+            let frequencies = order.iter().map(|&i| histogram.frequencies()[i]).collect();
+            Some(Histogram::new(frequencies, order.len())?)
+        }
+        None => None,
+    };
+
+    let mut image = ImageData::decode(png)?;
+    let bit_depth = image.header().bit_depth();
+    for pass in image.passes_mut() {
+        let width = pass.width();
+        for row in pass.rows_mut() {
+            remap_indices(row, width, bit_depth, &remap)?;
+        }
+    }
+    png.set_image_data(image.encode()?);
+
+    let entries = order.iter().map(|&i| palette.entries()[i]).collect();
+    png.replace_chunk(Palette::new(entries)?.to_chunk())?;
+    if png.unique_chunk("tRNS")?.is_some() {
+        let mut alphas: Vec<u8> = order.iter().map(|&i| colors[i][3]).collect();
+        while alphas.len() > 1 && alphas.last() == Some(&255) {
+            alphas.pop();
+        }
+        png.replace_chunk(Transparency::Indexed(alphas).to_chunk())?;
+    }
+    if let Some(background) = background {
+        png.replace_chunk(background.to_chunk()?)?;
+    }
+    if let Some(histogram) = histogram {
+        png.replace_chunk(histogram.to_chunk()?)?;
+    }
+    Ok(())
+}
+
+/// Recovers a message hidden by `embed_in_palette`.
+pub fn extract_from_palette(png: &Png, chunk_type: &ChunkType) -> Result<Vec<u8>> {
+    let (_, colors) = palette_colors(png)?;
+    let mut remaining = canonical_order(&colors)?;
+    let radices = permutation_radices(colors.len());
+
+    let digits: Vec<u32> = (0..colors.len())
+        .map(|index| {
+            let digit = remaining.iter().position(|&i| i == index).unwrap();
+            remaining.remove(digit);
+            digit as u32
+        })
+        .collect();
+    let payload = from_mixed_radix(&digits, &radices, radix_capacity(&radices)).unwrap_or_default();
+    unframe(chunk_type, &payload)
+}
+
+/// Number of filter types a scanline can be stored with.
+const FILTER_TYPES: u32 = 5;
+
+/// Longest message `embed_in_filters` can hide in this image.
+pub fn filter_capacity(image: &ImageData) -> Result<usize> {
+    let rows = image.passes().iter().map(|pass| pass.rows().len()).sum();
+    Ok(message_capacity(radix_capacity(&vec![FILTER_TYPES; rows])))
+}
+
+/// Longest message that replacing the lowest `planes` bits of every color sample could hold.
+///
+/// Alpha samples are left out since changing them is visible wherever the image is composited.
+pub fn lsb_capacity(image: &ImageData, planes: u8) -> Result<usize> {
+    let header = image.header();
+    let color_channels = match header.color_type() {
+        ColorType::Indexed => {
+            return Err(Box::new(std::io::Error::other(
+                "LSB embedding requires a grayscale or truecolor image",
+            )))
+        }
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => 1,
+        ColorType::Rgb | ColorType::Rgba => 3,
+    };
+    if planes > header.bit_depth() {
+        return Err(Box::new(std::io::Error::other(format!(
+            "image has only {} bits per sample",
+            header.bit_depth()
+        ))));
+    }
+    let pixels: usize = image
+        .passes()
+        .iter()
+        .map(|pass| pass.width() as usize * pass.rows().len())
+        .sum();
+    Ok(message_capacity(
+        pixels * color_channels * planes as usize / 8,
+    ))
+}
+
+/// Hides `message` in the filter type chosen for each scanline when IDAT is rewritten.
+///
+/// Every filter type reconstructs the same bytes, so no pixel changes.
+pub fn embed_in_filters(png: &mut Png, chunk_type: &ChunkType, message: &[u8]) -> Result<()> {
+    let mut image = ImageData::decode(png)?;
+    let rows = image.passes().iter().map(|pass| pass.rows().len()).sum();
+    let radices = vec![FILTER_TYPES; rows];
+    let payload = frame(chunk_type, message, radix_capacity(&radices))?;
+
+    let mut digits = to_mixed_radix(&payload, &radices).into_iter();
+    for pass in image.passes_mut() {
+        for filter_type in pass.filter_types_mut() {
+            *filter_type = digits.next().unwrap() as u8;
+        }
+    }
+    png.set_image_data(image.encode()?);
+    Ok(())
+}
+
+/// Recovers a message hidden by `embed_in_filters`.
+pub fn extract_from_filters(png: &Png, chunk_type: &ChunkType) -> Result<Vec<u8>> {
+    let image = ImageData::decode(png)?;
+    let digits: Vec<u32> = image
+        .passes()
+        .iter()
+        .flat_map(|pass| pass.filter_types().iter().map(|&f| f as u32))
+        .collect();
+    let radices = vec![FILTER_TYPES; digits.len()];
+    let payload = from_mixed_radix(&digits, &radices, radix_capacity(&radices)).unwrap_or_default();
+    unframe(chunk_type, &payload)
+}
+
+/// Appends `message` after IEND, framed as a chunk of type `chunk_type`.
+pub fn embed_in_trailer(png: &mut Png, chunk_type: &ChunkType, message: &[u8]) {
+    let mut trailing = png.trailing_data().to_vec();
+    trailing.extend(Chunk::new(chunk_type.clone(), message.to_vec()).as_bytes());
+    png.set_trailing_data(trailing);
+}
+
+/// Recovers the first message appended by `embed_in_trailer` with the given chunk type.
+pub fn extract_from_trailer(png: &Png, chunk_type: &ChunkType) -> Result<Vec<u8>> {
+    let trailing = png.trailing_data();
+    let mut index = 0;
+    while trailing.len() - index >= 12 {
+        let length = u32::from_be_bytes(trailing[index..index + 4].try_into()?) as usize;
+        if trailing.len() - index - 12 < length {
+            break;
+        }
+        let Ok(chunk) = Chunk::try_from(&trailing[index..]) else {
+            break;
+        };
+        if chunk.chunk_type() == chunk_type {
+            return Ok(chunk.data().to_vec());
+        }
+        index += length + 12;
+    }
+    Err(Box::new(std::io::Error::other(format!(
+        "no message with chunk type {} found",
+        chunk_type
+    ))))
+}
+
+/// Rewrites packed palette indices in an unfiltered scanline through `remap`.
+fn remap_indices(row: &mut [u8], width: u32, bit_depth: u8, remap: &[u8]) -> Result<()> {
+    let per_byte = (8 / bit_depth) as usize;
+    let mask = ((1u16 << bit_depth) - 1) as u8;
+    for x in 0..width as usize {
+        let byte = &mut row[x / per_byte];
+        let shift = 8 - bit_depth as usize * (x % per_byte + 1);
+        let index = (*byte >> shift) & mask;
+        let new = *remap.get(index as usize).ok_or_else(|| {
+            Box::new(std::io::Error::other(format!(
+                "pixel index {} is outside the palette",
+                index
+            )))
+        })?;
+        *byte = (*byte & !(mask << shift)) | (new << shift);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::png::tests::chunk;
+    use std::str::FromStr;
+
+    /// An 8x8 image using each of 64 distinct palette colors, the first four translucent.
+    pub(crate) fn indexed_png() -> Png {
+        let mut ihdr = vec![];
+        ihdr.extend(8u32.to_be_bytes());
+        ihdr.extend(8u32.to_be_bytes());
+        ihdr.extend([8, 3, 0, 0, 0]);
+        let plte: Vec<u8> = (0..64u8).flat_map(|i| [i * 4, 255 - i * 4, i]).collect();
+        let mut raw = vec![];
+        for y in 0..8u8 {
+            raw.push(1);
+            raw.push(y * 8);
+            raw.extend([1; 7]);
+        }
+
+        Png::from_chunks(vec![
+            chunk("IHDR", &ihdr),
+            chunk("PLTE", &plte),
+            chunk("tRNS", &[0, 64, 128, 192]),
+            chunk("bKGD", &[5]),
+            chunk(
+                "hIST",
+                &(0..64u16).flat_map(|i| i.to_be_bytes()).collect::<Vec<_>>(),
+            ),
+            chunk("IDAT", &crate::zlib::compress(&raw).unwrap()),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    fn rgba_pixels(png: &Png) -> Vec<[u8; 4]> {
+        let (_, colors) = palette_colors(png).unwrap();
+        ImageData::decode(png).unwrap().passes()[0]
+            .rows()
+            .iter()
+            .flatten()
+            .map(|&i| colors[i as usize])
+            .collect()
+    }
+
+    #[test]
+    fn test_mixed_radix_round_trip() {
+        let radices = permutation_radices(20);
+        let capacity = radix_capacity(&radices);
+        assert_eq!(capacity, 7); // 20! is just under 2^62
+        let bytes = vec![0xde, 0xad, 0xbe, 0xef, 0x01, 0x02, 0x03];
+        let digits = to_mixed_radix(&bytes, &radices);
+        assert!(digits.iter().zip(&radices).all(|(d, r)| d < r));
+        assert_eq!(from_mixed_radix(&digits, &radices, capacity), Some(bytes));
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let payload = frame(&chunk_type, b"hi", 10).unwrap();
+        assert_eq!(payload.len(), 10);
+        assert_eq!(unframe(&chunk_type, &payload).unwrap(), b"hi");
+        assert!(unframe(&ChunkType::from_str("RuSt").unwrap(), &payload).is_err());
+        assert!(frame(&chunk_type, b"too long", 10).is_err());
+    }
+
+    #[test]
+    fn test_palette_round_trip() {
+        let mut png = indexed_png();
+        let before = rgba_pixels(&png);
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+
+        embed_in_palette(&mut png, &chunk_type, b"hidden in plain sight").unwrap();
+
+        assert_ne!(png.palette().unwrap(), indexed_png().palette().unwrap());
+        assert_eq!(rgba_pixels(&png), before);
+        assert_eq!(
+            extract_from_palette(&png, &chunk_type).unwrap(),
+            b"hidden in plain sight"
+        );
+    }
+
+    #[test]
+    fn test_palette_background_and_histogram_follow_entries() {
+        let mut png = indexed_png();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        embed_in_palette(&mut png, &chunk_type, b"bg").unwrap();
+
+        let index = png.chunk_by_type("bKGD").unwrap().data()[0] as usize;
+        let palette = png.palette().unwrap().unwrap();
+        assert_eq!(palette.entries()[index], [20, 235, 5]);
+        let histogram = Histogram::parse(png.chunk_by_type("hIST").unwrap(), Some(64)).unwrap();
+        for (entry, &frequency) in palette.entries().iter().zip(histogram.frequencies()) {
+            assert_eq!(frequency, entry[2] as u16);
+        }
+    }
+
+    #[test]
+    fn test_palette_rejects_duplicate_background_and_stale_histogram() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let mut png = indexed_png();
+        png.append_chunk(chunk("bKGD", &[6]));
+        assert!(embed_in_palette(&mut png, &chunk_type, b"bg").is_err());
+
+        let mut png = indexed_png();
+        png.replace_chunk(chunk("hIST", &[0, 1, 0, 2])).unwrap();
+        let before = png.as_bytes();
+        assert!(embed_in_palette(&mut png, &chunk_type, b"bg").is_err());
+        assert_eq!(png.as_bytes(), before);
+    }
+
+    #[test]
+    fn test_palette_message_too_long() {
+        let mut png = indexed_png();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let (_, colors) = palette_colors(&png).unwrap();
+        let capacity = radix_capacity(&permutation_radices(colors.len()));
+        let message = vec![b'x'; capacity];
+        assert!(embed_in_palette(&mut png, &chunk_type, &message).is_err());
+    }
+
+    #[test]
+    fn test_palette_capacity() {
+        // 64! is just over 2^295, so 36 bytes
+        let mut png = indexed_png();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let capacity = palette_capacity(&png).unwrap();
+        assert_eq!(capacity, 36 - FRAME_OVERHEAD);
+        assert!(embed_in_palette(&mut png, &chunk_type, &vec![b'x'; capacity]).is_ok());
+        let mut png = indexed_png();
+        let message = vec![b'x'; capacity + 1];
+        assert!(embed_in_palette(&mut png, &chunk_type, &message).is_err());
+    }
+
+    #[test]
+    fn test_too_small_for_the_frame() {
+        // 4! orderings and 5^2 filter choices both hold less than one byte
+        let mut ihdr = vec![];
+        ihdr.extend(4u32.to_be_bytes());
+        ihdr.extend(2u32.to_be_bytes());
+        ihdr.extend([8, 3, 0, 0, 0]);
+        let plte: Vec<u8> = (0..4u8).flat_map(|i| [i, i, i]).collect();
+        let raw = [0, 0, 1, 2, 3, 0, 3, 2, 1, 0];
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", &ihdr),
+            chunk("PLTE", &plte),
+            chunk("IDAT", &crate::zlib::compress(&raw).unwrap()),
+            chunk("IEND", &[]),
+        ]);
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert_eq!(palette_capacity(&png).unwrap(), 0);
+        assert!(embed_in_palette(&mut png, &chunk_type, b"").is_err());
+        let image = ImageData::decode(&png).unwrap();
+        assert_eq!(filter_capacity(&image).unwrap(), 0);
+        assert!(embed_in_filters(&mut png, &chunk_type, b"").is_err());
+    }
+
+    #[test]
+    fn test_palette_without_message() {
+        let png = indexed_png();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert!(extract_from_palette(&png, &chunk_type).is_err());
+    }
+
+    #[test]
+    fn test_filters_round_trip() {
+        let mut png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        let before = ImageData::decode(&png).unwrap();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+
+        embed_in_filters(&mut png, &chunk_type, b"8 bytes!").unwrap();
+
+        let after = ImageData::decode(&png).unwrap();
+        assert_eq!(after.passes()[0].rows(), before.passes()[0].rows());
+        assert_ne!(
+            after.passes()[0].filter_types(),
+            before.passes()[0].filter_types()
+        );
+        assert_eq!(
+            extract_from_filters(&png, &chunk_type).unwrap(),
+            b"8 bytes!"
+        );
+    }
+
+    #[test]
+    fn test_filters_message_too_long() {
+        // 50 rows of 5 filter types hold 14 bytes, 8 after the frame
+        let mut png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert!(embed_in_filters(&mut png, &chunk_type, b"9 bytes!!").is_err());
+    }
+
+    #[test]
+    fn test_filter_capacity() {
+        let png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        let image = ImageData::decode(&png).unwrap();
+        assert_eq!(filter_capacity(&image).unwrap(), 8);
+    }
+
+    #[test]
+    fn test_filters_without_message() {
+        let png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert!(extract_from_filters(&png, &chunk_type).is_err());
+    }
+
+    #[test]
+    fn test_lsb_capacity() {
+        // 50x50 RGBA, alpha excluded: 7500 samples
+        let png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        let image = ImageData::decode(&png).unwrap();
+        assert_eq!(lsb_capacity(&image, 1).unwrap(), 7500 / 8 - FRAME_OVERHEAD);
+        assert_eq!(lsb_capacity(&image, 4).unwrap(), 7500 / 2 - FRAME_OVERHEAD);
+        assert!(lsb_capacity(&image, 9).is_err());
+        let indexed = ImageData::decode(&indexed_png()).unwrap();
+        assert!(lsb_capacity(&indexed, 1).is_err());
+    }
+
+    #[test]
+    fn test_trailer_round_trip() {
+        let mut png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        let first = ChunkType::from_str("ruSt").unwrap();
+        let second = ChunkType::from_str("ruST").unwrap();
+        embed_in_trailer(&mut png, &first, b"one");
+        embed_in_trailer(&mut png, &second, b"two");
+
+        let png = Png::try_from(&png.as_bytes()[..]).unwrap();
+        assert_eq!(extract_from_trailer(&png, &first).unwrap(), b"one");
+        assert_eq!(extract_from_trailer(&png, &second).unwrap(), b"two");
+        assert!(extract_from_trailer(&png, &ChunkType::from_str("RuSt").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_trailer_ignores_foreign_data() {
+        let mut png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        png.set_trailing_data(b"PK\x03\x04 not a chunk at all".to_vec());
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert!(extract_from_trailer(&png, &chunk_type).is_err());
+    }
+
+    #[test]
+    fn test_palette_requires_indexed_image() {
+        let mut png = Png::try_from(&crate::png::tests::PNG_FILE[..]).unwrap();
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert!(embed_in_palette(&mut png, &chunk_type, b"x").is_err());
+    }
+}
@@ -0,0 +1,373 @@
+use crate::chunk::{check_chunk, invalid};
+use crate::png::{Chunk, ChunkType};
+use crate::{zlib, Error, Result};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Checks the keyword rules shared by all text chunks and returns its Latin-1 bytes.
+pub(crate) fn keyword_bytes(keyword: &str) -> Result<Vec<u8>> {
+    let bytes = latin1_bytes(keyword)?;
+    let printable = bytes.iter().all(|&b| (32..=126).contains(&b) || b >= 161);
+    if bytes.is_empty()
+        || bytes.len() > 79
+        || !printable
+        || keyword.starts_with(' ')
+        || keyword.ends_with(' ')
+        || keyword.contains("  ")
+    {
+        return Err(invalid(format!("invalid keyword: {:?}", keyword)));
+    }
+    Ok(bytes)
+}
+
+fn latin1_bytes(text: &str) -> Result<Vec<u8>> {
+    text.chars()
+        .map(|c| {
+            u8::try_from(c as u32)
+                .map_err(|_| invalid(format!("{:?} cannot be encoded as Latin-1", c)))
+        })
+        .collect()
+}
+
+pub(crate) fn latin1_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Splits `data` at the first null byte, dropping the separator.
+pub(crate) fn split_null(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    let index = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| invalid("missing null separator".to_string()))?;
+    Ok((&data[..index], &data[index + 1..]))
+}
+
+/// tEXt: an uncompressed Latin-1 keyword/text pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    keyword: String,
+    text: String,
+}
+impl TextChunk {
+    pub fn new(keyword: &str, text: &str) -> Result<TextChunk> {
+        keyword_bytes(keyword)?;
+        if latin1_bytes(text)?.contains(&0) {
+            return Err(invalid("tEXt text must not contain NUL".to_string()));
+        }
+        Ok(TextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = keyword_bytes(&self.keyword)?;
+        data.push(0);
+        data.extend(latin1_bytes(&self.text)?);
+        Ok(Chunk::new(ChunkType::from_str("tEXt")?, data))
+    }
+}
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "tEXt", None)?;
+        let (keyword, text) = split_null(chunk.data())?;
+        TextChunk::new(&latin1_string(keyword), &latin1_string(text))
+    }
+}
+
+/// zTXt: a Latin-1 keyword/text pair with the text zlib-compressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTextChunk {
+    keyword: String,
+    text: String,
+}
+impl CompressedTextChunk {
+    pub fn new(keyword: &str, text: &str) -> Result<CompressedTextChunk> {
+        keyword_bytes(keyword)?;
+        latin1_bytes(text)?;
+        Ok(CompressedTextChunk {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = keyword_bytes(&self.keyword)?;
+        data.extend([0, 0]); // separator, compression method 0
+        data.extend(zlib::compress(&latin1_bytes(&self.text)?)?);
+        Ok(Chunk::new(ChunkType::from_str("zTXt")?, data))
+    }
+}
+impl TryFrom<&Chunk> for CompressedTextChunk {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "zTXt", None)?;
+        let (keyword, rest) = split_null(chunk.data())?;
+        match rest.split_first() {
+            Some((0, compressed)) => CompressedTextChunk::new(
+                &latin1_string(keyword),
+                &latin1_string(&zlib::decompress(compressed)?),
+            ),
+            _ => Err(invalid("unsupported zTXt compression method".to_string())),
+        }
+    }
+}
+
+/// iTXt: a UTF-8 text with language tag and translated keyword, optionally compressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalTextChunk {
+    keyword: String,
+    compressed: bool,
+    language_tag: String,
+    translated_keyword: String,
+    text: String,
+}
+impl InternationalTextChunk {
+    pub fn new(
+        keyword: &str,
+        compressed: bool,
+        language_tag: &str,
+        translated_keyword: &str,
+        text: &str,
+    ) -> Result<InternationalTextChunk> {
+        keyword_bytes(keyword)?;
+        let valid_tag = language_tag.split('-').all(|part| {
+            (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+        if !language_tag.is_empty() && !valid_tag {
+            return Err(invalid(format!("invalid language tag: {:?}", language_tag)));
+        }
+        if translated_keyword.contains('\0') || text.contains('\0') {
+            return Err(invalid("iTXt fields cannot contain null bytes".to_string()));
+        }
+        Ok(InternationalTextChunk {
+            keyword: keyword.to_string(),
+            compressed,
+            language_tag: language_tag.to_string(),
+            translated_keyword: translated_keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
+    pub fn language_tag(&self) -> &str {
+        &self.language_tag
+    }
+    pub fn translated_keyword(&self) -> &str {
+        &self.translated_keyword
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = keyword_bytes(&self.keyword)?;
+        data.extend([0, self.compressed as u8, 0]);
+        data.extend(self.language_tag.as_bytes());
+        data.push(0);
+        data.extend(self.translated_keyword.as_bytes());
+        data.push(0);
+        if self.compressed {
+            data.extend(zlib::compress(self.text.as_bytes())?);
+        } else {
+            data.extend(self.text.as_bytes());
+        }
+        Ok(Chunk::new(ChunkType::from_str("iTXt")?, data))
+    }
+}
+impl TryFrom<&Chunk> for InternationalTextChunk {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_chunk(chunk, "iTXt", None)?;
+        let (keyword, rest) = split_null(chunk.data())?;
+        let (compressed, rest) = match rest {
+            [0, 0, rest @ ..] => (false, rest),
+            [1, 0, rest @ ..] => (true, rest),
+            _ => return Err(invalid("invalid iTXt compression fields".to_string())),
+        };
+        let (language_tag, rest) = split_null(rest)?;
+        let (translated_keyword, text) = split_null(rest)?;
+        let text = if compressed {
+            zlib::decompress(text)?
+        } else {
+            text.to_vec()
+        };
+        InternationalTextChunk::new(
+            &latin1_string(keyword),
+            compressed,
+            std::str::from_utf8(language_tag)?,
+            std::str::from_utf8(translated_keyword)?,
+            &String::from_utf8(text)?,
+        )
+    }
+}
+
+/// Any of the three textual chunk kinds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextualChunk {
+    Text(TextChunk),
+    Compressed(CompressedTextChunk),
+    International(InternationalTextChunk),
+}
+impl TextualChunk {
+    pub fn is_text_type(chunk_type: &ChunkType) -> bool {
+        ["tEXt", "zTXt", "iTXt"]
+            .iter()
+            .any(|t| t.as_bytes() == chunk_type.bytes())
+    }
+    pub fn keyword(&self) -> &str {
+        match self {
+            TextualChunk::Text(text) => text.keyword(),
+            TextualChunk::Compressed(text) => text.keyword(),
+            TextualChunk::International(text) => text.keyword(),
+        }
+    }
+    pub fn text(&self) -> &str {
+        match self {
+            TextualChunk::Text(text) => text.text(),
+            TextualChunk::Compressed(text) => text.text(),
+            TextualChunk::International(text) => text.text(),
+        }
+    }
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        match self {
+            TextualChunk::Text(text) => text.to_chunk(),
+            TextualChunk::Compressed(text) => text.to_chunk(),
+            TextualChunk::International(text) => text.to_chunk(),
+        }
+    }
+}
+impl TryFrom<&Chunk> for TextualChunk {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        match &chunk.chunk_type().bytes() {
+            b"tEXt" => Ok(TextualChunk::Text(TextChunk::try_from(chunk)?)),
+            b"zTXt" => Ok(TextualChunk::Compressed(CompressedTextChunk::try_from(
+                chunk,
+            )?)),
+            b"iTXt" => Ok(TextualChunk::International(
+                InternationalTextChunk::try_from(chunk)?,
+            )),
+            _ => Err(invalid(format!(
+                "{} is not a text chunk",
+                chunk.chunk_type()
+            ))),
+        }
+    }
+}
+impl Display for TextualChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextualChunk::Text(text) => write!(f, "tEXt {}: {}", text.keyword, text.text),
+            TextualChunk::Compressed(text) => write!(f, "zTXt {}: {}", text.keyword, text.text),
+            TextualChunk::International(text) => {
+                write!(f, "iTXt {}", text.keyword())?;
+                if !text.language_tag().is_empty() {
+                    write!(f, " [{}]", text.language_tag())?;
+                }
+                if !text.translated_keyword().is_empty() {
+                    write!(f, " ({})", text.translated_keyword())?;
+                }
+                if text.compressed() {
+                    write!(f, " (compressed)")?;
+                }
+                write!(f, ": {}", text.text())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_chunk_round_trip() {
+        let text = TextChunk::new("Comment", "café au lait").unwrap();
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(&chunk.data()[..8], b"Comment\0");
+        assert_eq!(chunk.data()[11], 0xe9); // é as a single Latin-1 byte
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_text_chunk_rejects_non_latin1() {
+        assert!(TextChunk::new("Comment", "日本語").is_err());
+    }
+
+    #[test]
+    fn test_text_chunk_rejects_nul() {
+        assert!(TextChunk::new("Comment", "a\0b").is_err());
+        let chunk = Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"a\0b\0c".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_invalid_keywords() {
+        for keyword in [
+            "",
+            " Title",
+            "Title ",
+            "Two  spaces",
+            "Tab\tbed",
+            &"k".repeat(80),
+        ] {
+            assert!(TextChunk::new(keyword, "x").is_err(), "{:?}", keyword);
+        }
+        assert!(TextChunk::new("Creation Time", "x").is_ok());
+    }
+
+    #[test]
+    fn test_compressed_text_round_trip() {
+        let long = "repetitive ".repeat(100);
+        let text = CompressedTextChunk::new("Description", &long).unwrap();
+        let chunk = text.to_chunk().unwrap();
+        assert!(chunk.data().len() < long.len());
+        assert_eq!(CompressedTextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_international_text_round_trip() {
+        for compressed in [false, true] {
+            let text = InternationalTextChunk::new("Title", compressed, "ja", "タイトル", "日本語")
+                .unwrap();
+            let chunk = text.to_chunk().unwrap();
+            assert_eq!(InternationalTextChunk::try_from(&chunk).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_international_text_layout() {
+        let text = InternationalTextChunk::new("Title", false, "en-GB", "", "Hi").unwrap();
+        assert_eq!(text.to_chunk().unwrap().data(), b"Title\0\0\0en-GB\0\0Hi");
+        assert!(InternationalTextChunk::new("Title", false, "not a tag", "", "Hi").is_err());
+    }
+
+    #[test]
+    fn test_textual_chunk_dispatch() {
+        let chunk = CompressedTextChunk::new("Author", "me")
+            .unwrap()
+            .to_chunk()
+            .unwrap();
+        let text = TextualChunk::try_from(&chunk).unwrap();
+        assert_eq!(text.keyword(), "Author");
+        assert_eq!(text.text(), "me");
+        assert_eq!(text.to_string(), "zTXt Author: me");
+        let other = Chunk::new(ChunkType::from_str("RuSt").unwrap(), vec![]);
+        assert!(TextualChunk::try_from(&other).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::chunk::{check_chunk, invalid, u16_at};
+use crate::png::{Chunk, ChunkType, Png};
+use crate::{Error, Result};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// tIME: when the image was last modified, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastModified {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+impl LastModified {
+    pub fn new(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<LastModified> {
+        // 60 allows for leap seconds, as the spec does.
+        if !(1..=12).contains(&month)
+            || !(1..=days_in_month(year, month)).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return Err(invalid(format!(
+                "invalid time {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                year, month, day, hour, minute, second
+            )));
+        }
+        Ok(LastModified {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+    pub fn now() -> Result<LastModified> {
+        LastModified::from_unix(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+    }
+    pub fn from_unix(seconds: u64) -> Result<LastModified> {
+        let (days, time) = (seconds / 86400, seconds % 86400);
+        // Civil-from-days conversion over 400-year eras, starting each year in March.
+        let z = days as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        LastModified::new(
+            u16::try_from(year)?,
+            month as u8,
+            day as u8,
+            (time / 3600) as u8,
+            (time / 60 % 60) as u8,
+            (time % 60) as u8,
+        )
+    }
+    pub fn to_chunk(self) -> Result<Chunk> {
+        let mut data = self.year.to_be_bytes().to_vec();
+        data.extend([self.month, self.day, self.hour, self.minute, self.second]);
+        Ok(Chunk::new(ChunkType::from_str("tIME")?, data))
+    }
+}
+impl TryFrom<&Chunk> for LastModified {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+        check_chunk(chunk, "tIME", Some(7))?;
+        LastModified::new(u16_at(data, 0), data[2], data[3], data[4], data[5], data[6])
+    }
+}
+/// Parses `YYYY-MM-DD HH:MM:SS` or `YYYY-MM-DDTHH:MM:SS`, taken as UTC.
+impl FromStr for LastModified {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let error = || invalid(format!("expected YYYY-MM-DDTHH:MM:SS, found {:?}", s));
+        let (date, time) = s
+            .trim_end_matches('Z')
+            .split_once(['T', ' '])
+            .ok_or_else(error)?;
+        let fields: Vec<&str> = date.split('-').chain(time.split(':')).collect();
+        if fields.len() != 6 {
+            return Err(error());
+        }
+        let field = |index: usize| fields[index].parse::<u8>().map_err(|_| error());
+        LastModified::new(
+            fields[0].parse().map_err(|_| error())?,
+            field(1)?,
+            field(2)?,
+            field(3)?,
+            field(4)?,
+            field(5)?,
+        )
+    }
+}
+impl Display for LastModified {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Records `time` in the tIME chunk, updating it in place or adding one before IEND.
+pub fn touch(png: &mut Png, time: LastModified) -> Result<()> {
+    let chunk = time.to_chunk()?;
+    if png.replace_chunk(chunk.clone()).is_err() {
+        png.insert_before_end(chunk);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let time = LastModified::new(2024, 2, 29, 23, 59, 60).unwrap();
+        let chunk = time.to_chunk().unwrap();
+        assert_eq!(chunk.data(), [7, 232, 2, 29, 23, 59, 60]);
+        assert_eq!(LastModified::try_from(&chunk).unwrap(), time);
+        assert_eq!(time.to_string(), "2024-02-29 23:59:60 UTC");
+    }
+
+    #[test]
+    fn test_invalid_times() {
+        assert!(LastModified::new(2023, 2, 29, 0, 0, 0).is_err());
+        assert!(LastModified::new(2024, 13, 1, 0, 0, 0).is_err());
+        assert!(LastModified::new(2024, 1, 1, 24, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_from_unix() {
+        assert_eq!(
+            LastModified::from_unix(0).unwrap(),
+            LastModified::new(1970, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            LastModified::from_unix(1_709_251_199).unwrap(),
+            LastModified::new(2024, 2, 29, 23, 59, 59).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        let expected = LastModified::new(2024, 5, 1, 12, 34, 56).unwrap();
+        assert_eq!(
+            "2024-05-01T12:34:56".parse::<LastModified>().unwrap(),
+            expected
+        );
+        assert_eq!(
+            "2024-05-01 12:34:56Z".parse::<LastModified>().unwrap(),
+            expected
+        );
+        assert!("2024-05-01".parse::<LastModified>().is_err());
+    }
+
+    #[test]
+    fn test_touch_replaces_in_place() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let first = LastModified::new(2020, 1, 1, 0, 0, 0).unwrap();
+        touch(&mut png, first).unwrap();
+        let count = png.chunks().len();
+        let second = LastModified::new(2024, 1, 1, 0, 0, 0).unwrap();
+        touch(&mut png, second).unwrap();
+        assert_eq!(png.chunks().len(), count);
+        let chunk = png.chunk_by_type("tIME").unwrap();
+        assert_eq!(LastModified::try_from(chunk).unwrap(), second);
+    }
+}
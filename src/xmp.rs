@@ -0,0 +1,1032 @@
+use crate::chunk::invalid;
+use crate::png::Chunk;
+use crate::text::InternationalTextChunk;
+use crate::{Error, Result};
+use std::fmt::Display;
+
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const XML: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Prefixes that can be used in paths without declaring their namespace.
+const KNOWN_NAMESPACES: [(&str, &str); 9] = [
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+    ("xmpMM", "http://ns.adobe.com/xap/1.0/mm/"),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+    (
+        "Iptc4xmpCore",
+        "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/",
+    ),
+    ("plus", "http://ns.useplus.org/ldf/xmp/1.0/"),
+    ("tiff", "http://ns.adobe.com/tiff/1.0/"),
+    ("exif", "http://ns.adobe.com/exif/1.0/"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayKind {
+    Seq,
+    Bag,
+    Alt,
+}
+impl ArrayKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ArrayKind::Seq => "Seq",
+            ArrayKind::Bag => "Bag",
+            ArrayKind::Alt => "Alt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmpValue {
+    Text(String),
+    /// A URI written as `rdf:resource` rather than as element text.
+    Uri(String),
+    Array(ArrayKind, Vec<XmpItem>),
+    Struct(Vec<XmpProperty>),
+}
+
+/// Text as a string, a URI as `{"uri": ...}`, an array as its kind and items and a struct as
+/// its fields.
+#[cfg(feature = "serde")]
+impl serde::Serialize for XmpValue {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        match self {
+            XmpValue::Text(text) => serializer.serialize_str(text),
+            XmpValue::Uri(uri) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("uri", uri)?;
+                map.end()
+            }
+            XmpValue::Array(kind, items) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("array", &kind.name().to_lowercase())?;
+                map.serialize_entry("items", items)?;
+                map.end()
+            }
+            XmpValue::Struct(fields) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("fields", fields)?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// One `rdf:li` of an array, with its `xml:lang` for language alternatives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct XmpItem {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    language: Option<String>,
+    value: XmpValue,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    qualifiers: Vec<XmpProperty>,
+}
+
+/// A property with its `xml:lang` and any other qualifiers written next to an `rdf:value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct XmpProperty {
+    namespace: String,
+    prefix: String,
+    name: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    language: Option<String>,
+    value: XmpValue,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    qualifiers: Vec<XmpProperty>,
+}
+impl XmpProperty {
+    pub fn qualified_name(&self) -> String {
+        format!("{}:{}", self.prefix, self.name)
+    }
+}
+
+/// One step of a property path such as `dc:title[x-default]` or `dc:creator[2]`.
+struct Segment<'a> {
+    prefix: &'a str,
+    name: &'a str,
+    selector: Option<&'a str>,
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment<'_>>> {
+    path.split('/')
+        .map(|part| {
+            let (qualified, selector) = match part.split_once('[') {
+                Some((qualified, rest)) => {
+                    let selector = rest
+                        .strip_suffix(']')
+                        .ok_or_else(|| invalid(format!("invalid path segment: {}", part)))?;
+                    (qualified, Some(selector))
+                }
+                None => (part, None),
+            };
+            let (prefix, name) = qualified
+                .split_once(':')
+                .filter(|(prefix, name)| !prefix.is_empty() && !name.is_empty())
+                .ok_or_else(|| invalid(format!("expected prefix:name, found {}", qualified)))?;
+            Ok(Segment {
+                prefix,
+                name,
+                selector,
+            })
+        })
+        .collect()
+}
+
+/// The item `selector` picks out: a 1-based index, or a language for alternatives.
+fn item_position(items: &[XmpItem], selector: &str) -> Option<usize> {
+    match selector.parse::<usize>() {
+        Ok(index) => (index >= 1 && index <= items.len()).then(|| index - 1),
+        Err(_) => items
+            .iter()
+            .position(|item| item.language.as_deref() == Some(selector)),
+    }
+}
+
+/// An XMP packet as a tree of properties, stored in PNG as the `XML:com.adobe.xmp` iTXt chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Xmp {
+    properties: Vec<XmpProperty>,
+}
+impl Xmp {
+    pub const KEYWORD: &'static str = "XML:com.adobe.xmp";
+
+    pub fn parse(xml: &str) -> Result<Xmp> {
+        let document = roxmltree::Document::parse(xml)?;
+        let mut properties: Vec<XmpProperty> = document
+            .descendants()
+            .filter(|node| node.has_tag_name((RDF, "Description")))
+            .filter(|node| {
+                node.parent_element()
+                    .is_some_and(|parent| parent.has_tag_name((RDF, "RDF")))
+            })
+            .flat_map(parse_fields)
+            .collect();
+        let mut bound = vec![
+            ("x".to_string(), "adobe:ns:meta/".to_string()),
+            ("rdf".to_string(), RDF.to_string()),
+        ];
+        bind_prefixes(&mut properties, &mut bound)?;
+        Ok(Xmp { properties })
+    }
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        InternationalTextChunk::new(Xmp::KEYWORD, false, "", "", &self.to_xml())?.to_chunk()
+    }
+    pub fn is_xmp_chunk(chunk: &Chunk) -> bool {
+        &chunk.chunk_type().bytes() == b"iTXt"
+            && chunk.data().starts_with(Xmp::KEYWORD.as_bytes())
+            && chunk.data().get(Xmp::KEYWORD.len()) == Some(&0)
+    }
+
+    fn namespace_for(&self, prefix: &str) -> Option<String> {
+        fn search(properties: &[XmpProperty], prefix: &str) -> Option<String> {
+            properties.iter().find_map(|property| {
+                if property.prefix == prefix {
+                    return Some(property.namespace.clone());
+                }
+                let nested = match &property.value {
+                    XmpValue::Struct(fields) => search(fields, prefix),
+                    XmpValue::Array(_, items) => items.iter().find_map(|item| {
+                        let fields = match &item.value {
+                            XmpValue::Struct(fields) => search(fields, prefix),
+                            _ => None,
+                        };
+                        fields.or_else(|| search(&item.qualifiers, prefix))
+                    }),
+                    XmpValue::Text(_) | XmpValue::Uri(_) => None,
+                };
+                nested.or_else(|| search(&property.qualifiers, prefix))
+            })
+        }
+        search(&self.properties, prefix).or_else(|| {
+            KNOWN_NAMESPACES
+                .iter()
+                .find(|(known, _)| *known == prefix)
+                .map(|(_, namespace)| namespace.to_string())
+        })
+    }
+
+    pub fn get(&self, path: &str) -> Result<Option<&XmpValue>> {
+        let segments = parse_path(path)?;
+        let mut properties = &self.properties;
+        let mut found = None;
+        for (i, segment) in segments.iter().enumerate() {
+            let Some(namespace) = self.namespace_for(segment.prefix) else {
+                return Ok(None);
+            };
+            let Some(property) = properties
+                .iter()
+                .find(|p| p.namespace == namespace && p.name == segment.name)
+            else {
+                return Ok(None);
+            };
+            let mut value = &property.value;
+            if let Some(selector) = segment.selector {
+                let XmpValue::Array(_, items) = value else {
+                    return Ok(None);
+                };
+                let Some(position) = item_position(items, selector) else {
+                    return Ok(None);
+                };
+                value = &items[position].value;
+            }
+            if i + 1 < segments.len() {
+                let XmpValue::Struct(fields) = value else {
+                    return Ok(None);
+                };
+                properties = fields;
+            }
+            found = Some(value);
+        }
+        Ok(found)
+    }
+
+    /// Sets a simple value, creating properties, structs and arrays along `path` as needed.
+    ///
+    /// Without a selector a language alternative has its `x-default` item replaced and other
+    /// arrays become a single item. `namespace` declares a prefix the packet does not know yet.
+    pub fn set(&mut self, path: &str, value: &str, namespace: Option<&str>) -> Result<()> {
+        let segments = parse_path(path)?;
+        let namespaces = segments
+            .iter()
+            .map(|segment| {
+                self.namespace_for(segment.prefix)
+                    .or_else(|| namespace.map(str::to_string))
+                    .ok_or_else(|| invalid(format!("unknown namespace prefix: {}", segment.prefix)))
+            })
+            .collect::<Result<Vec<String>>>()?;
+
+        let mut properties = &mut self.properties;
+        for (i, (segment, namespace)) in segments.iter().zip(&namespaces).enumerate() {
+            let last = i + 1 == segments.len();
+            let index = match properties
+                .iter()
+                .position(|p| &p.namespace == namespace && p.name == segment.name)
+            {
+                Some(index) => index,
+                None => {
+                    let placeholder = match (segment.selector, last) {
+                        (Some(selector), _) if selector.parse::<usize>().is_err() => {
+                            XmpValue::Array(ArrayKind::Alt, vec![])
+                        }
+                        (Some(_), _) => XmpValue::Array(ArrayKind::Seq, vec![]),
+                        (None, true) => XmpValue::Text(String::new()),
+                        (None, false) => XmpValue::Struct(vec![]),
+                    };
+                    properties.push(XmpProperty {
+                        namespace: namespace.clone(),
+                        prefix: segment.prefix.to_string(),
+                        name: segment.name.to_string(),
+                        language: None,
+                        value: placeholder,
+                        qualifiers: vec![],
+                    });
+                    properties.len() - 1
+                }
+            };
+            let mut target = &mut properties[index].value;
+
+            if let Some(selector) = segment.selector {
+                let XmpValue::Array(_, items) = target else {
+                    return Err(invalid(format!(
+                        "{}:{} is not an array",
+                        segment.prefix, segment.name
+                    )));
+                };
+                let position = match item_position(items, selector) {
+                    Some(position) => position,
+                    None if selector == (items.len() + 1).to_string()
+                        || selector.parse::<usize>().is_err() =>
+                    {
+                        let language = selector
+                            .parse::<usize>()
+                            .is_err()
+                            .then(|| selector.to_string());
+                        let empty = if last {
+                            XmpValue::Text(String::new())
+                        } else {
+                            XmpValue::Struct(vec![])
+                        };
+                        items.push(XmpItem {
+                            language,
+                            value: empty,
+                            qualifiers: vec![],
+                        });
+                        items.len() - 1
+                    }
+                    None => {
+                        return Err(invalid(format!(
+                            "no item {} in {}:{}",
+                            selector, segment.prefix, segment.name
+                        )))
+                    }
+                };
+                target = &mut items[position].value;
+            }
+
+            if last {
+                match target {
+                    XmpValue::Array(ArrayKind::Alt, items) if segment.selector.is_none() => {
+                        match item_position(items, "x-default") {
+                            Some(position) => {
+                                items[position].value = XmpValue::Text(value.to_string())
+                            }
+                            None => items.insert(
+                                0,
+                                XmpItem {
+                                    language: Some("x-default".to_string()),
+                                    value: XmpValue::Text(value.to_string()),
+                                    qualifiers: vec![],
+                                },
+                            ),
+                        }
+                    }
+                    XmpValue::Array(_, items) if segment.selector.is_none() => {
+                        *items = vec![XmpItem {
+                            language: None,
+                            value: XmpValue::Text(value.to_string()),
+                            qualifiers: vec![],
+                        }];
+                    }
+                    _ => *target = XmpValue::Text(value.to_string()),
+                }
+                return Ok(());
+            }
+            let XmpValue::Struct(fields) = target else {
+                return Err(invalid(format!(
+                    "{}:{} is not a struct",
+                    segment.prefix, segment.name
+                )));
+            };
+            properties = fields;
+        }
+        Ok(())
+    }
+
+    /// Removes the property or array item at `path`, returning what was there.
+    pub fn remove(&mut self, path: &str) -> Result<XmpValue> {
+        let segments = parse_path(path)?;
+        let namespaces: Vec<Option<String>> = segments
+            .iter()
+            .map(|segment| self.namespace_for(segment.prefix))
+            .collect();
+        let not_found = || invalid(format!("property not found: {}", path));
+
+        let mut properties = &mut self.properties;
+        for (i, (segment, namespace)) in segments.iter().zip(&namespaces).enumerate() {
+            let namespace = namespace.as_ref().ok_or_else(not_found)?;
+            let index = properties
+                .iter()
+                .position(|p| &p.namespace == namespace && p.name == segment.name)
+                .ok_or_else(not_found)?;
+            let last = i + 1 == segments.len();
+            if last && segment.selector.is_none() {
+                return Ok(properties.remove(index).value);
+            }
+            let mut target = &mut properties[index].value;
+            if let Some(selector) = segment.selector {
+                let XmpValue::Array(_, items) = target else {
+                    return Err(not_found());
+                };
+                let position = item_position(items, selector).ok_or_else(not_found)?;
+                if last {
+                    return Ok(items.remove(position).value);
+                }
+                target = &mut items[position].value;
+            }
+            let XmpValue::Struct(fields) = target else {
+                return Err(not_found());
+            };
+            properties = fields;
+        }
+        Err(not_found())
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut namespaces: Vec<(String, String)> = vec![];
+        collect_namespaces(&self.properties, &mut namespaces);
+        let mut xml = String::from(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+             <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+             <rdf:Description rdf:about=\"\"",
+        );
+        for (prefix, namespace) in &namespaces {
+            xml.push_str(&format!("\n    xmlns:{}=\"{}\"", prefix, escape(namespace)));
+        }
+        xml.push_str(">\n");
+        for property in &self.properties {
+            write_property(&mut xml, property, 3);
+        }
+        xml.push_str("  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>");
+        xml
+    }
+}
+impl TryFrom<&Chunk> for Xmp {
+    type Error = Error;
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let text = InternationalTextChunk::try_from(chunk)?;
+        if text.keyword() != Xmp::KEYWORD {
+            return Err(invalid(format!("{} is not an XMP keyword", text.keyword())));
+        }
+        Xmp::parse(text.text())
+    }
+}
+impl Display for Xmp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for property in &self.properties {
+            show_property(f, property, 0)?;
+        }
+        Ok(())
+    }
+}
+impl Display for XmpValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XmpValue::Text(text) | XmpValue::Uri(text) => writeln!(f, "{}", text),
+            XmpValue::Array(_, items) => write_items(f, items, 0),
+            XmpValue::Struct(fields) => {
+                for field in fields {
+                    show_property(f, field, 0)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_value(
+    f: &mut std::fmt::Formatter<'_>,
+    label: &str,
+    value: &XmpValue,
+    depth: usize,
+) -> std::fmt::Result {
+    let indent = "  ".repeat(depth);
+    match value {
+        XmpValue::Text(text) | XmpValue::Uri(text) => {
+            writeln!(f, "{}{} = {}", indent, label, text)
+        }
+        XmpValue::Array(kind, items) => {
+            writeln!(f, "{}{} ({})", indent, label, kind.name().to_lowercase())?;
+            write_items(f, items, depth + 1)
+        }
+        XmpValue::Struct(fields) => {
+            writeln!(f, "{}{}", indent, label)?;
+            for field in fields {
+                show_property(f, field, depth + 1)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn show_property(
+    f: &mut std::fmt::Formatter<'_>,
+    property: &XmpProperty,
+    depth: usize,
+) -> std::fmt::Result {
+    write_value(f, &property.qualified_name(), &property.value, depth)?;
+    if let Some(language) = &property.language {
+        writeln!(f, "{}?xml:lang = {}", "  ".repeat(depth + 1), language)?;
+    }
+    show_qualifiers(f, &property.qualifiers, depth + 1)
+}
+
+/// Qualifiers are shown below their value, marked with `?` as in XMP paths.
+fn show_qualifiers(
+    f: &mut std::fmt::Formatter<'_>,
+    qualifiers: &[XmpProperty],
+    depth: usize,
+) -> std::fmt::Result {
+    for qualifier in qualifiers {
+        write_value(
+            f,
+            &format!("?{}", qualifier.qualified_name()),
+            &qualifier.value,
+            depth,
+        )?;
+    }
+    Ok(())
+}
+
+fn write_items(
+    f: &mut std::fmt::Formatter<'_>,
+    items: &[XmpItem],
+    depth: usize,
+) -> std::fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        let label = match &item.language {
+            Some(language) => format!("[{}]", language),
+            None => format!("[{}]", i + 1),
+        };
+        write_value(f, &label, &item.value, depth)?;
+        show_qualifiers(f, &item.qualifiers, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// Properties of a description or struct node, from both attributes and child elements.
+fn parse_fields(node: roxmltree::Node) -> Vec<XmpProperty> {
+    let mut properties = vec![];
+    for attribute in node.attributes() {
+        let Some(namespace) = attribute.namespace() else {
+            continue;
+        };
+        if namespace == RDF || namespace == XML {
+            continue;
+        }
+        properties.push(XmpProperty {
+            namespace: namespace.to_string(),
+            prefix: node
+                .lookup_prefix(namespace)
+                .unwrap_or_default()
+                .to_string(),
+            name: attribute.name().to_string(),
+            language: None,
+            value: XmpValue::Text(attribute.value().to_string()),
+            qualifiers: vec![],
+        });
+    }
+    for child in node.children().filter(|child| child.is_element()) {
+        let namespace = child.tag_name().namespace().unwrap_or_default();
+        if namespace == RDF {
+            continue;
+        }
+        let (language, value, qualifiers) = parse_qualified(child);
+        properties.push(XmpProperty {
+            namespace: namespace.to_string(),
+            prefix: child
+                .lookup_prefix(namespace)
+                .unwrap_or_default()
+                .to_string(),
+            name: child.tag_name().name().to_string(),
+            language,
+            value,
+            qualifiers,
+        });
+    }
+    properties
+}
+
+/// The value of a property or array item with its `xml:lang` and qualifiers. Qualifiers are the
+/// fields next to an `rdf:value`, written inline or in a nested `rdf:Description`.
+fn parse_qualified(node: roxmltree::Node) -> (Option<String>, XmpValue, Vec<XmpProperty>) {
+    let language = node.attribute((XML, "lang")).map(str::to_string);
+    let holder = node
+        .children()
+        .find(|child| child.has_tag_name((RDF, "Description")))
+        .unwrap_or(node);
+    if let Some(value) = holder
+        .children()
+        .find(|child| child.has_tag_name((RDF, "value")))
+    {
+        let language = language.or_else(|| value.attribute((XML, "lang")).map(str::to_string));
+        return (language, parse_value(value), parse_fields(holder));
+    }
+    if let Some(value) = holder.attribute((RDF, "value")) {
+        let value = XmpValue::Text(value.to_string());
+        return (language, value, parse_fields(holder));
+    }
+    (language, parse_value(node), vec![])
+}
+
+fn parse_value(node: roxmltree::Node) -> XmpValue {
+    let children: Vec<roxmltree::Node> =
+        node.children().filter(|child| child.is_element()).collect();
+    let container = children.iter().find_map(|child| {
+        let kind = match child.tag_name().name() {
+            "Seq" => ArrayKind::Seq,
+            "Bag" => ArrayKind::Bag,
+            "Alt" => ArrayKind::Alt,
+            _ => return None,
+        };
+        (child.tag_name().namespace() == Some(RDF)).then_some((kind, *child))
+    });
+    if let Some((kind, container)) = container {
+        let items = container
+            .children()
+            .filter(|child| child.has_tag_name((RDF, "li")))
+            .map(|li| {
+                let (language, value, qualifiers) = parse_qualified(li);
+                XmpItem {
+                    language,
+                    value,
+                    qualifiers,
+                }
+            })
+            .collect();
+        return XmpValue::Array(kind, items);
+    }
+    if let Some(description) = children
+        .iter()
+        .find(|child| child.has_tag_name((RDF, "Description")))
+    {
+        return XmpValue::Struct(parse_fields(*description));
+    }
+    let has_fields = !children.is_empty()
+        || node
+            .attributes()
+            .any(|a| a.namespace().is_some_and(|ns| ns != RDF && ns != XML));
+    if has_fields {
+        return XmpValue::Struct(parse_fields(node));
+    }
+    if let Some(resource) = node.attribute((RDF, "resource")) {
+        return XmpValue::Uri(resource.to_string());
+    }
+    XmpValue::Text(
+        node.descendants()
+            .filter(|n| n.is_text())
+            .filter_map(|n| n.text())
+            .collect(),
+    )
+}
+
+fn collect_namespaces(properties: &[XmpProperty], namespaces: &mut Vec<(String, String)>) {
+    for property in properties {
+        if !namespaces
+            .iter()
+            .any(|(prefix, _)| prefix == &property.prefix)
+        {
+            namespaces.push((property.prefix.clone(), property.namespace.clone()));
+        }
+        match &property.value {
+            XmpValue::Struct(fields) => collect_namespaces(fields, namespaces),
+            XmpValue::Array(_, items) => {
+                for item in items {
+                    if let XmpValue::Struct(fields) = &item.value {
+                        collect_namespaces(fields, namespaces);
+                    }
+                    collect_namespaces(&item.qualifiers, namespaces);
+                }
+            }
+            XmpValue::Text(_) | XmpValue::Uri(_) => {}
+        }
+        collect_namespaces(&property.qualifiers, namespaces);
+    }
+}
+
+/// Gives every property a prefix bound to its namespace alone, so `to_xml` can declare them all
+/// on one element. Properties in a default namespace get its usual prefix or a generated one.
+fn bind_prefixes(properties: &mut [XmpProperty], bound: &mut Vec<(String, String)>) -> Result<()> {
+    for property in properties {
+        if property.namespace.is_empty() {
+            return Err(invalid(format!(
+                "XMP property {} has no namespace",
+                property.name
+            )));
+        }
+        match bound
+            .iter()
+            .find(|(_, namespace)| namespace == &property.namespace)
+        {
+            Some((prefix, _)) => property.prefix = prefix.clone(),
+            None => {
+                let taken = |prefix: &str| {
+                    prefix.is_empty() || bound.iter().any(|(bound, _)| bound == prefix)
+                };
+                if taken(&property.prefix) {
+                    property.prefix = KNOWN_NAMESPACES
+                        .iter()
+                        .find(|(_, namespace)| *namespace == property.namespace)
+                        .map(|(prefix, _)| prefix.to_string())
+                        .filter(|prefix| !taken(prefix))
+                        .unwrap_or_else(|| {
+                            (1..)
+                                .map(|n| format!("ns{}", n))
+                                .find(|prefix| !taken(prefix))
+                                .unwrap_or_default()
+                        });
+                }
+                bound.push((property.prefix.clone(), property.namespace.clone()));
+            }
+        }
+        match &mut property.value {
+            XmpValue::Struct(fields) => bind_prefixes(fields, bound)?,
+            XmpValue::Array(_, items) => {
+                for item in items {
+                    if let XmpValue::Struct(fields) = &mut item.value {
+                        bind_prefixes(fields, bound)?;
+                    }
+                    bind_prefixes(&mut item.qualifiers, bound)?;
+                }
+            }
+            XmpValue::Text(_) | XmpValue::Uri(_) => {}
+        }
+        bind_prefixes(&mut property.qualifiers, bound)?;
+    }
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_property(xml: &mut String, property: &XmpProperty, depth: usize) {
+    write_element(
+        xml,
+        &property.qualified_name(),
+        property.language.as_deref(),
+        &property.value,
+        &property.qualifiers,
+        depth,
+    );
+}
+
+fn write_element(
+    xml: &mut String,
+    tag: &str,
+    language: Option<&str>,
+    value: &XmpValue,
+    qualifiers: &[XmpProperty],
+    depth: usize,
+) {
+    let indent = " ".repeat(depth);
+    let lang = language
+        .map(|language| format!(" xml:lang=\"{}\"", escape(language)))
+        .unwrap_or_default();
+    if !qualifiers.is_empty() {
+        xml.push_str(&format!(
+            "{}<{}{} rdf:parseType=\"Resource\">\n",
+            indent, tag, lang
+        ));
+        write_element(xml, "rdf:value", None, value, &[], depth + 1);
+        for qualifier in qualifiers {
+            write_property(xml, qualifier, depth + 1);
+        }
+        xml.push_str(&format!("{}</{}>\n", indent, tag));
+        return;
+    }
+    match value {
+        XmpValue::Text(text) => {
+            xml.push_str(&format!(
+                "{}<{}{}>{}</{}>\n",
+                indent,
+                tag,
+                lang,
+                escape(text),
+                tag
+            ));
+        }
+        XmpValue::Uri(uri) => {
+            xml.push_str(&format!(
+                "{}<{}{} rdf:resource=\"{}\"/>\n",
+                indent,
+                tag,
+                lang,
+                escape(uri)
+            ));
+        }
+        XmpValue::Array(kind, items) => {
+            xml.push_str(&format!(
+                "{}<{}{}>\n{} <rdf:{}>\n",
+                indent,
+                tag,
+                lang,
+                indent,
+                kind.name()
+            ));
+            for item in items {
+                write_element(
+                    xml,
+                    "rdf:li",
+                    item.language.as_deref(),
+                    &item.value,
+                    &item.qualifiers,
+                    depth + 2,
+                );
+            }
+            xml.push_str(&format!(
+                "{} </rdf:{}>\n{}</{}>\n",
+                indent,
+                kind.name(),
+                indent,
+                tag
+            ));
+        }
+        XmpValue::Struct(fields) => {
+            xml.push_str(&format!(
+                "{}<{}{} rdf:parseType=\"Resource\">\n",
+                indent, tag, lang
+            ));
+            for field in fields {
+                write_property(xml, field, depth + 1);
+            }
+            xml.push_str(&format!("{}</{}>\n", indent, tag));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about=""
+        xmlns:dc="http://purl.org/dc/elements/1.1/"
+        xmlns:xmpRights="http://ns.adobe.com/xap/1.0/rights/"
+        xmlns:Iptc4xmpCore="http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/"
+        dc:format="image/png">
+      <dc:title>
+        <rdf:Alt>
+          <rdf:li xml:lang="x-default">Dice</rdf:li>
+          <rdf:li xml:lang="de">Würfel</rdf:li>
+        </rdf:Alt>
+      </dc:title>
+      <dc:creator><rdf:Seq><rdf:li>Ann</rdf:li><rdf:li>Bob</rdf:li></rdf:Seq></dc:creator>
+      <xmpRights:Marked>True</xmpRights:Marked>
+      <Iptc4xmpCore:CreatorContactInfo rdf:parseType="Resource">
+        <Iptc4xmpCore:CiEmailWork>ann@example.com</Iptc4xmpCore:CiEmailWork>
+      </Iptc4xmpCore:CreatorContactInfo>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    fn text(value: &str) -> Option<XmpValue> {
+        Some(XmpValue::Text(value.to_string()))
+    }
+
+    #[test]
+    fn test_parse_properties() {
+        let xmp = Xmp::parse(PACKET).unwrap();
+        let names: Vec<String> = xmp.properties.iter().map(|p| p.qualified_name()).collect();
+        assert_eq!(
+            names,
+            [
+                "dc:format",
+                "dc:title",
+                "dc:creator",
+                "xmpRights:Marked",
+                "Iptc4xmpCore:CreatorContactInfo"
+            ]
+        );
+        assert_eq!(
+            xmp.properties[0].namespace,
+            "http://purl.org/dc/elements/1.1/"
+        );
+    }
+
+    #[test]
+    fn test_get_paths() {
+        let xmp = Xmp::parse(PACKET).unwrap();
+        assert_eq!(xmp.get("dc:format").unwrap().cloned(), text("image/png"));
+        assert_eq!(xmp.get("dc:title[de]").unwrap().cloned(), text("Würfel"));
+        assert_eq!(xmp.get("dc:creator[2]").unwrap().cloned(), text("Bob"));
+        assert_eq!(
+            xmp.get("Iptc4xmpCore:CreatorContactInfo/Iptc4xmpCore:CiEmailWork")
+                .unwrap()
+                .cloned(),
+            text("ann@example.com")
+        );
+        assert_eq!(xmp.get("dc:creator[3]").unwrap(), None);
+        assert!(xmp.get("no-prefix").is_err());
+    }
+
+    #[test]
+    fn test_set_paths() {
+        let mut xmp = Xmp::parse(PACKET).unwrap();
+        xmp.set("dc:title", "Two dice", None).unwrap();
+        xmp.set("dc:creator[3]", "Cy", None).unwrap();
+        xmp.set("xmpRights:UsageTerms[en]", "CC-BY", None).unwrap();
+        xmp.set(
+            "Iptc4xmpCore:CreatorContactInfo/Iptc4xmpCore:CiUrlWork",
+            "https://example.com",
+            None,
+        )
+        .unwrap();
+        xmp.set("my:Flag", "yes", Some("http://example.com/ns/"))
+            .unwrap();
+
+        assert_eq!(
+            xmp.get("dc:title[x-default]").unwrap().cloned(),
+            text("Two dice")
+        );
+        assert_eq!(xmp.get("dc:title[de]").unwrap().cloned(), text("Würfel"));
+        assert_eq!(xmp.get("dc:creator[3]").unwrap().cloned(), text("Cy"));
+        assert_eq!(
+            xmp.get("xmpRights:UsageTerms[en]").unwrap().cloned(),
+            text("CC-BY")
+        );
+        assert_eq!(
+            xmp.get("Iptc4xmpCore:CreatorContactInfo/Iptc4xmpCore:CiUrlWork")
+                .unwrap()
+                .cloned(),
+            text("https://example.com")
+        );
+        assert_eq!(xmp.get("my:Flag").unwrap().cloned(), text("yes"));
+        assert!(xmp.set("unknown:Thing", "x", None).is_err());
+        assert!(xmp.set("dc:creator[9]", "x", None).is_err());
+    }
+
+    #[test]
+    fn test_remove_paths() {
+        let mut xmp = Xmp::parse(PACKET).unwrap();
+        assert_eq!(
+            xmp.remove("dc:creator[1]").unwrap(),
+            XmpValue::Text("Ann".to_string())
+        );
+        assert_eq!(xmp.get("dc:creator[1]").unwrap().cloned(), text("Bob"));
+        xmp.remove("dc:format").unwrap();
+        assert_eq!(xmp.get("dc:format").unwrap(), None);
+        assert!(xmp.remove("dc:format").is_err());
+    }
+
+    #[test]
+    fn test_xml_round_trip() {
+        let mut xmp = Xmp::parse(PACKET).unwrap();
+        xmp.set("dc:description", "a <b> & \"c\"", None).unwrap();
+        assert_eq!(Xmp::parse(&xmp.to_xml()).unwrap(), xmp);
+    }
+
+    #[test]
+    fn test_keeps_resources_languages_and_qualifiers() {
+        let packet = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+          <rdf:Description xmlns:dc="http://purl.org/dc/elements/1.1/"
+              xmlns:xmpRights="http://ns.adobe.com/xap/1.0/rights/"
+              xmlns:q="http://example.com/q/">
+            <xmpRights:WebStatement rdf:resource="https://example.com/terms"/>
+            <dc:source xml:lang="en">Archive</dc:source>
+            <dc:rights rdf:parseType="Resource">
+              <rdf:value>CC-BY</rdf:value>
+              <q:since>2020</q:since>
+            </dc:rights>
+          </rdf:Description>
+        </rdf:RDF>"#;
+        let mut xmp = Xmp::parse(packet).unwrap();
+        let source = &xmp.properties[1];
+        assert_eq!(source.language.as_deref(), Some("en"));
+        let rights = &xmp.properties[2];
+        assert_eq!(rights.value, XmpValue::Text("CC-BY".to_string()));
+        assert_eq!(rights.qualifiers[0].qualified_name(), "q:since");
+
+        let xml = xmp.to_xml();
+        assert!(
+            xml.contains(r#"<xmpRights:WebStatement rdf:resource="https://example.com/terms"/>"#)
+        );
+        assert!(xml.contains(r#"<dc:source xml:lang="en">Archive</dc:source>"#));
+        assert_eq!(Xmp::parse(&xml).unwrap(), xmp);
+
+        xmp.set("dc:rights", "CC0", None).unwrap();
+        let rights = &Xmp::parse(&xmp.to_xml()).unwrap().properties[2];
+        assert_eq!(rights.value, XmpValue::Text("CC0".to_string()));
+        assert_eq!(rights.qualifiers.len(), 1);
+        assert!(xmp
+            .to_string()
+            .contains("dc:rights = CC0\n  ?q:since = 2020\n"));
+    }
+
+    #[test]
+    fn test_binds_a_prefix_for_default_namespaces() {
+        let packet = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+          <rdf:Description>
+            <Rating xmlns="http://ns.adobe.com/xap/1.0/">5</Rating>
+            <Label xmlns="http://example.com/a/">red</Label>
+            <b:Label xmlns:b="http://example.com/b/">blue</b:Label>
+            <b:Mood xmlns:b="http://example.com/c/">calm</b:Mood>
+          </rdf:Description>
+        </rdf:RDF>"#;
+        let xmp = Xmp::parse(packet).unwrap();
+        let names: Vec<String> = xmp.properties.iter().map(|p| p.qualified_name()).collect();
+        assert_eq!(names, ["xmp:Rating", "ns1:Label", "b:Label", "ns2:Mood"]);
+        assert_eq!(Xmp::parse(&xmp.to_xml()).unwrap(), xmp);
+
+        let unbound = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+          <rdf:Description><Rating>5</Rating></rdf:Description>
+        </rdf:RDF>"#;
+        assert!(Xmp::parse(unbound).is_err());
+    }
+
+    #[test]
+    fn test_display_tree() {
+        let xmp = Xmp::parse(PACKET).unwrap();
+        let shown = xmp.to_string();
+        assert!(shown.starts_with("dc:format = image/png\ndc:title (alt)\n  [x-default] = Dice\n"));
+        assert!(shown.contains(
+            "Iptc4xmpCore:CreatorContactInfo\n  Iptc4xmpCore:CiEmailWork = ann@example.com\n"
+        ));
+        let creators = xmp.get("dc:creator").unwrap().unwrap();
+        assert_eq!(creators.to_string(), "[1] = Ann\n[2] = Bob\n");
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let xmp = Xmp::parse(PACKET).unwrap();
+        let chunk = xmp.to_chunk().unwrap();
+        assert!(Xmp::is_xmp_chunk(&chunk));
+        assert_eq!(Xmp::try_from(&chunk).unwrap(), xmp);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::Result;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+/// Deflates `data` into a zlib stream, the only compression method PNG defines.
+pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Most bytes `decompress` will inflate, so a small stream cannot exhaust memory.
+pub const MAX_INFLATED: usize = 1 << 28;
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    decompress_limited(data, MAX_INFLATED)
+}
+
+/// Inflates `data`, failing once the output would exceed `limit` bytes.
+pub fn decompress_limited(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut inflated = vec![];
+    ZlibDecoder::new(data)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut inflated)?;
+    if inflated.len() > limit {
+        return Err(Box::new(std::io::Error::other(format!(
+            "compressed data inflates to more than {} bytes",
+            limit
+        ))));
+    }
+    Ok(inflated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress_limit() {
+        let compressed = compress(&[0; 4096]).unwrap();
+        assert_eq!(decompress(&compressed).unwrap().len(), 4096);
+        assert_eq!(decompress_limited(&compressed, 4096).unwrap().len(), 4096);
+        assert!(decompress_limited(&compressed, 4095).is_err());
+    }
+}